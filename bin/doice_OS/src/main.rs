@@ -7,6 +7,7 @@ use doice_lib::{
 
 use clap::Parser;
 use doice_lib::activity_host::ActivityHost;
+use std::path::PathBuf;
 
 const LOGO: &[u8] = include_bytes!("../../../design/Logo.png");
 
@@ -18,21 +19,43 @@ const LOGO: &[u8] = include_bytes!("../../../design/Logo.png");
 struct Cli {
     #[arg(short, long)]
     big: bool,
+    /// Reads the config from this file instead of the config dir
+    #[arg(long)]
+    config: Option<PathBuf>,
 }
 
-fn gui_fullscreen_main() {
+impl Cli {
+    /// The config from the given file, or from the config dir without one
+    fn config(&self) -> Config {
+        match &self.config {
+            Some(path) => Config::load(path).unwrap_or_else(|err| {
+                eprintln!("invalid config, using the defaults instead: {err}");
+                Config::default()
+            }),
+            None => Config::load_or_default(),
+        }
+    }
+}
+
+fn gui_fullscreen_main(config: Config) {
     // Set options
+    let mut viewport = egui::ViewportBuilder::default()
+        .with_min_inner_size((320.0, 100.0))
+        .with_icon(IconData {
+            rgba: LOGO.to_vec(),
+            width: 256,
+            height: 256,
+        })
+        .with_transparent(true)
+        .with_decorations(false)
+        .with_fullscreen(false);
+    if let Some(size) = config.window {
+        viewport = viewport.with_inner_size((size.width, size.height));
+    }
     let options = eframe::NativeOptions {
-        viewport: egui::ViewportBuilder::default()
-            .with_min_inner_size((320.0, 100.0))
-            .with_icon(IconData {
-                rgba: LOGO.to_vec(),
-                width: 256,
-                height: 256,
-            })
-            .with_transparent(true)
-            .with_decorations(false)
-            .with_fullscreen(false),
+        viewport,
+        // The theme comes from the config instead
+        follow_system_theme: false,
         ..Default::default()
     };
 
@@ -41,7 +64,7 @@ fn gui_fullscreen_main() {
         "Doice OS",
         options,
         Box::new(|cc| {
-            let mut app = Box::new(DoiceApp::new(cc));
+            let mut app = Box::new(DoiceApp::new(cc, config));
             app.register_activity::<LegacyDiceRoller>();
             app.register_activity::<StarfuryYeeter>();
             app.register_activity::<DiceRoller>();
@@ -55,12 +78,12 @@ fn gui_fullscreen_main() {
     );
 }
 
-fn gui_analyzer_only() {
+fn gui_analyzer_only(config: Config) {
     // Set options
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
             .with_min_inner_size((320.0, 100.0))
-            .with_inner_size((600.0, 670.0))
+            .with_inner_size(config.window_size((600.0, 670.0)))
             .with_icon(IconData {
                 rgba: LOGO.to_vec(),
                 width: 256,
@@ -70,38 +93,46 @@ fn gui_analyzer_only() {
             .with_decorations(true)
             .with_resizable(false)
             .with_fullscreen(false),
+        follow_system_theme: false,
         ..Default::default()
     };
 
     eframe::run_native(
         "Doice Analyzer",
         options,
-        Box::new(|cc| Box::new(ActivityHost::new::<DiceRoller>(cc))),
+        Box::new(|cc| Box::new(ActivityHost::new::<DiceRoller>(cc, config))),
     );
 }
 
 fn gui_main() {
     let cli = Cli::parse();
+    let config = cli.config();
     if cli.big {
-        gui_fullscreen_main();
+        gui_fullscreen_main(config);
     } else {
-        gui_analyzer_only();
+        gui_analyzer_only(config);
     }
 }
 
 fn tailor_test_main() -> eframe::Result<()> {
+    let config = Cli::parse().config();
     // Set options
+    let mut viewport = egui::ViewportBuilder::default()
+        .with_min_inner_size((320.0, 100.0))
+        .with_icon(IconData {
+            rgba: LOGO.to_vec(),
+            width: 256,
+            height: 256,
+        })
+        .with_transparent(true)
+        .with_decorations(false)
+        .with_fullscreen(false);
+    if let Some(size) = config.window {
+        viewport = viewport.with_inner_size((size.width, size.height));
+    }
     let options = eframe::NativeOptions {
-        viewport: egui::ViewportBuilder::default()
-            .with_min_inner_size((320.0, 100.0))
-            .with_icon(IconData {
-                rgba: LOGO.to_vec(),
-                width: 256,
-                height: 256,
-            })
-            .with_transparent(true)
-            .with_decorations(false)
-            .with_fullscreen(false),
+        viewport,
+        follow_system_theme: false,
         ..Default::default()
     };
 
@@ -109,7 +140,7 @@ fn tailor_test_main() -> eframe::Result<()> {
     eframe::run_native(
         "Doice.",
         options,
-        Box::new(|cc| Box::new(TailoredUI::new(cc))),
+        Box::new(|cc| Box::new(TailoredUI::new(cc, config))),
     )
 }
 
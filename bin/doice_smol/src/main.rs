@@ -4,6 +4,7 @@ use crate::egui::IconData;
 use clap::Parser;
 use doice_lib::activities::*;
 use doice_lib::activity_host::ActivityHost;
+use std::path::PathBuf;
 
 const LOGO: &[u8] = include_bytes!("../../../design/Logo2.png");
 
@@ -12,10 +13,21 @@ const LOGO: &[u8] = include_bytes!("../../../design/Logo2.png");
 #[command(version)]
 #[command(about = "Rolls nice dice. Once, twice, or thrice")]
 #[command(long_about = None)]
-struct Cli {}
+struct Cli {
+    /// Reads the config from this file instead of the config dir
+    #[arg(long)]
+    config: Option<PathBuf>,
+}
 
 fn main() {
-    let _cli = Cli::parse();
+    let cli = Cli::parse();
+    let config = match cli.config {
+        Some(path) => Config::load(&path).unwrap_or_else(|err| {
+            eprintln!("invalid config, using the defaults instead: {err}");
+            Config::default()
+        }),
+        None => Config::load_or_default(),
+    };
 
     // Set options
     let options = eframe::NativeOptions {
@@ -29,14 +41,16 @@ fn main() {
             .with_transparent(true)
             .with_decorations(false)
             .with_fullscreen(false)
-            .with_inner_size((420.0, 516.0)),
+            .with_inner_size(config.window_size((420.0, 516.0))),
+        // The theme comes from the config instead
+        follow_system_theme: false,
         ..Default::default()
     };
 
     eframe::run_native(
         "Doice Analyzer",
         options,
-        Box::new(|cc| Box::new(ActivityHost::new::<DiceRoller>(cc))),
+        Box::new(|cc| Box::new(ActivityHost::new::<DiceRoller>(cc, config))),
     )
     .expect("Application errored out.");
 }
@@ -0,0 +1,203 @@
+use std::str::FromStr;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use doice_gui::{
+    components::{DiceHistory, DiceHistoryEntry, Initiator},
+    eframe::epaint::Color32,
+};
+use doice_roller::{with_cancel_token, CancelToken, DiceError, ProbDist, Roll, RollOut};
+use doice_utils::ParExecutor;
+
+/// The field that typing goes to, which Tab cycles through
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+pub enum Focus {
+    #[default]
+    Roll,
+    Dc,
+    Initiative,
+}
+
+impl Focus {
+    fn cycle(&mut self) {
+        *self = match *self {
+            Focus::Roll => Focus::Dc,
+            Focus::Dc => Focus::Initiative,
+            Focus::Initiative => Focus::Roll,
+        }
+    }
+}
+
+/// Mirrors the wide analyzer: a roll with its distribution, a DC, the history of rolls and an initiative list
+#[derive(Default)]
+pub struct App {
+    pub focus: Focus,
+    pub input: String,
+    pub dc_input: String,
+    /// "name initiative", where the initiative may also be a roll like d20+2
+    pub initiative_input: String,
+    roll: Roll,
+    dist_gen: ParExecutor<Result<ProbDist, DiceError>>,
+    dist_cancel: CancelToken,
+    pub loading: bool,
+    pub dist: ProbDist,
+    pub error: Option<String>,
+    pub res: Option<RollOut>,
+    pub history: DiceHistory,
+    pub initiator: Initiator,
+    pub quit: bool,
+}
+
+impl App {
+    pub fn new(dc: Option<isize>) -> Self {
+        let mut out = Self {
+            dc_input: dc.map(|dc| dc.to_string()).unwrap_or_default(),
+            ..Default::default()
+        };
+        // Make sure the chart is displaying something valid
+        out.display_roll();
+        out
+    }
+
+    pub fn dc(&self) -> Option<isize> {
+        self.dc_input.trim().parse().ok()
+    }
+
+    /// Chance in percent of rolling at least the DC
+    pub fn success_chance(&self, dc: isize) -> f64 {
+        100.0 * self.dist.range(dc..).fold(0.0, |acc, (_, prob)| acc + prob)
+    }
+
+    /// Picks up the distribution once it is computed
+    pub fn tick(&mut self) {
+        if let Some(res) = self.dist_gen.try_get_data() {
+            self.loading = false;
+            match res {
+                Ok(dist) => self.dist = dist,
+                // Errors like overflow only show up once the distribution is computed
+                Err(err) => self.error = Some(err.into()),
+            }
+        }
+    }
+
+    pub fn on_key(&mut self, key: KeyEvent) {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Esc => self.quit = true,
+            KeyCode::Char('c') if ctrl => self.quit = true,
+            KeyCode::Tab => self.focus.cycle(),
+            _ => match self.focus {
+                Focus::Roll => self.on_roll_key(key),
+                Focus::Dc => {
+                    edit(&mut self.dc_input, key);
+                }
+                Focus::Initiative => self.on_initiative_key(key, ctrl),
+            },
+        }
+    }
+
+    fn on_roll_key(&mut self, key: KeyEvent) {
+        if key.code == KeyCode::Enter {
+            self.roll();
+        } else if edit(&mut self.input, key) {
+            self.display_roll();
+        }
+    }
+
+    fn on_initiative_key(&mut self, key: KeyEvent, ctrl: bool) {
+        match key.code {
+            KeyCode::Char('s') if ctrl => self.initiator.sort(),
+            KeyCode::Char('n') if ctrl => self.initiator.next(),
+            KeyCode::Char('x') if ctrl => self.initiator.clear(),
+            KeyCode::Enter if self.initiative_input.trim().is_empty() => self.initiator.next(),
+            KeyCode::Enter => {
+                if let Err(err) = self.add_combatant() {
+                    self.error = Some(err);
+                }
+            }
+            _ => {
+                edit(&mut self.initiative_input, key);
+            }
+        }
+    }
+
+    /// Adds the combatant in the initiative input, rolling its initiative if it is a roll
+    fn add_combatant(&mut self) -> Result<(), String> {
+        let input = self.initiative_input.trim();
+        let (name, initiative) = input
+            .rsplit_once(' ')
+            .ok_or("expected a name followed by an initiative, like Goblin d20+2")?;
+        let initiative = Roll::from_str(initiative)?.try_roll_quiet()?;
+        self.initiator.add(name.trim(), initiative);
+        self.initiative_input.clear();
+        self.error = None;
+        Ok(())
+    }
+
+    /// Parses the input, and starts computing its distribution if it is valid
+    fn display_roll(&mut self) {
+        match Roll::from_str(&self.input) {
+            Ok(roll) => {
+                self.error = None;
+                self.roll = roll.clone();
+                self.loading = true;
+                self.res = None;
+                // Stop computing the distribution of the previous roll, if that is still going on
+                self.dist_cancel.cancel();
+                self.dist_cancel = CancelToken::new();
+                let cancel = self.dist_cancel.clone();
+                self.dist_gen.process_with(roll, move |roll| {
+                    with_cancel_token(&cancel, || roll.try_dist())
+                });
+            }
+            Err(err) => self.error = Some(err.into()),
+        }
+    }
+
+    fn roll(&mut self) {
+        let mut res = match self.roll.try_roll() {
+            Ok(res) => res,
+            Err(err) => {
+                self.error = Some(err.into());
+                return;
+            }
+        };
+        if res.txt.sections.len() > self.roll.limits().max_text_sections {
+            res.txt = "[...]".into();
+        }
+        res.txt.append(" = ");
+        match self.dc() {
+            Some(dc) if dc <= res.value => res
+                .txt
+                .append_colored(&res.value.to_string(), Color32::GREEN),
+            Some(_) => res.txt.append_colored(&res.value.to_string(), Color32::RED),
+            None => res.txt.append(&res.value.to_string()),
+        }
+
+        // While the distribution is loading, the luck of the roll is unknown
+        let (avg, variance) = if self.loading {
+            (f64::NAN, f64::NAN)
+        } else {
+            (self.dist.expectation(), self.dist.var())
+        };
+        self.history.add_entry(DiceHistoryEntry::new(
+            self.roll.clone(),
+            self.input.clone(),
+            res.clone(),
+            avg,
+            variance,
+        ));
+        self.res = Some(res);
+    }
+}
+
+/// Applies typing and deleting to a line of text, returning whether it changed
+fn edit(line: &mut String, key: KeyEvent) -> bool {
+    match key.code {
+        KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+            line.push(c);
+            true
+        }
+        KeyCode::Backspace => line.pop().is_some(),
+        _ => false,
+    }
+}
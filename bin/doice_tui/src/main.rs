@@ -0,0 +1,73 @@
+use std::{io, time::Duration};
+
+use clap::Parser;
+use crossterm::{
+    event::{self, Event, KeyEventKind},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use ratatui::{
+    backend::{Backend, CrosstermBackend},
+    Terminal,
+};
+
+use app::App;
+
+/// State of the analyzer and its handling of keys
+mod app;
+/// Drawing of the analyzer
+mod ui;
+
+/// How long to wait for input before redrawing, so finished distributions and the ages of rolls show up
+const TICK: Duration = Duration::from_millis(100);
+
+#[derive(Parser)]
+#[command(name = "Doice TUI")]
+#[command(version)]
+#[command(about = "Rolls nice dice in the terminal, for when no window can be opened")]
+#[command(long_about = None)]
+struct Cli {
+    /// DC to show the chance of success against
+    #[arg(long)]
+    dc: Option<isize>,
+}
+
+fn main() -> io::Result<()> {
+    let cli = Cli::parse();
+    let mut app = App::new(cli.dc);
+
+    // Leave the terminal usable if anything goes wrong
+    let hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let _ = restore_terminal();
+        hook(info);
+    }));
+
+    enable_raw_mode()?;
+    execute!(io::stdout(), EnterAlternateScreen)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+    let res = run(&mut terminal, &mut app);
+    restore_terminal()?;
+    terminal.show_cursor()?;
+    res
+}
+
+fn run<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<()> {
+    while !app.quit {
+        app.tick();
+        terminal.draw(|frame| ui::draw(frame, app))?;
+        if event::poll(TICK)? {
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press {
+                    app.on_key(key);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn restore_terminal() -> io::Result<()> {
+    disable_raw_mode()?;
+    execute!(io::stdout(), LeaveAlternateScreen)
+}
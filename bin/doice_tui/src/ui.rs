@@ -0,0 +1,245 @@
+use doice_gui::eframe::epaint::Color32;
+use doice_roller::{Layouter, Value};
+use ratatui::{
+    prelude::*,
+    widgets::{
+        block::Title, Bar, BarChart, BarGroup, Block, Borders, List, ListItem, Paragraph, Wrap,
+    },
+};
+
+use crate::app::{App, Focus};
+
+/// Bars are never wider than this, however few outcomes there are
+const MAX_BAR_WIDTH: u16 = 5;
+const HELP: &str =
+    "Tab: next field | Enter: roll / add / next turn | Ctrl-S: sort | Ctrl-N: next | Ctrl-X: clear | Esc: quit";
+
+pub fn draw(frame: &mut Frame, app: &App) {
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(3),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .split(frame.size());
+    let top = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Min(0), Constraint::Length(12)])
+        .split(rows[0]);
+    let main = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(65), Constraint::Percentage(35)])
+        .split(rows[1]);
+    let left = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(0), Constraint::Length(4)])
+        .split(main[0]);
+    let right = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+        .split(main[1]);
+
+    draw_input(frame, app, top[0], Focus::Roll, " Roll ", &app.input);
+    draw_input(frame, app, top[1], Focus::Dc, " DC ", &app.dc_input);
+    draw_chart(frame, app, left[0]);
+    draw_stats(frame, app, left[1]);
+    draw_history(frame, app, right[0]);
+    draw_initiative(frame, app, right[1]);
+
+    let status = match &app.error {
+        Some(err) => Span::styled(err.as_str(), Style::default().fg(Color::Red)),
+        None => Span::styled(HELP, Style::default().fg(Color::DarkGray)),
+    };
+    frame.render_widget(Paragraph::new(status), rows[2]);
+}
+
+/// A bordered block, highlighted if typing goes to it
+fn block(app: &App, focus: Focus, title: &str) -> Block<'static> {
+    let style = if app.focus == focus {
+        Style::default().fg(Color::Yellow)
+    } else {
+        Style::default()
+    };
+    Block::default()
+        .borders(Borders::ALL)
+        .border_style(style)
+        .title(title.to_string())
+}
+
+fn draw_input(frame: &mut Frame, app: &App, area: Rect, focus: Focus, title: &str, txt: &str) {
+    frame.render_widget(Paragraph::new(txt).block(block(app, focus, title)), area);
+    if app.focus == focus {
+        frame.set_cursor(area.x + 1 + txt.chars().count() as u16, area.y + 1);
+    }
+}
+
+fn draw_chart(frame: &mut Frame, app: &App, area: Rect) {
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(" Probability Distribution ");
+    let (Some(min), Some(max)) = (app.dist.min(), app.dist.max()) else {
+        frame.render_widget(block, area);
+        return;
+    };
+    if app.loading {
+        frame.render_widget(Paragraph::new("Loading...").block(block), area);
+        return;
+    }
+
+    // Neighbouring outcomes share a bar if there are more outcomes than fit
+    let inner = block.inner(area);
+    let outcomes = max.abs_diff(min).saturating_add(1);
+    let max_bars = usize::from(inner.width / 2).max(1);
+    let bucket = outcomes.div_ceil(max_bars);
+    let bar_count = outcomes.div_ceil(bucket);
+    let bar_width = (inner.width / bar_count as u16)
+        .saturating_sub(1)
+        .clamp(1, MAX_BAR_WIDTH);
+
+    let dc = app.dc();
+    let rolled = app.res.as_ref().map(|res| res.value);
+    let bars: Vec<Bar> = (0..bar_count)
+        .map(|i| {
+            let start = min.saturating_add((i * bucket) as Value);
+            let end = start.saturating_add(bucket as Value - 1).min(max);
+            let prob = app
+                .dist
+                .range(start..=end)
+                .fold(0.0, |acc, (_, prob)| acc + prob);
+            let color = match (rolled, dc) {
+                (Some(value), _) if (start..=end).contains(&value) => Color::Yellow,
+                (_, Some(dc)) if end < dc => Color::Red,
+                (_, Some(_)) => Color::Green,
+                _ => Color::LightBlue,
+            };
+            Bar::default()
+                .value((prob * 10_000.0).round() as u64)
+                .text_value(String::new())
+                .label(Line::from(start.to_string()))
+                .style(Style::default().fg(color))
+        })
+        .collect();
+
+    let chart = BarChart::default()
+        .block(block)
+        .bar_width(bar_width)
+        .bar_gap(1)
+        .data(BarGroup::default().bars(&bars));
+    frame.render_widget(chart, area);
+}
+
+fn draw_stats(frame: &mut Frame, app: &App, area: Rect) {
+    let mut info = if app.dist.is_empty() || app.loading {
+        String::new()
+    } else {
+        format!(
+            "average = {:.3}   deviation = {:.3}",
+            app.dist.expectation(),
+            app.dist.sigma()
+        )
+    };
+    if let (Some(dc), false) = (app.dc(), info.is_empty()) {
+        info += &format!("   success = {:.2}%", app.success_chance(dc));
+    }
+    if let Some(approx) = app.dist.approximation() {
+        info += &format!("   approximate (±{:.1}%)", approx.max_error * 100.0);
+    }
+
+    let mut lines = vec![Line::from(info)];
+    if let Some(res) = &app.res {
+        lines.push(Line::from(spans(&res.txt)));
+    }
+    let stats = Paragraph::new(lines)
+        .wrap(Wrap { trim: true })
+        .block(Block::default().borders(Borders::ALL));
+    frame.render_widget(stats, area);
+}
+
+fn draw_history(frame: &mut Frame, app: &App, area: Rect) {
+    let luck = app.history.luck();
+    let luck_color = match luck.total_cmp(&0.0) {
+        std::cmp::Ordering::Less => Color::Red,
+        std::cmp::Ordering::Equal => Color::Gray,
+        std::cmp::Ordering::Greater => Color::Green,
+    };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(" History ")
+        .title(
+            Title::from(Span::styled(
+                format!(" Session Luck: {luck:.3} "),
+                Style::default().fg(luck_color),
+            ))
+            .alignment(Alignment::Right),
+        );
+
+    // Like the gui, stick to the newest rolls
+    let height = usize::from(block.inner(area).height);
+    let entries = app.history.entries();
+    let items: Vec<ListItem> = entries[entries.len().saturating_sub(height)..]
+        .iter()
+        .map(|entry| {
+            let mut line = vec![Span::raw(entry.src().to_string()), Span::raw(" -> ")];
+            line.extend(spans(&entry.result().txt));
+            line.push(Span::styled(
+                format!("  {}", entry.age()),
+                Style::default().fg(Color::DarkGray),
+            ));
+            ListItem::new(Line::from(line))
+        })
+        .collect();
+    frame.render_widget(List::new(items).block(block), area);
+}
+
+fn draw_initiative(frame: &mut Frame, app: &App, area: Rect) {
+    let block = block(app, Focus::Initiative, " Initiative ");
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Min(0)])
+        .split(inner);
+
+    let prompt = format!("+ {}", app.initiative_input);
+    if app.focus == Focus::Initiative {
+        frame.set_cursor(rows[0].x + prompt.chars().count() as u16, rows[0].y);
+    }
+    frame.render_widget(
+        Paragraph::new(prompt).style(Style::default().fg(Color::DarkGray)),
+        rows[0],
+    );
+
+    let current = app.initiator.current();
+    let items: Vec<ListItem> = app
+        .initiator
+        .entries()
+        .map(|(name, initiative)| {
+            let style = if Some(initiative) == current {
+                Style::default().add_modifier(Modifier::BOLD | Modifier::REVERSED)
+            } else {
+                Style::default()
+            };
+            ListItem::new(format!("{initiative:>3}  {name}")).style(style)
+        })
+        .collect();
+    frame.render_widget(List::new(items), rows[1]);
+}
+
+/// The text of a roll, with its colors and strikethrough
+fn spans(txt: &Layouter) -> Vec<Span<'static>> {
+    txt.sections
+        .iter()
+        .map(|(section, format)| {
+            let mut style = Style::default();
+            if format.color != Color32::GRAY {
+                let [r, g, b, _] = format.color.to_array();
+                style = style.fg(Color::Rgb(r, g, b));
+            }
+            if format.strikethrough.width > 0.0 {
+                style = style.add_modifier(Modifier::CROSSED_OUT);
+            }
+            Span::styled(section.clone(), style)
+        })
+        .collect()
+}
@@ -20,7 +20,7 @@ pub fn start(canvas_id: &str) -> Result<(), eframe::wasm_bindgen::JsValue> {
     eframe::start_web(
         canvas_id,
         Box::new(|cc| {
-            let mut app = Box::new(DoiceApp::new(cc));
+            let mut app = Box::new(DoiceApp::new(cc, Config::default()));
             app.register_activity::<LegacyDiceRoller>();
             app.register_activity::<StarfuryYeeter>();
             app.register_activity::<DiceRoller>();
@@ -0,0 +1,86 @@
+/// Rolls that are listed by /history without a count
+const DEFAULT_HISTORY: usize = 5;
+
+/// A command given in a chat message
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Command {
+    /// /r 2d20kh1+5 # attack, rolls an expression with an optional label
+    Roll { expr: String, label: Option<String> },
+    /// /dist 4d6kh3, the statistics of an expression
+    Dist { expr: String },
+    /// /history 10, the latest rolls in the channel
+    History { count: usize },
+    /// /def attack 2d20kh1+5, which can then be rolled as $attack
+    Define { name: String, expr: String },
+    /// /undef attack
+    Undefine { name: String },
+    /// /macros, lists the macros of the channel
+    Macros,
+    /// /help
+    Help,
+}
+
+impl Command {
+    pub const HELP: &'static str = "\
+`/r 2d20kh1+5 # attack` rolls an expression, with an optional label after #
+`/dist 4d6kh3` shows the average and deviation of an expression
+`/history 10` lists the latest rolls in this channel
+`/def attack 2d20kh1+5` defines a macro, to be rolled like `/r $attack + 2`
+`/undef attack` removes a macro, and `/macros` lists them";
+
+    /// Parses a chat message, giving None if it is not meant for the bot
+    pub fn parse(text: &str) -> Option<Result<Self, String>> {
+        let text = text.trim().strip_prefix('/')?;
+        let (name, args) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+        let args = args.trim();
+        Some(match name {
+            "r" | "roll" => match args.split_once('#') {
+                Some((expr, label)) => Ok(Command::Roll {
+                    expr: expr.trim().to_string(),
+                    label: Some(label.trim().to_string()).filter(|label| !label.is_empty()),
+                }),
+                None => Ok(Command::Roll {
+                    expr: args.to_string(),
+                    label: None,
+                }),
+            },
+            "dist" => Ok(Command::Dist {
+                expr: args.to_string(),
+            }),
+            "history" => match args {
+                "" => Ok(Command::History {
+                    count: DEFAULT_HISTORY,
+                }),
+                count => count
+                    .parse()
+                    .map(|count| Command::History { count })
+                    .map_err(|_| format!("history: invalid count {count}")),
+            },
+            "def" => {
+                let (name, expr) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+                let name = name.trim_start_matches('$');
+                if name.is_empty() || !name.chars().all(is_name_char) {
+                    Err("def: macro names consist of letters, digits and _".into())
+                } else if expr.trim().is_empty() {
+                    Err("def: expected a name and an expression, like /def attack 1d20+5".into())
+                } else {
+                    Ok(Command::Define {
+                        name: name.to_string(),
+                        expr: expr.trim().to_string(),
+                    })
+                }
+            }
+            "undef" => Ok(Command::Undefine {
+                name: args.trim_start_matches('$').to_string(),
+            }),
+            "macros" => Ok(Command::Macros),
+            "help" => Ok(Command::Help),
+            // Other bots may answer other commands
+            _ => return None,
+        })
+    }
+}
+
+pub(crate) fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
@@ -0,0 +1,46 @@
+use doice_roller::Layouter;
+
+/// Characters that would otherwise be taken for Markdown formatting
+const SPECIAL: &[char] = &['\\', '*', '_', '~', '`', '|', '>'];
+
+/// Writes the text of a roll as Markdown, with the struck through parts, like dropped dice, in ~~strikethrough~~
+pub fn markdown(txt: &Layouter) -> String {
+    let mut out = String::new();
+    let mut struck = String::new();
+    for (section, format) in &txt.sections {
+        if format.strikethrough.width > 0.0 {
+            struck += section;
+            continue;
+        }
+        strike(&mut out, &struck);
+        struck.clear();
+        out += &escape(section);
+    }
+    strike(&mut out, &struck);
+    out
+}
+
+/// Appends txt in strikethrough, keeping surrounding whitespace outside of the markers so they are recognized
+fn strike(out: &mut String, txt: &str) {
+    let trimmed = txt.trim();
+    if trimmed.is_empty() {
+        *out += txt;
+        return;
+    }
+    let start = txt.len() - txt.trim_start().len();
+    let end = start + trimmed.len();
+    *out += &txt[..start];
+    *out += &format!("~~{}~~", escape(trimmed));
+    *out += &txt[end..];
+}
+
+fn escape(txt: &str) -> String {
+    let mut out = String::with_capacity(txt.len());
+    for c in txt.chars() {
+        if SPECIAL.contains(&c) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
@@ -0,0 +1,255 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use doice_roller::{EvalLimits, Roll, Value};
+
+/// Parsing of the commands in chat messages
+mod command;
+pub use command::Command;
+
+/// Formatting of rolls as Markdown
+mod format;
+pub use format::markdown;
+
+#[cfg(test)]
+mod test;
+
+/// Each channel remembers at most this many rolls
+const MAX_HISTORY: usize = 100;
+/// Replies are kept below the message size limit of common chat services
+const MAX_REPLY: usize = 1900;
+/// Expressions may grow to at most this many bytes when their macros are expanded,
+/// as macros referring to each other several times grow exponentially
+const MAX_EXPANSION: usize = 1000;
+
+/// A chat message, as received from whatever chat service the bot is connected to
+#[derive(Clone, Debug)]
+pub struct Incoming {
+    pub channel: String,
+    pub author: String,
+    pub text: String,
+}
+
+/// The connection to a chat service
+pub trait Transport {
+    /// Waits for the next message, or gives None once the connection is closed
+    fn receive(&mut self) -> Option<Incoming>;
+
+    /// Posts a message to the channel
+    fn send(&mut self, channel: &str, text: &str);
+}
+
+/// A roll made in a channel
+#[derive(Clone, Debug)]
+pub struct HistoryEntry {
+    pub author: String,
+    pub label: Option<String>,
+    pub expr: String,
+    pub value: Value,
+}
+
+#[derive(Default)]
+struct Channel {
+    history: VecDeque<HistoryEntry>,
+    /// Expressions by name, already expanded so that macros never refer to other macros
+    macros: BTreeMap<String, String>,
+}
+
+/// Answers dice commands like "/r 2d20kh1+5 # attack", independent of the chat service
+#[derive(Default)]
+pub struct Bot {
+    limits: EvalLimits,
+    channels: HashMap<String, Channel>,
+}
+
+impl Bot {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a bot whose rolls are evaluated within the given limits
+    #[must_use]
+    pub fn with_limits(limits: EvalLimits) -> Self {
+        Bot {
+            limits,
+            ..Default::default()
+        }
+    }
+
+    /// Answers messages until the transport is closed
+    pub fn run(&mut self, transport: &mut impl Transport) {
+        while let Some(msg) = transport.receive() {
+            if let Some(reply) = self.handle(&msg) {
+                transport.send(&msg.channel, &reply);
+            }
+        }
+    }
+
+    /// Answers a single message, giving None for messages that are not commands
+    pub fn handle(&mut self, msg: &Incoming) -> Option<String> {
+        let reply = match Command::parse(&msg.text)? {
+            Ok(command) => self.execute(command, msg),
+            Err(err) => Err(err),
+        };
+        let reply = match reply {
+            Ok(reply) => reply,
+            Err(err) => format!("**{}**: {err}", msg.author),
+        };
+        Some(shorten(reply))
+    }
+
+    /// The rolls made in a channel, from oldest to newest
+    pub fn history(&self, channel: &str) -> impl Iterator<Item = &HistoryEntry> {
+        self.channels
+            .get(channel)
+            .into_iter()
+            .flat_map(|channel| channel.history.iter())
+    }
+
+    fn execute(&mut self, command: Command, msg: &Incoming) -> Result<String, String> {
+        let limits = self.limits;
+        let channel = self.channels.entry(msg.channel.clone()).or_default();
+        match command {
+            Command::Roll { expr, label } => {
+                let expr = channel.expand(&expr)?;
+                let roll = Roll::parse_with_limits(&expr, limits)?;
+                let out = roll.try_roll()?;
+                let mut txt = if out.txt.sections.len() > limits.max_text_sections {
+                    "[...]".to_string()
+                } else {
+                    markdown(&out.txt)
+                };
+
+                let name = label
+                    .as_ref()
+                    .map_or(String::new(), |label| format!(" {label}"));
+                let mut reply = format!(
+                    "**{}**{name}: `{expr}` → {txt} = **{}**",
+                    msg.author, out.value
+                );
+                if reply.len() > MAX_REPLY {
+                    txt = "[...]".to_string();
+                    reply = format!(
+                        "**{}**{name}: `{expr}` → {txt} = **{}**",
+                        msg.author, out.value
+                    );
+                }
+
+                channel.history.push_back(HistoryEntry {
+                    author: msg.author.clone(),
+                    label,
+                    expr,
+                    value: out.value,
+                });
+                if channel.history.len() > MAX_HISTORY {
+                    channel.history.pop_front();
+                }
+                Ok(reply)
+            }
+            Command::Dist { expr } => {
+                let expr = channel.expand(&expr)?;
+                let dist = Roll::parse_with_limits(&expr, limits)?.try_dist()?;
+                let (Some(min), Some(max)) = (dist.min(), dist.max()) else {
+                    return Err("the expression has no outcomes".into());
+                };
+                Ok(format!(
+                    "`{expr}`: average {:.2}, deviation {:.2}, from {min} to {max}",
+                    dist.expectation(),
+                    dist.sigma()
+                ))
+            }
+            Command::History { count } => {
+                if channel.history.is_empty() {
+                    return Ok("No rolls yet".into());
+                }
+                // The newest rolls are kept when they do not all fit in a reply
+                let mut lines = VecDeque::new();
+                let mut len = 0;
+                for entry in channel.history.iter().rev().take(count) {
+                    let label = entry
+                        .label
+                        .as_deref()
+                        .map_or(String::new(), |label| format!(" {label}"));
+                    let line = format!(
+                        "**{}**{label}: `{}` = **{}**",
+                        entry.author, entry.expr, entry.value
+                    );
+                    len += line.len() + 1;
+                    if len > MAX_REPLY && !lines.is_empty() {
+                        break;
+                    }
+                    lines.push_front(line);
+                }
+                Ok(Vec::from(lines).join("\n"))
+            }
+            Command::Define { name, expr } => {
+                let expr = channel.expand(&expr)?;
+                Roll::parse_with_limits(&expr, limits)?;
+                let reply = format!("Defined `${name}` as `{expr}`");
+                channel.macros.insert(name, expr);
+                Ok(reply)
+            }
+            Command::Undefine { name } => match channel.macros.remove(&name) {
+                Some(_) => Ok(format!("Removed `${name}`")),
+                None => Err(format!("there is no macro ${name}")),
+            },
+            Command::Macros => {
+                if channel.macros.is_empty() {
+                    return Ok("No macros yet, define one like /def attack 1d20+5".into());
+                }
+                Ok(channel
+                    .macros
+                    .iter()
+                    .map(|(name, expr)| format!("`${name}` = `{expr}`"))
+                    .collect::<Vec<_>>()
+                    .join("\n"))
+            }
+            Command::Help => Ok(Command::HELP.into()),
+        }
+    }
+}
+
+impl Channel {
+    /// Replaces every $name in src by the expression of the macro in parentheses
+    fn expand(&self, src: &str) -> Result<String, String> {
+        let mut out = String::new();
+        let mut rest = src;
+        while let Some(start) = rest.find('$') {
+            out += &rest[..start];
+            let name_len = rest[start + 1..]
+                .find(|c: char| !command::is_name_char(c))
+                .unwrap_or(rest.len() - start - 1);
+            let name = &rest[start + 1..start + 1 + name_len];
+            let expr = self
+                .macros
+                .get(name)
+                .ok_or_else(|| format!("there is no macro ${name}"))?;
+            out += &format!("({expr})");
+            rest = &rest[start + 1 + name_len..];
+            if out.len() + rest.len() > MAX_EXPANSION {
+                return Err(format!(
+                    "the expression grows beyond {MAX_EXPANSION} characters when expanding its macros"
+                ));
+            }
+        }
+        out += rest;
+        Ok(out)
+    }
+}
+
+/// Cuts a reply down to MAX_REPLY bytes, at the end of a line where possible
+fn shorten(mut reply: String) -> String {
+    const ELLIPSIS: &str = "\n[...]";
+    if reply.len() <= MAX_REPLY {
+        return reply;
+    }
+    let mut end = MAX_REPLY - ELLIPSIS.len();
+    while !reply.is_char_boundary(end) {
+        end -= 1;
+    }
+    if let Some(line_end) = reply[..end].rfind('\n') {
+        end = line_end;
+    }
+    reply.truncate(end);
+    reply + ELLIPSIS
+}
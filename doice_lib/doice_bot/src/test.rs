@@ -0,0 +1,136 @@
+use doice_roller::Layouter;
+
+use super::*;
+
+/// An in-process chat service, which delivers queued messages and keeps what the bot sends
+#[derive(Default)]
+struct FakeTransport {
+    incoming: VecDeque<Incoming>,
+    sent: Vec<(String, String)>,
+}
+
+impl FakeTransport {
+    fn say(&mut self, channel: &str, author: &str, text: &str) {
+        self.incoming.push_back(Incoming {
+            channel: channel.into(),
+            author: author.into(),
+            text: text.into(),
+        });
+    }
+}
+
+impl Transport for FakeTransport {
+    fn receive(&mut self) -> Option<Incoming> {
+        self.incoming.pop_front()
+    }
+
+    fn send(&mut self, channel: &str, text: &str) {
+        self.sent.push((channel.into(), text.into()));
+    }
+}
+
+#[test]
+fn command_test() {
+    assert_eq!(
+        Command::parse("/r 2d20kh1+5 # attack"),
+        Some(Ok(Command::Roll {
+            expr: "2d20kh1+5".into(),
+            label: Some("attack".into())
+        }))
+    );
+    assert_eq!(
+        Command::parse("/roll d6"),
+        Some(Ok(Command::Roll {
+            expr: "d6".into(),
+            label: None
+        }))
+    );
+    assert_eq!(
+        Command::parse("/history"),
+        Some(Ok(Command::History { count: 5 }))
+    );
+    assert!(matches!(Command::parse("/def x"), Some(Err(_))));
+    assert!(matches!(Command::parse("/def a-b d6"), Some(Err(_))));
+    assert_eq!(Command::parse("hello there"), None);
+    assert_eq!(Command::parse("/kick bob"), None);
+}
+
+#[test]
+fn transport_test() {
+    let mut transport = FakeTransport::default();
+    transport.say("tavern", "alice", "/r 2d20kh1+5 # attack");
+    transport.say("tavern", "bob", "just chatting");
+    transport.say("tavern", "bob", "/def sword 1d8+3");
+    transport.say("tavern", "bob", "/r $sword * 2 # crit");
+    transport.say("dungeon", "carol", "/r $sword");
+    transport.say("dungeon", "carol", "/r d6/0");
+    transport.say("tavern", "alice", "/history");
+    doice_roller::seed(3);
+    Bot::new().run(&mut transport);
+
+    let sent = transport.sent;
+    // Chatter is left alone
+    assert_eq!(sent.len(), 6);
+    assert!(sent.iter().take(3).all(|(channel, _)| channel == "tavern"));
+
+    let (_, attack) = &sent[0];
+    assert!(
+        attack.starts_with("**alice** attack: `2d20kh1+5` → "),
+        "{attack}"
+    );
+    // The dropped die is struck through
+    assert_eq!(attack.matches("~~").count(), 2, "{attack}");
+
+    assert!(sent[2].1.contains("`(1d8+3) * 2`"), "{}", sent[2].1);
+    // Macros and history belong to a channel
+    assert!(sent[3].1.contains("no macro $sword"), "{}", sent[3].1);
+    assert!(sent[4].1.contains("division by zero"), "{}", sent[4].1);
+    let history = &sent[5].1;
+    assert_eq!(history.lines().count(), 2, "{history}");
+    assert!(history.contains("**bob** crit"), "{history}");
+}
+
+#[test]
+fn markdown_test() {
+    let mut txt = Layouter::new();
+    txt.append("[");
+    txt.append_strikethrough("3");
+    txt.append(", 17] ");
+    txt.append_strikethrough("2, ");
+    txt.append_strikethrough("4");
+    txt.append(" * 2");
+    assert_eq!(markdown(&txt), "[~~3~~, 17] ~~2, 4~~ \\* 2");
+}
+
+#[test]
+fn limits_test() {
+    let mut transport = FakeTransport::default();
+    transport.say("tavern", "bob", "/def a d6");
+    for (name, prev) in ["b", "c", "d", "e", "f", "g", "h", "i"]
+        .iter()
+        .zip("abcdefgh".chars())
+    {
+        transport.say("tavern", "bob", &format!("/def {name} ${prev}+${prev}"));
+    }
+    transport.say(
+        "tavern",
+        "bob",
+        "/def long 1+1+1+1+1+1+1+1+1+1+1+1+1+1+1+1+1+1+1+1",
+    );
+    for i in 0..120 {
+        transport.say("tavern", "bob", &format!("/r $long # roll {i}"));
+    }
+    transport.say("tavern", "bob", "/history 100");
+    Bot::new().run(&mut transport);
+
+    let sent = transport.sent;
+    // Doubling macros are cut off once they grow too long
+    assert!(sent[5].1.starts_with("Defined"), "{}", sent[5].1);
+    assert!(sent[8].1.contains("beyond"), "{}", sent[8].1);
+    assert!(sent.iter().all(|(_, reply)| reply.len() <= MAX_REPLY));
+    let history = &sent.last().unwrap().1;
+    assert!(
+        history.ends_with("roll 119: `(1+1+1+1+1+1+1+1+1+1+1+1+1+1+1+1+1+1+1+1)` = **20**"),
+        "{history}"
+    );
+}
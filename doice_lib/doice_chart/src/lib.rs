@@ -0,0 +1,264 @@
+use std::{fmt::Write, fs, path::Path};
+
+use doice_roller::{ProbDist, Value};
+
+#[cfg(test)]
+mod test;
+
+const MARGIN: f64 = 20.0;
+/// Room for the labels of an axis
+const AXIS_MARGIN: f64 = 50.0;
+const FONT: &str = r#"font-family="sans-serif" font-size="12""#;
+/// Same as the bars in the gui
+const BAR_COLOR: &str = "#add8e6";
+const DC_COLOR: &str = "#404040";
+const CUMULATIVE_COLOR: &str = "#e07000";
+
+/// What to draw besides the distribution itself
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChartOptions {
+    pub width: u32,
+    pub height: u32,
+    pub title: Option<String>,
+    /// Draws the chance to roll at least each outcome as a line, with its own axis on the right
+    pub cumulative: bool,
+    /// Marks the DC with a vertical line, labelled with the chance to succeed
+    pub dc: Option<Value>,
+}
+
+impl Default for ChartOptions {
+    fn default() -> Self {
+        ChartOptions {
+            width: 800,
+            height: 500,
+            title: None,
+            cumulative: false,
+            dc: None,
+        }
+    }
+}
+
+/// Draws the distribution as a bar chart in svg
+#[must_use]
+pub fn svg(dist: &ProbDist, options: &ChartOptions) -> String {
+    let (width, height) = (f64::from(options.width), f64::from(options.height));
+    let left = AXIS_MARGIN;
+    let right = width
+        - if options.cumulative {
+            AXIS_MARGIN
+        } else {
+            MARGIN
+        };
+    let top = if options.title.is_some() {
+        2.0 * MARGIN
+    } else {
+        MARGIN
+    };
+    let bottom = height - AXIS_MARGIN;
+
+    let mut out = String::new();
+    writeln!(
+        out,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}">"#
+    )
+    .unwrap();
+    writeln!(out, r#"<rect width="100%" height="100%" fill="white"/>"#).unwrap();
+    if let Some(title) = &options.title {
+        writeln!(
+            out,
+            r#"<text x="{}" y="{}" text-anchor="middle" {FONT} font-size="16">{}</text>"#,
+            width / 2.0,
+            MARGIN + 4.0,
+            escape(title)
+        )
+        .unwrap();
+    }
+
+    let (Some(min), Some(max)) = (dist.min(), dist.max()) else {
+        out += "</svg>\n";
+        return out;
+    };
+
+    // Neighbouring outcomes share a bar if there are more outcomes than pixels to draw them
+    let outcomes = max.abs_diff(min).saturating_add(1);
+    let bucket = outcomes.div_ceil(((right - left) / 2.0).max(1.0) as usize);
+    let bars: Vec<(Value, Value, f64)> = (0..outcomes.div_ceil(bucket))
+        .map(|i| {
+            let start = min.saturating_add((i * bucket) as Value);
+            let end = start.saturating_add(bucket as Value - 1).min(max);
+            let prob = dist
+                .range(start..=end)
+                .fold(0.0, |acc, (_, prob)| acc + prob);
+            (start, end, prob)
+        })
+        .collect();
+    let peak = bars.iter().map(|(_, _, prob)| *prob).fold(0.0, f64::max) * 1.1;
+
+    let x = |outcome: f64| left + (outcome - min as f64 + 0.5) / outcomes as f64 * (right - left);
+    let y = |prob: f64| bottom - prob / peak * (bottom - top);
+
+    // Axes with their ticks
+    writeln!(
+        out,
+        r#"<path d="M{left} {top} V{bottom} H{right}" stroke="black" fill="none"/>"#
+    )
+    .unwrap();
+    let step = tick_step(peak, 5.0);
+    for i in 0..=(peak / step) as usize {
+        let prob = i as f64 * step;
+        writeln!(
+            out,
+            r#"<text x="{}" y="{}" text-anchor="end" {FONT}>{}%</text>"#,
+            left - 4.0,
+            y(prob) + 4.0,
+            round_percent(prob, step)
+        )
+        .unwrap();
+    }
+    let step = tick_step(outcomes as f64, 10.0).max(1.0) as Value;
+    let first = min.div_euclid(step).saturating_mul(step);
+    for outcome in (first..=max).step_by(step as usize).filter(|o| *o >= min) {
+        writeln!(
+            out,
+            r#"<text x="{}" y="{}" text-anchor="middle" {FONT}>{outcome}</text>"#,
+            x(outcome as f64),
+            bottom + 16.0
+        )
+        .unwrap();
+    }
+
+    for (start, end, prob) in &bars {
+        let (x0, x1) = (x(*start as f64 - 0.5), x(*end as f64 + 0.5));
+        writeln!(
+            out,
+            r#"<rect x="{x0:.2}" y="{:.2}" width="{:.2}" height="{:.2}" fill="{BAR_COLOR}" stroke="white" stroke-width="{}"/>"#,
+            y(*prob),
+            x1 - x0,
+            bottom - y(*prob),
+            if bucket == 1 && outcomes < 200 { 1 } else { 0 }
+        )
+        .unwrap();
+    }
+
+    if options.cumulative {
+        let cumulative = dist.get_rev_cumulative_prob();
+        let y = |prob: f64| bottom - prob * (bottom - top);
+        let points = cumulative
+            .iter()
+            .map(|(outcome, prob)| format!("{:.2},{:.2}", x(*outcome as f64), y(*prob)))
+            .collect::<Vec<_>>()
+            .join(" ");
+        writeln!(
+            out,
+            r#"<polyline points="{points}" fill="none" stroke="{CUMULATIVE_COLOR}" stroke-width="2"/>"#
+        )
+        .unwrap();
+        writeln!(
+            out,
+            r#"<path d="M{right} {top} V{bottom}" stroke="{CUMULATIVE_COLOR}" fill="none"/>"#
+        )
+        .unwrap();
+        for i in 0..=4 {
+            let prob = f64::from(i) / 4.0;
+            writeln!(
+                out,
+                r#"<text x="{}" y="{}" {FONT} fill="{CUMULATIVE_COLOR}">{}%</text>"#,
+                right + 4.0,
+                y(prob) + 4.0,
+                100.0 * prob
+            )
+            .unwrap();
+        }
+    }
+
+    if let Some(dc) = options.dc {
+        let success = dist.range(dc..).fold(0.0, |acc, (_, prob)| acc + prob);
+        let dc_x = x(dc as f64 - 0.5).clamp(left, right);
+        writeln!(
+            out,
+            r#"<path d="M{dc_x:.2} {top} V{bottom}" stroke="{DC_COLOR}" stroke-width="2.5" stroke-dasharray="6 4"/>"#
+        )
+        .unwrap();
+        writeln!(
+            out,
+            r#"<text x="{:.2}" y="{}" {FONT} fill="{DC_COLOR}">DC {dc}: {:.1}%</text>"#,
+            dc_x + 4.0,
+            top + 12.0,
+            100.0 * success
+        )
+        .unwrap();
+    }
+
+    out += "</svg>\n";
+    out
+}
+
+/// Draws the distribution like `svg`, rasterized to png
+#[cfg(feature = "png")]
+pub fn png(dist: &ProbDist, options: &ChartOptions) -> Result<Vec<u8>, String> {
+    use resvg::{
+        tiny_skia::{Pixmap, Transform},
+        usvg::{self, fontdb, TreeParsing, TreeTextToPath},
+    };
+
+    let mut tree = usvg::Tree::from_str(&svg(dist, options), &usvg::Options::default())
+        .map_err(|err| format!("chart: {err}"))?;
+    let mut fonts = fontdb::Database::new();
+    fonts.load_system_fonts();
+    tree.convert_text(&fonts);
+
+    let mut pixmap = Pixmap::new(options.width, options.height)
+        .ok_or("chart: the chart must be at least 1 by 1 pixels")?;
+    resvg::Tree::from_usvg(&tree).render(Transform::default(), &mut pixmap.as_mut());
+    pixmap.encode_png().map_err(|err| format!("chart: {err}"))
+}
+
+/// Saves the chart as svg or png, depending on the extension of the path
+pub fn save(dist: &ProbDist, options: &ChartOptions, path: &Path) -> Result<(), String> {
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_lowercase);
+    let contents = match extension.as_deref() {
+        Some("svg") => svg(dist, options).into_bytes(),
+        #[cfg(feature = "png")]
+        Some("png") => png(dist, options)?,
+        _ => {
+            return Err(format!(
+                "chart: cannot save {} as svg or png",
+                path.display()
+            ))
+        }
+    };
+    fs::write(path, contents)
+        .map_err(|err| format!("chart: could not write {}: {err}", path.display()))
+}
+
+/// A round distance between ticks, such that range is divided into about the given number of ticks
+fn tick_step(range: f64, ticks: f64) -> f64 {
+    let raw = range / ticks;
+    if raw <= 0.0 || !raw.is_finite() {
+        return 1.0;
+    }
+    let magnitude = 10f64.powf(raw.log10().floor());
+    let step = match raw / magnitude {
+        norm if norm <= 1.0 => 1.0,
+        norm if norm <= 2.0 => 2.0,
+        norm if norm <= 5.0 => 5.0,
+        _ => 10.0,
+    };
+    step * magnitude
+}
+
+/// Writes a probability as a percentage, with only as many decimals as the ticks need
+fn round_percent(prob: f64, step: f64) -> String {
+    let decimals = (-(100.0 * step).log10().floor()).max(0.0) as usize;
+    format!("{:.decimals$}", 100.0 * prob)
+}
+
+fn escape(txt: &str) -> String {
+    txt.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,40 @@
+use std::str::FromStr;
+
+use doice_roller::{Roll, Rollable};
+
+use super::*;
+
+#[test]
+fn svg_test() {
+    let dist = Roll::from_str("2d6").unwrap().dist();
+    let options = ChartOptions {
+        title: Some("2d6 < 3d4".into()),
+        cumulative: true,
+        dc: Some(8),
+        ..Default::default()
+    };
+    let chart = svg(&dist, &options);
+    assert!(chart.starts_with("<svg"));
+    assert!(chart.trim_end().ends_with("</svg>"));
+    assert_eq!(chart.matches(&format!(r#"fill="{BAR_COLOR}""#)).count(), 11);
+    assert!(chart.contains("2d6 &lt; 3d4"));
+    assert!(chart.contains("<polyline"));
+    assert!(chart.contains("DC 8: 41.7%"));
+
+    // Wide distributions are drawn with fewer bars than outcomes
+    let dist = Roll::from_str("d10000").unwrap().dist();
+    let chart = svg(&dist, &ChartOptions::default());
+    assert!(chart.matches(&format!(r#"fill="{BAR_COLOR}""#)).count() <= 400);
+
+    let chart = svg(&ProbDist::new(), &ChartOptions::default());
+    assert!(chart.trim_end().ends_with("</svg>"));
+}
+
+#[test]
+fn tick_test() {
+    assert_eq!(tick_step(11.0, 10.0), 2.0);
+    assert_eq!(tick_step(100.0, 10.0), 10.0);
+    assert!((tick_step(0.3, 5.0) - 0.1).abs() < 1e-12);
+    assert_eq!(round_percent(0.05, 0.05), "5");
+    assert_eq!(round_percent(0.002, 0.001), "0.2");
+}
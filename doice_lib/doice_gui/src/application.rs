@@ -7,13 +7,14 @@ use eframe::{
 };
 
 use dyn_clone::{clone_box, DynClone};
+use serde_json::Value;
 
 use {
     dnd_data::{character::Character, DnData},
     doice_utils::{Named, Search},
 };
 
-use super::components::DiceGrapher;
+use super::{components::DiceGrapher, Config, SavedState};
 
 pub struct DCtx {
     focus: Option<usize>,
@@ -79,6 +80,17 @@ impl AppData {
             character: RwLock::new(Character::init_test(dnd_data)),
         }
     }
+
+    /// Restores the shared state saved by an earlier session
+    pub fn load(&self, state: &mut SavedState) {
+        let grapher = std::mem::take(&mut state.dice_grapher);
+        self.dice_grapher.write().unwrap().load(grapher);
+    }
+
+    /// Adds the shared state to what is saved for the next session
+    pub fn save(&self, state: &mut SavedState) {
+        state.dice_grapher = self.dice_grapher.read().unwrap().save();
+    }
 }
 
 // impl Default for AppData {
@@ -93,20 +105,60 @@ impl AppData {
 pub struct DoiceApp {
     taskbar: Taskbar,
     data: Rc<AppData>,
+    /// The activities of the previous session, which are reopened once they have been registered.
+    /// None once that has happened
+    saved_activities: Option<Vec<(String, Value)>>,
+    /// Opened instead if there are no activities to reopen
+    startup_activity: Option<String>,
 }
 
 #[cfg(feature = "eframe")]
 impl DoiceApp {
-    /// Currently equal to DoiceApp::default().
-    pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
+    /// Applies the config before anything is created, so the components can respect it
+    pub fn new(cc: &eframe::CreationContext<'_>, config: Config) -> Self {
         // Customize egui here with cc.egui_ctx.set_fonts and cc.egui_ctx.set_visuals.
-        // Restore app state using cc.storage (requires the "persistence" feature).
         // Use the cc.gl (a glow::Context) to create graphics shaders and buffers that you can use
         // for e.g. egui::PaintCallback.
+        config.apply(&cc.egui_ctx);
+        let data = AppData::new(cc);
+        let mut saved_activities = Vec::new();
+        if let Some(mut state) = SavedState::load(cc.storage) {
+            data.load(&mut state);
+            saved_activities = state.activities;
+        }
 
         Self {
-            data: Rc::new(AppData::new(cc)),
+            data: Rc::new(data),
             taskbar: Default::default(),
+            saved_activities: Some(saved_activities),
+            startup_activity: config.startup_activity,
+        }
+    }
+
+    /// Reopens the activities of the previous session with their saved state, or opens the startup activity
+    fn restore_activities(&mut self, ctx: &Context) {
+        let Some(mut saved) = self.saved_activities.take() else {
+            return;
+        };
+        if saved.is_empty() {
+            if let Some(name) = self.startup_activity.take() {
+                saved.push((name, Value::Null));
+            }
+        }
+        for (name, state) in saved {
+            let Some(i) = self
+                .taskbar
+                .launchable_act
+                .iter()
+                .position(|act| act.name() == name)
+            else {
+                continue;
+            };
+            if self.taskbar.start_activity(i, ctx).is_ok() && !state.is_null() {
+                if let Some(entry) = self.taskbar.open_activities.last_mut() {
+                    entry.act.load_state(state);
+                }
+            }
         }
     }
 
@@ -141,7 +193,7 @@ impl DoiceApp {
 #[cfg(feature = "eframe")]
 impl eframe::App for DoiceApp {
     fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
-        ctx.set_visuals(egui::Visuals::dark());
+        self.restore_activities(ctx);
 
         egui::TopBottomPanel::top("Big Taskbar").show(ctx, |ui| self.taskbar.show(ui, frame));
 
@@ -205,7 +257,20 @@ impl eframe::App for DoiceApp {
         }
     }
 
-    fn save(&mut self, _storage: &mut dyn eframe::Storage) {}
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        let mut state = SavedState::new();
+        self.data.save(&mut state);
+        state.activities = self
+            .taskbar
+            .open_activities
+            .iter()
+            .map(|e| {
+                let act_state = e.act.save_state().unwrap_or(Value::Null);
+                (e.act.name().to_string(), act_state)
+            })
+            .collect();
+        state.store(storage);
+    }
 
     fn on_exit(&mut self, _gl: std::option::Option<&eframe::glow::Context>) {}
 
@@ -213,13 +278,15 @@ impl eframe::App for DoiceApp {
         std::time::Duration::from_secs(30)
     }
 
-    fn clear_color(&self, _visuals: &egui::Visuals) -> [f32; 4] {
+    fn clear_color(&self, visuals: &egui::Visuals) -> [f32; 4] {
         // NOTE: a bright gray makes the shadows of the windows look weird.
         // We use a bit of transparency so that if the user switches on the
         // `transparent()` option they get immediate results.
-        egui::Color32::from_rgba_unmultiplied(12, 12, 12, 180).to_normalized_gamma_f32()
-
-        // _visuals.window_fill() would also be a natural choice
+        if visuals.dark_mode {
+            egui::Color32::from_rgba_unmultiplied(12, 12, 12, 180).to_normalized_gamma_f32()
+        } else {
+            visuals.window_fill().to_normalized_gamma_f32()
+        }
     }
 
     fn persist_egui_memory(&self) -> bool {
@@ -243,6 +310,12 @@ pub trait Activity: DynClone + Send {
     fn resize(&self) -> bool;
     /// Indicates in which category the activity thinks it belongs in
     fn category(&self) -> &str;
+    /// The state to restore the activity with in the next session, if it has any worth keeping (optional)
+    fn save_state(&self) -> Option<Value> {
+        None
+    }
+    /// Restores the state returned by `save_state` in an earlier session, after `init` (optional)
+    fn load_state(&mut self, _state: Value) {}
 }
 
 impl Named for Box<dyn Activity> {
@@ -400,7 +473,7 @@ impl Taskbar {
     // }
 
     /// Helper function to start the activity with a certain index
-    fn start_activity(&mut self, i: usize, ui: &mut Ui) -> Result<(), ()> {
+    fn start_activity(&mut self, i: usize, ctx: &Context) -> Result<(), ()> {
         // Bounds check
         if self.launchable_act.len() <= i {
             return Err(());
@@ -417,7 +490,7 @@ impl Taskbar {
             act: clone_box(self.launchable_act[i].as_ref()),
         };
         // Initialized activity
-        entry.act.init(ui.ctx().clone());
+        entry.act.init(ctx.clone());
         // Increment seq and store entry in list
         self.seq += 1;
         self.open_activities.push(entry);
@@ -479,7 +552,7 @@ impl Taskbar {
 
         // If user has confirmed
         if ui.input(|i| i.key_pressed(egui::Key::Enter)) {
-            self.start_activity(self.search_res[0].1, ui)
+            self.start_activity(self.search_res[0].1, ui.ctx())
                 .expect("Search result out of bounds!");
             self.search_mode = false;
             self.search_text.clear();
@@ -534,7 +607,7 @@ impl Taskbar {
 
                     // Handle clicks
                     for (clicked, _) in resp.iter().filter(|(_, r)| r.clicked()) {
-                        self.start_activity(*clicked, ui)
+                        self.start_activity(*clicked, ui.ctx())
                             .expect("There were more responses than activities, somehow.");
                     }
                 });
@@ -14,12 +14,21 @@ use eframe::{
 };
 
 use egui_plot::{Bar, BarChart, Plot, VLine};
+use serde::{Deserialize, Serialize};
+
+#[cfg(not(target_arch = "wasm32"))]
+use {doice_chart::ChartOptions, eframe::egui::Button};
 
 use {
-    doice_roller::{DiceError, Layouter, ProbDist, Roll, RollOut, Rollable, SampleDist},
+    doice_roller::{
+        with_cancel_token, CancelToken, DiceError, Layouter, ProbDist, Roll, RollOut, Rollable,
+        SampleDist,
+    },
     doice_utils::ParExecutor,
 };
 
+use crate::Config;
+
 use super::{
     dice_docs::dice_docs,
     dice_history::{DiceHistory, DiceHistoryEntry},
@@ -55,15 +64,33 @@ impl CurrentPanel {
     }
 }
 
+/// The part of a `DiceGrapher` that is kept between sessions
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GrapherState {
+    /// The roll that was last entered
+    pub input: String,
+    pub dc_on: bool,
+    pub dc_val: isize,
+    pub history: DiceHistory,
+    pub initiator: Initiator,
+}
+
 #[derive(Default)]
 pub struct DiceGrapher<const EXP_UPDATE: u64 = 100> {
     bars: Vec<Bar>,
     roll: Roll,
     roll_txt: String,
-    dist_gen: ParExecutor<ProbDist>,
+    dist_gen: ParExecutor<Result<(ProbDist, Vec<(String, ProbDist)>), DiceError>>,
+    dist_cancel: CancelToken,
     ctx: Context,
     loading: bool,
     current_dist: ProbDist,
+    total_dist: ProbDist,
+    /// Distributions of the components of a multi-valued roll, like {fire: 3d6, cold: 2d6}
+    component_dists: Vec<(String, ProbDist)>,
+    /// The component whose distribution is shown, or None for the total
+    shown_component: Option<usize>,
     display_error: Option<String>,
     avg: f64,
     variance: f64,
@@ -100,6 +127,7 @@ impl<const EXP_UPDATE: u64> DiceGrapher<EXP_UPDATE> {
             loading: false,
             ..Default::default()
         };
+        out.set_dc(Config::get(&out.ctx).default_dc);
         // Make sure the graph is displaying something valid
         out.display_roll("");
         out
@@ -166,7 +194,17 @@ impl<const EXP_UPDATE: u64> DiceGrapher<EXP_UPDATE> {
                 self.display_error = None;
                 self.roll = roll.clone();
                 self.loading = true;
-                self.dist_gen.process_into(roll).keep_notifier();
+                // Stop sampling the distribution of the previous roll, if that is still going on
+                self.dist_cancel.cancel();
+                self.dist_cancel = CancelToken::new();
+                let cancel = self.dist_cancel.clone();
+                self.dist_gen
+                    .process_with(roll, move |roll| {
+                        with_cancel_token(&cancel, || {
+                            roll.try_dist().map(|dist| (dist, roll.component_dists()))
+                        })
+                    })
+                    .keep_notifier();
                 self.exp_bars.clear();
                 self.res = None;
                 self.exp_dist.clear();
@@ -185,8 +223,14 @@ impl<const EXP_UPDATE: u64> DiceGrapher<EXP_UPDATE> {
     }
 
     pub fn roll(&mut self) -> RollOut {
-        let mut res = self.roll.roll();
-        if res.txt.sections.len() > 100 {
+        let mut res = match self.roll.try_roll() {
+            Ok(res) => res,
+            Err(err) => {
+                self.display_error = Some(err.into());
+                return RollOut::default();
+            }
+        };
+        if res.txt.sections.len() > self.roll.limits().max_text_sections {
             res.txt = Layouter::default();
             res.txt.append("[...]");
         }
@@ -250,6 +294,9 @@ impl<const EXP_UPDATE: u64> DiceGrapher<EXP_UPDATE> {
                 .name("Probability Distribution")
         };
 
+        self.component_picker(ui);
+        let shown_value = self.res.as_ref().map(|res| self.shown_value(res));
+        let highlight = Config::get(&self.ctx).colors.reroll;
         Plot::new("Roll Analyzer")
             .data_aspect(self.aspect_rat)
             .view_aspect(1.0)
@@ -271,17 +318,17 @@ impl<const EXP_UPDATE: u64> DiceGrapher<EXP_UPDATE> {
                 }
 
                 // If there is a roll result, show it too
-                if let Some(res) = &self.res {
+                if let Some(value) = shown_value {
                     let clr = if self.dc_on {
-                        if self.dc_val <= res.value {
+                        if self.dc_val <= value {
                             Color32::GREEN
                         } else {
                             Color32::RED
                         }
                     } else {
-                        Color32::GOLD
+                        highlight
                     };
-                    ui.vline(VLine::new(res.value as f64).name("Roll Result").color(clr));
+                    ui.vline(VLine::new(value as f64).name("Roll Result").color(clr));
                 }
             });
 
@@ -303,6 +350,14 @@ impl<const EXP_UPDATE: u64> DiceGrapher<EXP_UPDATE> {
                         if self.dc_on {
                             write!(info_text, ";\tsuccess = {:.2}%", self.success_chance).unwrap();
                         }
+                        if let Some(approx) = self.current_dist.approximation() {
+                            write!(
+                                info_text,
+                                ";\tapproximate (±{:.1}%)",
+                                approx.max_error * 100.0
+                            )
+                            .unwrap();
+                        }
 
                         info_text
                     }),
@@ -346,12 +401,23 @@ impl<const EXP_UPDATE: u64> DiceGrapher<EXP_UPDATE> {
                         if self.dc_on {
                             write!(info_text, ";\tsuccess = {:.2}%", self.success_chance).unwrap();
                         }
+                        if let Some(approx) = self.current_dist.approximation() {
+                            write!(
+                                info_text,
+                                ";\tapproximate (±{:.1}%)",
+                                approx.max_error * 100.0
+                            )
+                            .unwrap();
+                        }
 
                         info_text
                     }),
                 });
             }
 
+            self.component_picker(ui);
+            let shown_value = self.res.as_ref().map(|res| self.shown_value(res));
+            let highlight = Config::get(&self.ctx).colors.reroll;
             Plot::new("Roll Analyzer")
                 //.data_aspect(self.aspect_rat)
                 .auto_bounds_y()
@@ -376,17 +442,17 @@ impl<const EXP_UPDATE: u64> DiceGrapher<EXP_UPDATE> {
                     }
 
                     // If there is a roll result, show it too
-                    if let Some(res) = &self.res {
+                    if let Some(value) = shown_value {
                         let clr = if self.dc_on {
-                            if self.dc_val <= res.value {
+                            if self.dc_val <= value {
                                 Color32::GREEN
                             } else {
                                 Color32::RED
                             }
                         } else {
-                            Color32::GOLD
+                            highlight
                         };
-                        ui.vline(VLine::new(res.value as f64).name("Roll Result").color(clr));
+                        ui.vline(VLine::new(value as f64).name("Roll Result").color(clr));
                     }
                 });
         });
@@ -394,22 +460,63 @@ impl<const EXP_UPDATE: u64> DiceGrapher<EXP_UPDATE> {
 
     fn handle_dist_gen(&mut self) {
         // If new dist is available
-        if let Some(dist) = self.dist_gen.try_get_data() {
+        if let Some(res) = self.dist_gen.try_get_data() {
             // Stop loading and make new plot
             self.loading = false;
-            self.current_dist = dist;
-            self.recalc_aspect();
-            let threshold = self.peak / 1000.0;
-            self.current_dist.retain(|_, prob| *prob > threshold);
-            self.recalc_aspect();
-            self.remake_bars();
-            self.avg = self.current_dist.expectation();
-            self.cumulative = self.current_dist.get_cumulative_prob();
-            self.variance = self.current_dist.var();
-            self.refresh_dc();
+            match res {
+                Ok((dist, component_dists)) => {
+                    self.total_dist = dist;
+                    self.component_dists = component_dists;
+                    self.shown_component = None;
+                    self.show_dist();
+                }
+                // Errors like overflow only show up once the distribution is computed
+                Err(err) => self.display_error = Some(err.into()),
+            }
+        }
+    }
+
+    /// Plots either the total distribution or that of the chosen component
+    fn show_dist(&mut self) {
+        self.current_dist = match self.shown_component {
+            Some(i) => self.component_dists[i].1.clone(),
+            None => self.total_dist.clone(),
+        };
+        self.recalc_aspect();
+        let threshold = self.peak / 1000.0;
+        self.current_dist.retain(|_, prob| *prob > threshold);
+        self.recalc_aspect();
+        self.remake_bars();
+        self.avg = self.current_dist.expectation();
+        self.cumulative = self.current_dist.get_cumulative_prob();
+        self.variance = self.current_dist.var();
+        self.refresh_dc();
+    }
+
+    /// Lets the user choose between the total and the components of a multi-valued roll
+    fn component_picker(&mut self, ui: &mut Ui) {
+        if self.component_dists.is_empty() || self.loading {
+            return;
+        }
+        let prev = self.shown_component;
+        ui.horizontal_wrapped(|ui| {
+            ui.selectable_value(&mut self.shown_component, None, "Total");
+            for (i, (name, _)) in self.component_dists.iter().enumerate() {
+                ui.selectable_value(&mut self.shown_component, Some(i), name.as_str());
+            }
+        });
+        if self.shown_component != prev {
+            self.show_dist();
         }
     }
 
+    /// The value of the roll result for the shown distribution
+    fn shown_value(&self, res: &RollOut) -> isize {
+        self.shown_component
+            .and_then(|i| res.components.get(i))
+            .map_or(res.value, |(_, value)| *value)
+    }
+
     fn refresh_dc(&mut self) {
         // Find the last entry that is < dc
         let e = self.cumulative.iter().filter(|e| *e.0 < self.dc_val).last();
@@ -426,6 +533,32 @@ impl<const EXP_UPDATE: u64> DiceGrapher<EXP_UPDATE> {
         }
     }
 
+    /// Asks where to save the shown distribution, and saves it there as svg or png
+    #[cfg(not(target_arch = "wasm32"))]
+    fn export_chart(&mut self) {
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("SVG", &["svg"])
+            .add_filter("PNG", &["png"])
+            .set_file_name("chart.svg")
+            .save_file()
+        else {
+            return;
+        };
+        let title = match self.shown_component {
+            Some(i) => format!("{} ({})", self.roll_txt, self.component_dists[i].0),
+            None => self.roll_txt.clone(),
+        };
+        let options = ChartOptions {
+            title: Some(title),
+            cumulative: true,
+            dc: self.dc_on.then_some(self.dc_val),
+            ..Default::default()
+        };
+        if let Err(err) = doice_chart::save(&self.current_dist, &options, &path) {
+            self.display_error = Some(err);
+        }
+    }
+
     fn handle_experiment(&mut self) {
         // If new experimental data is available, process it
         if let Some((samples, roll)) = self.exp_exec.try_get_data() {
@@ -469,6 +602,18 @@ impl<const EXP_UPDATE: u64> DiceGrapher<EXP_UPDATE> {
                 if self.dc_on && (!prev_checked || val_response.changed()) {
                     self.refresh_dc();
                 }
+
+                #[cfg(not(target_arch = "wasm32"))]
+                if ui
+                    .add_enabled(
+                        !self.loading && !self.current_dist.is_empty(),
+                        Button::new("Export"),
+                    )
+                    .on_hover_text("Save the chart as svg or png")
+                    .clicked()
+                {
+                    self.export_chart();
+                }
             })
         });
 
@@ -507,6 +652,18 @@ impl<const EXP_UPDATE: u64> DiceGrapher<EXP_UPDATE> {
                 if self.dc_on && (!prev_checked || val_response.changed()) {
                     self.refresh_dc();
                 }
+
+                #[cfg(not(target_arch = "wasm32"))]
+                if ui
+                    .add_enabled(
+                        !self.loading && !self.current_dist.is_empty(),
+                        Button::new("Export"),
+                    )
+                    .on_hover_text("Save the chart as svg or png")
+                    .clicked()
+                {
+                    self.export_chart();
+                }
             })
         });
 
@@ -519,6 +676,25 @@ impl<const EXP_UPDATE: u64> DiceGrapher<EXP_UPDATE> {
         // self.history.show(ui);
     }
 
+    pub fn save(&self) -> GrapherState {
+        GrapherState {
+            input: self.roll_txt.clone(),
+            dc_on: self.dc_on,
+            dc_val: self.dc_val,
+            history: self.history.clone(),
+            initiator: self.initiator.clone(),
+        }
+    }
+
+    /// Restores the state saved by `save`, and starts analyzing the roll that was last entered
+    pub fn load(&mut self, state: GrapherState) {
+        self.dc_on = state.dc_on;
+        self.dc_val = state.dc_val;
+        self.history = state.history;
+        self.initiator = state.initiator;
+        self.display_roll(state.input.as_str());
+    }
+
     pub fn initiator(&self) -> &Initiator {
         &self.initiator
     }
@@ -546,9 +722,13 @@ impl Clone for DiceGrapher {
             roll: self.roll.clone(),
             roll_txt: self.roll_txt.clone(),
             dist_gen: ParExecutor::with_notifyer(move || extra_ctx.request_repaint()),
+            dist_cancel: CancelToken::new(),
             ctx: self.ctx.clone(),
             loading: self.loading,
             current_dist: self.current_dist.clone(),
+            total_dist: self.total_dist.clone(),
+            component_dists: self.component_dists.clone(),
+            shown_component: self.shown_component,
             display_error: self.display_error.clone(),
             avg: self.avg,
             variance: self.variance,
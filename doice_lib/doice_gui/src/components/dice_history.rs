@@ -1,16 +1,31 @@
+use std::str::FromStr;
+
 use eframe::emath::Align;
-use instant::{Duration, Instant};
+use instant::{Duration, Instant, SystemTime};
+use serde::{Deserialize, Serialize, Serializer};
+
+use eframe::egui::epaint::{text::LayoutJob, Color32, Stroke};
+use eframe::egui::{Layout, ScrollArea, TextFormat, TextStyle, Ui};
 
-use eframe::egui::epaint::{text::LayoutJob, Color32};
-use eframe::egui::{Layout, ScrollArea, TextStyle, Ui};
+use doice_roller::{Layouter, Roll, RollOut};
 
-use doice_roller::{Roll, RollOut};
+/// At most this many of the newest entries are saved between sessions, as every one is parsed again on startup
+const MAX_SAVED: usize = 100;
 
-#[derive(Clone, Default)]
+#[derive(Clone, Default, Serialize, Deserialize)]
 pub struct DiceHistory {
+    #[serde(serialize_with = "serialize_newest")]
     entries: Vec<DiceHistoryEntry>,
 }
 
+/// Serializes only the newest `MAX_SAVED` entries
+fn serialize_newest<S: Serializer>(
+    entries: &[DiceHistoryEntry],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    entries[entries.len().saturating_sub(MAX_SAVED)..].serialize(serializer)
+}
+
 impl DiceHistory {
     pub fn new() -> Self {
         Default::default()
@@ -20,6 +35,24 @@ impl DiceHistory {
         self.entries.push(entry);
     }
 
+    /// The entries from oldest to newest
+    pub fn entries(&self) -> &[DiceHistoryEntry] {
+        &self.entries
+    }
+
+    /// The average luck of all rolls in the session, see `DiceHistoryEntry::luck`
+    pub fn luck(&self) -> f64 {
+        if self.entries.is_empty() {
+            return 0.0;
+        }
+        let weight = 1.0f64 / (self.entries.len() as f64);
+        self.entries
+            .iter()
+            .map(|entry| entry.luck() * weight)
+            .filter(|w_luck| w_luck.is_normal())
+            .sum()
+    }
+
     pub fn show(&mut self, ui: &mut Ui) {
         ui.group(|ui| {
             ui.set_height(ui.available_width());
@@ -34,16 +67,7 @@ impl DiceHistory {
                 });
         });
         ui.vertical_centered(|ui| {
-            let luck: f64 = if self.entries.is_empty() {
-                0.0f64
-            } else {
-                let weight = 1.0f64 / (self.entries.len() as f64);
-                self.entries
-                    .iter()
-                    .map(|entry| entry.luck() * weight)
-                    .filter(|w_luck| w_luck.is_normal())
-                    .sum()
-            };
+            let luck = self.luck();
 
             ui.colored_label(
                 match luck.total_cmp(&0.0f64) {
@@ -58,16 +82,7 @@ impl DiceHistory {
 
     pub fn show_flex(&mut self, ui: &mut Ui) {
         ui.with_layout(Layout::bottom_up(Align::Center), |ui| {
-            let luck: f64 = if self.entries.is_empty() {
-                0.0f64
-            } else {
-                let weight = 1.0f64 / (self.entries.len() as f64);
-                self.entries
-                    .iter()
-                    .map(|entry| entry.luck() * weight)
-                    .filter(|w_luck| w_luck.is_normal())
-                    .sum()
-            };
+            let luck = self.luck();
 
             ui.colored_label(
                 match luck.total_cmp(&0.0f64) {
@@ -95,7 +110,8 @@ impl DiceHistory {
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(from = "SavedEntry", into = "SavedEntry")]
 pub struct DiceHistoryEntry {
     roll: Roll,
     roll_txt: String,
@@ -124,7 +140,7 @@ impl DiceHistoryEntry {
             ui.label(LayoutJob::from(self.result.txt.clone()));
 
             ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
-                ui.label(format!("{} ago", fmt_duration(&self.ts.elapsed())));
+                ui.label(self.age());
             });
         });
     }
@@ -150,6 +166,11 @@ impl DiceHistoryEntry {
         self.ts.elapsed()
     }
 
+    /// The time since the roll as text, like "5s ago"
+    pub fn age(&self) -> String {
+        format!("{} ago", fmt_duration(&self.ts.elapsed()))
+    }
+
     /// Gives an indication of how lucky/unlucky a roll was.
     /// An output of -1 means the roll was 1 deviation below the mean
     /// An output of 1 means the roll was 1 deviation above the mean
@@ -158,6 +179,95 @@ impl DiceHistoryEntry {
     }
 }
 
+/// A section of result text as it is saved, as its text, color and strikethrough
+type SavedSection = (String, [u8; 4], Option<(f32, [u8; 4])>);
+
+/// A `DiceHistoryEntry` as it is saved between sessions.
+/// The roll is parsed again from its text, and the time is kept as a unix timestamp instead of an instant
+#[derive(Serialize, Deserialize)]
+struct SavedEntry {
+    roll_txt: String,
+    value: isize,
+    txt: Vec<SavedSection>,
+    components: Vec<(String, isize)>,
+    /// None if not finite, which JSON cannot represent
+    avg: Option<f64>,
+    variance: Option<f64>,
+    /// Seconds since the unix epoch
+    rolled_at: u64,
+}
+
+impl From<DiceHistoryEntry> for SavedEntry {
+    fn from(entry: DiceHistoryEntry) -> Self {
+        let txt = entry
+            .result
+            .txt
+            .sections
+            .into_iter()
+            .map(|(txt, format)| {
+                let strikethrough = (format.strikethrough.width > 0.0).then_some((
+                    format.strikethrough.width,
+                    format.strikethrough.color.to_array(),
+                ));
+                (txt, format.color.to_array(), strikethrough)
+            })
+            .collect();
+        let rolled_at = unix_time().saturating_sub(entry.ts.elapsed().as_secs());
+
+        SavedEntry {
+            roll_txt: entry.roll_txt,
+            value: entry.result.value,
+            txt,
+            components: entry.result.components,
+            avg: entry.avg.is_finite().then_some(entry.avg),
+            variance: entry.variance.is_finite().then_some(entry.variance),
+            rolled_at,
+        }
+    }
+}
+
+impl From<SavedEntry> for DiceHistoryEntry {
+    fn from(saved: SavedEntry) -> Self {
+        let sections = saved
+            .txt
+            .into_iter()
+            .map(|(txt, [r, g, b, a], strikethrough)| {
+                let format = TextFormat {
+                    color: Color32::from_rgba_premultiplied(r, g, b, a),
+                    strikethrough: strikethrough.map_or(Stroke::NONE, |(width, [r, g, b, a])| {
+                        Stroke::new(width, Color32::from_rgba_premultiplied(r, g, b, a))
+                    }),
+                    ..Default::default()
+                };
+                (txt, format)
+            })
+            .collect();
+        let age = Duration::from_secs(unix_time().saturating_sub(saved.rolled_at));
+
+        DiceHistoryEntry {
+            // The text parsed fine when it was rolled, but may not in a later version
+            roll: Roll::from_str(&saved.roll_txt).unwrap_or_default(),
+            roll_txt: saved.roll_txt,
+            result: RollOut {
+                value: saved.value,
+                txt: Layouter { sections },
+                components: saved.components,
+            },
+            avg: saved.avg.unwrap_or(f64::NAN),
+            variance: saved.variance.unwrap_or(f64::NAN),
+            ts: Instant::now().checked_sub(age).unwrap_or_else(Instant::now),
+        }
+    }
+}
+
+/// Seconds since the unix epoch
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
 fn fmt_duration(duration: &Duration) -> String {
     let seconds = duration.as_secs();
     if seconds < 60 {
@@ -167,3 +277,71 @@ fn fmt_duration(duration: &Duration) -> String {
         format!("{} min", minutes)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saved_entry_test() {
+        let mut txt = Layouter::new();
+        txt.append("[");
+        txt.append_strikethrough("3");
+        txt.append_colored(", 17", Color32::GOLD);
+        txt.append("]");
+        let result = RollOut {
+            value: 17,
+            txt,
+            components: vec![("fire".into(), 17)],
+        };
+        let mut entry = DiceHistoryEntry::new(
+            Roll::from_str("2d20kh1").unwrap(),
+            "2d20kh1".into(),
+            result,
+            f64::NAN,
+            f64::INFINITY,
+        );
+        entry.ts = Instant::now()
+            .checked_sub(Duration::from_secs(120))
+            .unwrap();
+
+        let json = serde_json::to_string(&entry).unwrap();
+        let loaded: DiceHistoryEntry = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.src(), "2d20kh1");
+        assert_eq!(loaded.result.value, 17);
+        assert_eq!(loaded.result.components, entry.result.components);
+        let formats = |entry: &DiceHistoryEntry| {
+            entry
+                .result
+                .txt
+                .sections
+                .iter()
+                .map(|(txt, format)| (txt.clone(), format.color, format.strikethrough))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(formats(&loaded), formats(&entry));
+        // Non-finite values are saved as null, and read back as NaN
+        assert!(loaded.avg.is_nan() && loaded.variance.is_nan());
+        // The age is kept to the second, give or take a second passing during the test
+        assert!((120..=121).contains(&loaded.ts.elapsed().as_secs()));
+    }
+
+    #[test]
+    fn saved_history_test() {
+        let mut history = DiceHistory::new();
+        for i in 0..250 {
+            let roll_txt = format!("d20+{i}");
+            let roll = Roll::from_str(&roll_txt).unwrap();
+            let result = roll.try_roll().unwrap();
+            history.add_entry(DiceHistoryEntry::new(roll, roll_txt, result, 10.5, 33.25));
+        }
+
+        // Only the newest entries are saved, in order
+        let json = serde_json::to_string(&history).unwrap();
+        let loaded: DiceHistory = serde_json::from_str(&json).unwrap();
+        assert_eq!(history.entries().len(), 250);
+        assert_eq!(loaded.entries().len(), MAX_SAVED);
+        assert_eq!(loaded.entries()[0].src(), "d20+150");
+        assert_eq!(loaded.entries()[MAX_SAVED - 1].src(), "d20+249");
+    }
+}
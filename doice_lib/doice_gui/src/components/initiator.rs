@@ -3,13 +3,15 @@ use eframe::{
     emath::Align,
 };
 use egui_dnd::{dnd, utils::shift_vec, DragDropItem};
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Default, Clone, Hash)]
+#[derive(Debug, Default, Clone, Hash, Serialize, Deserialize)]
 struct Item {
     id: usize,
     name: String,
     initiative: isize,
     note: String,
+    #[serde(skip)]
     remove: bool,
 }
 
@@ -71,13 +73,16 @@ impl Item {
 //     }
 // }
 
-#[derive(Default, Clone)]
+#[derive(Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Initiator {
     pre_item: Item,
+    #[serde(skip)]
     innit_text: String,
     list: Vec<Item>,
     //dnd: DragDropUi,
     current: Option<isize>,
+    #[serde(skip)]
     clear_confirm: bool,
 }
 
@@ -86,6 +91,63 @@ impl Initiator {
         Self::default()
     }
 
+    /// Adds a combatant to the end of the list
+    pub fn add(&mut self, name: &str, initiative: isize) {
+        self.list.push(Item {
+            id: self.pre_item.id,
+            name: name.to_string(),
+            initiative,
+            ..Default::default()
+        });
+        self.pre_item.id += 1;
+    }
+
+    /// Removes the combatant at index, if there is one
+    pub fn remove(&mut self, index: usize) {
+        if index < self.list.len() {
+            self.list.remove(index);
+        }
+    }
+
+    /// The names and initiatives of the combatants, in the order of the list
+    pub fn entries(&self) -> impl Iterator<Item = (&str, isize)> {
+        self.list
+            .iter()
+            .map(|item| (item.name.as_str(), item.initiative))
+    }
+
+    /// The initiative whose turn it is, if the encounter has started
+    pub fn current(&self) -> Option<isize> {
+        self.current
+    }
+
+    /// Sorts the list from high to low initiative
+    pub fn sort(&mut self) {
+        self.list.sort_by_key(|item| -item.initiative);
+    }
+
+    /// Moves on to the next lower initiative, or starts at the highest one
+    pub fn next(&mut self) {
+        self.current = if let Some(i) = self.current {
+            self.list
+                .iter()
+                .filter(|e| e.initiative < i)
+                .max_by_key(|e| e.initiative)
+                .map(|e| e.initiative)
+        } else {
+            self.list
+                .iter()
+                .max_by_key(|e| e.initiative)
+                .map(|e| e.initiative)
+        };
+    }
+
+    /// Removes all combatants
+    pub fn clear(&mut self) {
+        self.list.clear();
+        self.clear_confirm = false;
+    }
+
     pub fn show_flex(&mut self, ui: &mut Ui) {
         // Entry adder
         ui.group(|ui| {
@@ -135,28 +197,15 @@ impl Initiator {
         ui.group(|ui| {
             ui.horizontal(|ui| {
                 if ui.button("Sort").clicked() {
-                    self.list.sort_by_key(|item| -item.initiative);
-                    //self.list.reverse();
+                    self.sort();
                 }
                 if ui.button("Next").clicked() {
-                    self.current = if let Some(i) = self.current {
-                        self.list
-                            .iter()
-                            .filter(|e| e.initiative < i)
-                            .max_by_key(|e| e.initiative)
-                            .map(|e| e.initiative)
-                    } else {
-                        self.list
-                            .iter()
-                            .max_by_key(|e| e.initiative)
-                            .map(|e| e.initiative)
-                    };
+                    self.next();
                 }
                 if self.clear_confirm {
                     let sure = ui.button("Sure?");
                     if sure.clicked() {
-                        self.list.clear();
-                        self.clear_confirm = false;
+                        self.clear();
                     } else if sure.clicked_elsewhere() {
                         self.clear_confirm = false;
                     }
@@ -226,28 +275,15 @@ impl Initiator {
         ui.group(|ui| {
             ui.horizontal(|ui| {
                 if ui.button("Sort").clicked() {
-                    self.list.sort_by_key(|item| -item.initiative);
-                    //self.list.reverse();
+                    self.sort();
                 }
                 if ui.button("Next").clicked() {
-                    self.current = if let Some(i) = self.current {
-                        self.list
-                            .iter()
-                            .filter(|e| e.initiative < i)
-                            .max_by_key(|e| e.initiative)
-                            .map(|e| e.initiative)
-                    } else {
-                        self.list
-                            .iter()
-                            .max_by_key(|e| e.initiative)
-                            .map(|e| e.initiative)
-                    };
+                    self.next();
                 }
                 if self.clear_confirm {
                     let sure = ui.button("Sure?");
                     if sure.clicked() {
-                        self.list.clear();
-                        self.clear_confirm = false;
+                        self.clear();
                     } else if sure.clicked_elsewhere() {
                         self.clear_confirm = false;
                     }
@@ -1,8 +1,10 @@
 mod dice_grapher;
-pub use dice_grapher::DiceGrapher;
+pub use dice_grapher::{DiceGrapher, GrapherState};
 /// Actually just impls DoiceShow on character
 mod character_sheet;
 mod dice_docs;
 mod dice_history;
 mod initiator;
+pub use dice_history::{DiceHistory, DiceHistoryEntry};
+pub use initiator::Initiator;
 mod wide_grapher;
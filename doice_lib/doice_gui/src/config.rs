@@ -0,0 +1,237 @@
+use std::{
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use eframe::{
+    egui::{Context, Id, Visuals},
+    epaint::Color32,
+};
+use serde::{Deserialize, Serialize};
+
+use doice_roller::{set_accents, Accents};
+
+/// Key of the config in the data of the egui context, see `Config::get`
+const CONFIG_ID: &str = "doice_config";
+
+/// Preferences of the user, read from config.toml in the config dir.
+/// Anything left out of the file keeps its default
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub theme: Theme,
+    pub colors: Colors,
+    /// Size of the window at startup, if it should differ from the size the binary picks
+    pub window: Option<WindowSize>,
+    /// DC of the dice grapher when starting without a saved session
+    pub default_dc: Option<isize>,
+    /// Size of body text, the other text styles are scaled along with it
+    pub font_size: Option<f32>,
+    /// Name of the activity to open when starting without a saved session, for apps hosting several
+    pub startup_activity: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    #[default]
+    Dark,
+    Light,
+}
+
+impl Theme {
+    pub fn visuals(self) -> Visuals {
+        match self {
+            Theme::Dark => Visuals::dark(),
+            Theme::Light => Visuals::light(),
+        }
+    }
+}
+
+/// Accent colors, written like "#ffd700"
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Colors {
+    /// Highlights rerolled dice, and the result of a roll on the chart
+    #[serde(with = "hex")]
+    pub reroll: Color32,
+    /// Strikes through dropped dice
+    #[serde(with = "hex")]
+    pub strikethrough: Color32,
+}
+
+impl Default for Colors {
+    fn default() -> Self {
+        Colors {
+            reroll: Accents::DEFAULT.reroll,
+            strikethrough: Accents::DEFAULT.strikethrough,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct WindowSize {
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Config {
+    /// Where the config is read from, like ~/.config/doice/config.toml
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("doice").join("config.toml"))
+    }
+
+    /// Reads the config at path, which gives the default config if there is no file there
+    pub fn load(path: &Path) -> Result<Self, String> {
+        match fs::read_to_string(path) {
+            Ok(src) => toml::from_str(&src).map_err(|err| format!("{}: {err}", path.display())),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(format!("{}: {err}", path.display())),
+        }
+    }
+
+    /// Reads the config from `Config::path`, falling back to the default config if it is invalid
+    pub fn load_or_default() -> Self {
+        let Some(path) = Self::path() else {
+            return Self::default();
+        };
+        Self::load(&path).unwrap_or_else(|err| {
+            eprintln!("invalid config, using the defaults instead: {err}");
+            Self::default()
+        })
+    }
+
+    /// The size of the window at startup, given the size the binary would use otherwise
+    pub fn window_size(&self, default: (f32, f32)) -> (f32, f32) {
+        self.window
+            .map_or(default, |size| (size.width, size.height))
+    }
+
+    /// Applies the theme, font size and colors, and makes the config available to the components through `Config::get`
+    pub fn apply(&self, ctx: &Context) {
+        ctx.set_visuals(self.theme.visuals());
+        if let Some(size) = self.font_size.filter(|size| *size > 0.0) {
+            let mut style = (*ctx.style()).clone();
+            let body = style
+                .text_styles
+                .get(&eframe::egui::TextStyle::Body)
+                .map_or(size, |font| font.size);
+            for font in style.text_styles.values_mut() {
+                font.size *= size / body;
+            }
+            ctx.set_style(style);
+        }
+        set_accents(Accents {
+            strikethrough: self.colors.strikethrough,
+            reroll: self.colors.reroll,
+        });
+        ctx.data_mut(|data| data.insert_temp(Id::new(CONFIG_ID), self.clone()));
+    }
+
+    /// The config applied to the context, or the default config if there is none
+    pub fn get(ctx: &Context) -> Self {
+        ctx.data(|data| data.get_temp(Id::new(CONFIG_ID)))
+            .unwrap_or_default()
+    }
+}
+
+/// (De)serializes colors as hex strings, like "#ffd700", or "#ffd70080" with alpha
+mod hex {
+    use eframe::epaint::Color32;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(color: &Color32, serializer: S) -> Result<S::Ok, S::Error> {
+        let [r, g, b, a] = color.to_srgba_unmultiplied();
+        let hex = if a == u8::MAX {
+            format!("#{r:02x}{g:02x}{b:02x}")
+        } else {
+            format!("#{r:02x}{g:02x}{b:02x}{a:02x}")
+        };
+        serializer.serialize_str(&hex)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Color32, D::Error> {
+        let src = String::deserialize(deserializer)?;
+        parse(&src).ok_or_else(|| {
+            D::Error::custom(format!(
+                "invalid color {src:?}, expected one like \"#ffd700\""
+            ))
+        })
+    }
+
+    fn parse(src: &str) -> Option<Color32> {
+        let hex = src.strip_prefix('#')?;
+        if !hex.is_ascii() || !matches!(hex.len(), 6 | 8) {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+        let alpha = if hex.len() == 8 { channel(6)? } else { u8::MAX };
+        Some(Color32::from_rgba_unmultiplied(
+            channel(0)?,
+            channel(2)?,
+            channel(4)?,
+            alpha,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_test() {
+        let config: Config = toml::from_str(
+            r##"
+theme = "light"
+default_dc = 15
+font_size = 16.0
+startup_activity = "Initiative"
+
+[colors]
+reroll = "#00ff00"
+strikethrough = "#ffd70080"
+
+[window]
+width = 300.0
+height = 200.0
+"##,
+        )
+        .unwrap();
+        assert_eq!(config.theme, Theme::Light);
+        assert_eq!(config.default_dc, Some(15));
+        assert_eq!(config.font_size, Some(16.0));
+        assert_eq!(config.startup_activity.as_deref(), Some("Initiative"));
+        assert_eq!(config.colors.reroll, Color32::GREEN);
+        assert_eq!(
+            config.colors.strikethrough,
+            Color32::from_rgba_unmultiplied(0xff, 0xd7, 0x00, 0x80)
+        );
+        assert_eq!(config.window_size((800.0, 600.0)), (300.0, 200.0));
+    }
+
+    #[test]
+    fn partial_test() {
+        assert_eq!(toml::from_str::<Config>("").unwrap(), Config::default());
+
+        let config: Config = toml::from_str("[colors]\nreroll = \"#ff0000\"").unwrap();
+        assert_eq!(config.colors.reroll, Color32::RED);
+        assert_eq!(config.colors.strikethrough, Colors::default().strikethrough);
+        assert_eq!(config.theme, Theme::Dark);
+        assert_eq!(config.window_size((800.0, 600.0)), (800.0, 600.0));
+
+        // Without a file, everything keeps its default
+        let path = Path::new("/nonexistent/doice/config.toml");
+        assert_eq!(Config::load(path), Ok(Config::default()));
+    }
+
+    #[test]
+    fn invalid_color_test() {
+        for color in ["ffd700", "#ffd70", "#ffd7000", "#gggggg", "#ééé", ""] {
+            let src = format!("[colors]\nreroll = \"{color}\"");
+            assert!(toml::from_str::<Config>(&src).is_err(), "{color}");
+        }
+        assert!(toml::from_str::<Config>("theme = \"purple\"").is_err());
+    }
+}
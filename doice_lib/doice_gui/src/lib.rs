@@ -8,6 +8,10 @@
 mod application;
 /// Some ui components
 pub mod components;
+/// Preferences of the user, like the theme
+mod config;
+/// Saving and restoring the state of the app between sessions
+mod persistence;
 mod show_trait;
 
 // The only things that need to be accessed from the outside
@@ -19,7 +23,9 @@ pub use application::DCtx;
 /// Main application
 #[cfg(feature = "eframe")]
 pub use application::DoiceApp;
+pub use config::{Colors, Config, Theme, WindowSize};
 pub use dnd_data;
 #[cfg(feature = "eframe")]
 pub use eframe;
+pub use persistence::{SavedState, STATE_VERSION};
 pub use show_trait::DoiceShow;
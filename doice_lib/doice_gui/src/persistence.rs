@@ -0,0 +1,107 @@
+use eframe::Storage;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::components::GrapherState;
+
+/// Version of the saved state, which must be raised whenever older versions can no longer read it.
+/// State saved by another version is discarded instead of being misread, and overwritten at the next save
+pub const STATE_VERSION: u32 = 1;
+/// Key of the state in eframe's storage
+const STATE_KEY: &str = "doice_state";
+
+/// Everything that is kept between sessions, stored as JSON in eframe's storage
+#[derive(Serialize, Deserialize)]
+pub struct SavedState {
+    version: u32,
+    /// The dice grapher shared through `AppData`
+    pub dice_grapher: GrapherState,
+    /// The names of the activities along with their state, see `Activity::save_state`.
+    /// Null if the activity has no state to save
+    pub activities: Vec<(String, Value)>,
+}
+
+impl Default for SavedState {
+    fn default() -> Self {
+        SavedState {
+            version: STATE_VERSION,
+            dice_grapher: GrapherState::default(),
+            activities: Vec::new(),
+        }
+    }
+}
+
+impl SavedState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads the state saved by an earlier session, if there is one saved by this version
+    pub fn load(storage: Option<&dyn Storage>) -> Option<Self> {
+        let json = storage?.get_string(STATE_KEY)?;
+        let state: SavedState = serde_json::from_str(&json).ok()?;
+        (state.version == STATE_VERSION).then_some(state)
+    }
+
+    pub fn store(&self, storage: &mut dyn Storage) {
+        if let Ok(json) = serde_json::to_string(self) {
+            storage.set_string(STATE_KEY, json);
+        }
+    }
+
+    /// Takes the state of the first saved activity with this name, if it had any
+    pub fn take_activity(&mut self, name: &str) -> Option<Value> {
+        let i = self.activities.iter().position(|(act, _)| act == name)?;
+        Some(self.activities.remove(i).1).filter(|state| !state.is_null())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::json;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct MemoryStorage(HashMap<String, String>);
+
+    impl Storage for MemoryStorage {
+        fn get_string(&self, key: &str) -> Option<String> {
+            self.0.get(key).cloned()
+        }
+
+        fn set_string(&mut self, key: &str, value: String) {
+            self.0.insert(key.to_owned(), value);
+        }
+
+        fn flush(&mut self) {}
+    }
+
+    #[test]
+    fn version_test() {
+        let mut storage = MemoryStorage::default();
+        assert!(SavedState::load(Some(&storage)).is_none());
+
+        let mut state = SavedState::new();
+        state.activities = vec![
+            ("Notes".into(), json!({ "text": "Goblins at the gate" })),
+            ("Dice".into(), Value::Null),
+        ];
+        state.store(&mut storage);
+        let mut loaded = SavedState::load(Some(&storage)).unwrap();
+        assert_eq!(
+            loaded.take_activity("Notes"),
+            Some(json!({ "text": "Goblins at the gate" }))
+        );
+        assert_eq!(loaded.take_activity("Dice"), None);
+        assert_eq!(loaded.take_activity("Notes"), None);
+
+        // State saved by another version is discarded
+        let mut json: Value = serde_json::from_str(&storage.0[STATE_KEY]).unwrap();
+        json["version"] = json!(STATE_VERSION + 1);
+        storage.set_string(STATE_KEY, json.to_string());
+        assert!(SavedState::load(Some(&storage)).is_none());
+    }
+}
@@ -0,0 +1,24 @@
+//! Checks that no input string can panic the roller, run with `cargo fuzz run roll` from doice_roller
+#![no_main]
+
+use std::time::Duration;
+
+use doice_roller::{EvalLimits, Roll, Rollable};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|src: &str| {
+    // Tight limits keep every input quick to evaluate
+    let limits = EvalLimits {
+        max_dice: 1000,
+        max_faces: 1000,
+        max_support: 100_000,
+        time_budget: Duration::from_millis(100),
+        ..Default::default()
+    };
+    if let Ok(roll) = Roll::parse_with_limits(src, limits) {
+        let _ = roll.try_roll();
+        let _ = roll.try_roll_quiet();
+        let _ = roll.try_dist();
+        let _ = roll.component_dists();
+    }
+});
@@ -1,72 +1,229 @@
-use std::time::Duration;
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
 use instant::Instant;
 
+use crate::{eval, prob_dist::Approximation, DiceError, Value};
+
 use super::{ProbDist, Rollable, SampleDist};
 
-/// Trait enabling the bruteforcing of the probability distribution of any rollable thing
-pub trait BruteForceProbDist {
-    /// Highly approximate guideline for how long the bruteforcing operation is allowed to take
-    const BRUTEFORCE_TIME: Duration;
+/// Sampling batches are grown until a single batch takes about this long, so cancellation and time checks stay responsive
+const BATCH_TIME: Duration = Duration::from_millis(50);
+const FIRST_BATCH: usize = 1024;
 
-    /// Calculate the probability distribution by force
-    fn bruteforce_probdist(&self) -> ProbDist;
+thread_local! {
+    /// Token cancelling the sampling started on this thread, see `with_cancel_token`
+    static CANCEL: RefCell<Option<CancelToken>> = const { RefCell::new(None) };
 }
 
-/// Simplest blanket impl of my life lmao
-#[cfg(feature = "rayon")]
-impl<T: Rollable + Sync> BruteForceProbDist for T {
-    const BRUTEFORCE_TIME: Duration = Duration::from_millis(2000);
+/// Shared flag that stops any Monte Carlo sampling it is attached to
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
 
-    fn bruteforce_probdist(&self) -> ProbDist {
-        use rayon::prelude::*;
-        use std::thread::available_parallelism;
+impl CancelToken {
+    #[must_use]
+    pub fn new() -> Self {
+        CancelToken::default()
+    }
 
-        // Measure time for a single roll
-        let start = Instant::now();
-        let first_roll = self.roll_quiet();
-        let single_roll_time = start.elapsed();
-        // Initialize sample dist
-        let mut samples = SampleDist::new();
-        samples.add_sample(first_roll);
-        // Determine how many rolls to do
-        let roll_num = available_parallelism().unwrap().get() as u128
-            * Self::BRUTEFORCE_TIME.as_nanos()
-            / single_roll_time.as_nanos();
-        // Then roll them in parallel
-        let many_samples: Vec<_> = (0..roll_num)
-            .into_par_iter()
-            .map(|_| self.roll_quiet())
+    /// Stops all sampling using this token as soon as the current batch is done
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Runs f, making all Monte Carlo sampling started by it on this thread stop once token is cancelled.
+/// This makes it possible to cancel `Rollable::dist`, which has no way to pass along a token itself.
+pub fn with_cancel_token<T>(token: &CancelToken, f: impl FnOnce() -> T) -> T {
+    let prev = CANCEL.with(|cancel| cancel.replace(Some(token.clone())));
+    let out = f();
+    CANCEL.with(|cancel| *cancel.borrow_mut() = prev);
+    out
+}
+
+/// Configuration of the Monte Carlo engine, which samples a rollable until its distribution is known accurately enough
+#[derive(Clone, Copy, Debug)]
+pub struct MonteCarlo {
+    /// Sampling stops once the standard error of the probability of every outcome is below this
+    pub target_error: f64,
+    /// Sampling stops once this much time has passed, even if the target error has not been reached
+    pub time_budget: Duration,
+    /// Convergence is not checked before this many samples have been taken
+    pub min_samples: usize,
+}
+
+impl Default for MonteCarlo {
+    fn default() -> Self {
+        Self {
+            target_error: 0.001,
+            time_budget: Duration::from_millis(2000),
+            min_samples: 10_000,
+        }
+    }
+}
+
+/// Outcome of a Monte Carlo run
+#[derive(Clone, Debug, Default)]
+pub struct MonteCarloResult {
+    pub samples: SampleDist,
+    /// Estimated standard error of the probability of every sampled outcome
+    pub outcome_errors: BTreeMap<Value, f64>,
+    pub mean: f64,
+    /// Estimated standard error of the mean
+    pub mean_error: f64,
+    /// Whether the target error was reached before the time ran out
+    pub converged: bool,
+    pub cancelled: bool,
+}
+
+impl MonteCarloResult {
+    #[must_use]
+    pub fn sample_count(&self) -> usize {
+        self.samples.values().sum()
+    }
+
+    /// Largest estimated standard error of the probability of any outcome
+    #[must_use]
+    pub fn max_error(&self) -> f64 {
+        self.outcome_errors.values().copied().fold(0.0, f64::max)
+    }
+
+    #[must_use]
+    pub fn approximation(&self) -> Approximation {
+        Approximation {
+            samples: self.sample_count(),
+            max_error: self.max_error(),
+            mean_error: self.mean_error,
+        }
+    }
+
+    /// The sampled distribution, marked as approximate
+    #[must_use]
+    pub fn dist(&self) -> ProbDist {
+        let mut dist = ProbDist::from(self.samples.clone());
+        dist.set_approximation(Some(self.approximation()));
+        dist
+    }
+
+    /// Recomputes the error estimates from the samples
+    fn update_stats(&mut self) {
+        let n = self.sample_count() as f64;
+        self.mean = self
+            .samples
+            .iter()
+            .map(|(&outcome, &count)| outcome as f64 * count as f64)
+            .sum::<f64>()
+            / n;
+        let var = self
+            .samples
+            .iter()
+            .map(|(&outcome, &count)| (outcome as f64 - self.mean).powi(2) * count as f64)
+            .sum::<f64>()
+            / n;
+        self.mean_error = (var / n).sqrt();
+        // The count of every outcome is binomially distributed
+        self.outcome_errors = self
+            .samples
+            .iter()
+            .map(|(&outcome, &count)| {
+                let p = count as f64 / n;
+                (outcome, (p * (1.0 - p) / n).sqrt())
+            })
             .collect();
-        // Then add all the samples to the list, and convert the samples to a probdist
-        samples.add_samples(&many_samples);
-        let mut prob = ProbDist::default();
-        prob.read_samples(&samples);
-        prob
     }
 }
 
-/// If rayon is not enabled, fall back on single threaded impl
+impl MonteCarlo {
+    /// Samples rollable until the target error is reached, the time budget runs out, or the sampling is cancelled
+    pub fn run<R: Rollable + ?Sized>(&self, rollable: &R) -> MonteCarloResult {
+        let cancel = CANCEL.with(|cancel| cancel.borrow().clone());
+        let start = Instant::now();
+        let mut out = MonteCarloResult::default();
+        let mut batch = FIRST_BATCH;
+
+        loop {
+            let batch_start = Instant::now();
+            match sample_batch(rollable, batch) {
+                Ok(samples) => out.samples.add_samples(&samples),
+                // Errors are reported on the sampling threads, so pass them on to this one
+                Err(err) => {
+                    eval::report(err);
+                    break;
+                }
+            }
+            out.update_stats();
+
+            if out.sample_count() >= self.min_samples && out.max_error() <= self.target_error {
+                out.converged = true;
+                break;
+            }
+            if cancel.as_ref().is_some_and(CancelToken::is_cancelled) {
+                out.cancelled = true;
+                break;
+            }
+            if start.elapsed() > self.time_budget {
+                break;
+            }
+            if batch_start.elapsed() < BATCH_TIME {
+                batch *= 2;
+            }
+        }
+
+        out
+    }
+}
+
+#[cfg(feature = "rayon")]
+fn sample_batch<R: Rollable + ?Sized>(rollable: &R, n: usize) -> Result<Vec<Value>, DiceError> {
+    use rayon::prelude::*;
+
+    // The limits of this thread have to be passed on to the threads doing the sampling
+    let limits = eval::limits();
+    (0..n)
+        .into_par_iter()
+        .map(|_| eval::with_limits(limits, || eval::checked(|| rollable.roll_quiet())))
+        .collect()
+}
+
+/// If rayon is not enabled, fall back on single threaded sampling
 #[cfg(not(feature = "rayon"))]
+fn sample_batch<R: Rollable + ?Sized>(rollable: &R, n: usize) -> Result<Vec<Value>, DiceError> {
+    (0..n)
+        .map(|_| eval::checked(|| rollable.roll_quiet()))
+        .collect()
+}
+
+/// Trait enabling the bruteforcing of the probability distribution of any rollable thing
+pub trait BruteForceProbDist {
+    /// Highly approximate guideline for how long the bruteforcing operation is allowed to take
+    const BRUTEFORCE_TIME: Duration;
+
+    /// Calculate the probability distribution by force
+    fn bruteforce_probdist(&self) -> ProbDist;
+}
+
+/// Simplest blanket impl of my life lmao
 impl<T: Rollable + Sync> BruteForceProbDist for T {
     const BRUTEFORCE_TIME: Duration = Duration::from_millis(2000);
 
     fn bruteforce_probdist(&self) -> ProbDist {
-        // Measure time for a single roll
-        let start = Instant::now();
-        let first_roll = self.roll().value;
-        let single_roll_time = start.elapsed();
-        // Initialize sample dist
-        let mut samples = SampleDist::new();
-        samples.add_sample(first_roll);
-        // Determine how many rolls to do
-        let roll_num = Self::BRUTEFORCE_TIME.as_nanos() / single_roll_time.as_nanos();
-        // Then roll them
-        let many_samples: Vec<_> = (0..roll_num).map(|_| self.roll().value).collect();
-        // Then add all the samples to the list, and convert the samples to a probdist
-        samples.add_samples(&many_samples);
-        let mut prob = ProbDist::default();
-        prob.read_samples(&samples);
-        prob
+        MonteCarlo {
+            time_budget: Self::BRUTEFORCE_TIME.min(eval::limits().time_budget),
+            ..Default::default()
+        }
+        .run(self)
+        .dist()
     }
 }
@@ -1,13 +1,55 @@
 use std::{convert::Infallible, error::Error, fmt::Display};
 
+/// What went wrong, for errors that callers may want to handle differently
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// The input is not a valid expression
+    #[default]
+    Invalid,
+    /// A rolled or possible divisor was zero
+    DivisionByZero,
+    /// A result does not fit in a `Value`
+    Overflow,
+    /// Evaluating the expression would take more dice, memory or time than allowed
+    ResourceLimit,
+}
+
 #[derive(Debug, Default, Clone)]
 pub struct DiceError {
+    kind: ErrorKind,
     desc: String,
 }
 
+impl DiceError {
+    #[must_use]
+    pub fn new(kind: ErrorKind, desc: impl Into<String>) -> Self {
+        DiceError {
+            kind,
+            desc: desc.into(),
+        }
+    }
+
+    #[must_use]
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    pub(crate) fn division_by_zero() -> Self {
+        DiceError::new(ErrorKind::DivisionByZero, "division by zero")
+    }
+
+    pub(crate) fn overflow() -> Self {
+        DiceError::new(ErrorKind::Overflow, "result too large")
+    }
+
+    pub(crate) fn resource_limit(desc: impl Into<String>) -> Self {
+        DiceError::new(ErrorKind::ResourceLimit, desc)
+    }
+}
+
 impl From<String> for DiceError {
     fn from(str: String) -> Self {
-        DiceError { desc: str }
+        DiceError::new(ErrorKind::Invalid, str)
     }
 }
 
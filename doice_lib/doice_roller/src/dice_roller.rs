@@ -1,18 +1,190 @@
 use itertools::Itertools;
 use std::{cmp::Ordering, collections::BTreeMap, fmt::Debug, str::FromStr};
 
-use crate::Value;
+use crate::{eval, DiceError, Value};
 
-use super::{layouter::Layouter, prob_dist::ProbDist, RollOut, Rollable};
+use super::{layouter::Layouter, prob_dist::ProbDist, rng::with_rng, RollOut, Rollable};
 
-use rand::{distributions::Uniform, prelude::*};
+use rand::prelude::*;
 
-const MAX_DIE: usize = 1_000_000;
-const MAX_TEXT_DICE: usize = 1000;
+/// The faces of a single die
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Faces {
+    /// Every value from the first through the second, like 1 through 6 for a d6
+    Range(Value, Value),
+    /// Fate dice, worth -1, 0 or 1 and shown as -, 0 and +
+    Fudge,
+    /// Arbitrary faces, which may repeat to make a value more likely
+    Custom(Vec<Value>),
+}
+
+impl Faces {
+    /// Number of faces of the die
+    #[must_use]
+    pub fn face_count(&self) -> usize {
+        match self {
+            Faces::Range(min, max) => max.abs_diff(*min) + 1,
+            Faces::Fudge => 3,
+            Faces::Custom(faces) => faces.len(),
+        }
+    }
+
+    fn sample(&self, rng: &mut impl Rng) -> Value {
+        match self {
+            Faces::Range(min, max) => rng.gen_range(*min..=*max),
+            Faces::Fudge => rng.gen_range(-1..=1),
+            Faces::Custom(faces) => faces[rng.gen_range(0..faces.len())],
+        }
+    }
+
+    /// Distribution of a single roll of the die
+    #[must_use]
+    pub fn dist(&self) -> ProbDist {
+        let density = 1.0 / self.face_count() as f64;
+        let dist: BTreeMap<_, _> = match self {
+            Faces::Range(min, max) => (*min..=*max).map(|face| (face, density)).collect(),
+            Faces::Fudge => (-1..=1).map(|face| (face, density)).collect(),
+            Faces::Custom(faces) => faces
+                .iter()
+                .copied()
+                .counts()
+                .into_iter()
+                .map(|(face, count)| (face, count as f64 * density))
+                .collect(),
+        };
+        ProbDist::try_from(dist).unwrap_or_default()
+    }
+
+    /// Average of a single roll of the die
+    #[must_use]
+    pub fn mean(&self) -> f64 {
+        match self {
+            Faces::Range(min, max) => (*min as f64 + *max as f64) / 2.0,
+            Faces::Fudge => 0.0,
+            Faces::Custom(faces) => {
+                faces.iter().map(|&face| face as f64).sum::<f64>() / faces.len() as f64
+            }
+        }
+    }
+
+    /// Largest absolute value of any face
+    fn largest_magnitude(&self) -> Value {
+        let magnitude = |face: &Value| face.checked_abs().unwrap_or(Value::MAX);
+        match self {
+            Faces::Range(min, max) => magnitude(min).max(magnitude(max)),
+            Faces::Fudge => 1,
+            Faces::Custom(faces) => faces.iter().map(magnitude).max().unwrap_or_default(),
+        }
+    }
+
+    /// How a rolled face is shown in the text of a roll
+    fn label(&self, face: Value) -> String {
+        match (self, face) {
+            (Faces::Fudge, 1) => "+".to_string(),
+            (Faces::Fudge, -1) => "-".to_string(),
+            _ => face.to_string(),
+        }
+    }
+
+    /// Parses a list of faces separated by commas, where a single range like -2..2 stands for all values in it
+    fn parse_list(src: &str) -> Result<Self, DiceError> {
+        let parse_face = |face: &str| {
+            face.trim()
+                .parse::<Value>()
+                .map_err(|_| format!("Invalid die face {}", face.trim()))
+        };
+        let parse_range = |range: &str| -> Result<Option<(Value, Value)>, DiceError> {
+            let Some((min, max)) = range.split_once("..") else {
+                return Ok(None);
+            };
+            let (min, max) = (parse_face(min)?, parse_face(max)?);
+            if min > max {
+                return Err(format!("Invalid range of faces {}", range.trim()).into());
+            }
+            if max.abs_diff(min) >= eval::limits().max_faces {
+                return Err(too_many_faces());
+            }
+            Ok(Some((min, max)))
+        };
+
+        let entries = src.split(',').collect_vec();
+        if let [entry] = entries[..] {
+            if let Some((min, max)) = parse_range(entry)? {
+                return Ok(Faces::Range(min, max));
+            }
+        }
+
+        let mut faces = Vec::new();
+        for entry in entries {
+            match parse_range(entry)? {
+                Some((min, max)) => faces.extend(min..=max),
+                None => faces.push(parse_face(entry)?),
+            }
+            if faces.len() > eval::limits().max_faces {
+                return Err(too_many_faces());
+            }
+        }
+        Ok(Faces::Custom(faces))
+    }
+}
+
+/// A d20, like a die without a number of faces
+impl Default for Faces {
+    fn default() -> Self {
+        Faces::Range(1, 20)
+    }
+}
+
+fn too_many_faces() -> DiceError {
+    DiceError::resource_limit(format!(
+        "Die size too large! At most {} faces are allowed",
+        eval::limits().max_faces
+    ))
+}
+
+impl FromStr for Faces {
+    type Err = DiceError;
+
+    /// Parses the faces after the d of a die: a number of faces, % for a d100, F for fate dice,
+    /// or faces between braces like {0,0,1,1,2,3}, which may hold ranges like {-2..2}
+    fn from_str(src: &str) -> Result<Self, Self::Err> {
+        let src = src.trim();
+        let faces = match src {
+            "%" => Faces::Range(1, 100),
+            "F" | "f" => Faces::Fudge,
+            _ => match src.strip_prefix('{') {
+                Some(faces) => {
+                    let faces = faces
+                        .strip_suffix('}')
+                        .ok_or("Improper dice definition! [no '}']")?;
+                    Self::parse_list(faces)?
+                }
+                None => {
+                    // Parse the leading number of faces, 20 seems like sensible default
+                    let type_len = src.chars().take_while(|c| c.is_numeric()).count();
+                    let dice_type = match &src[..type_len] {
+                        "" => 20,
+                        // Too many faces to even parse are too many faces
+                        dice_type => dice_type.parse().unwrap_or(Value::MAX),
+                    };
+                    if dice_type == 0 {
+                        return Err("0-dice are not supported!".into());
+                    }
+                    Faces::Range(1, dice_type)
+                }
+            },
+        };
+
+        if faces.face_count() > eval::limits().max_faces {
+            return Err(too_many_faces());
+        }
+        Ok(faces)
+    }
+}
 
 #[derive(Clone, Default, Debug)]
 pub struct DiceRoller {
-    dice_type: usize,
+    faces: Faces,
     dice_count: usize,
     advantage: isize,
 }
@@ -22,8 +194,14 @@ impl DiceRoller {
     /// The normal maximum values for a diceroll are not enforced here!
     #[must_use]
     pub fn new(dice_type: usize, dice_count: usize, advantage: isize) -> Self {
+        Self::with_faces(Faces::Range(1, dice_type as Value), dice_count, advantage)
+    }
+
+    /// Like new, for dice with any faces
+    #[must_use]
+    pub fn with_faces(faces: Faces, dice_count: usize, advantage: isize) -> Self {
         Self {
-            dice_type,
+            faces,
             dice_count,
             advantage,
         }
@@ -32,14 +210,12 @@ impl DiceRoller {
     /// Calculates the average of itself, not taking into account any modifiers like advantage or adapters
     #[must_use]
     pub fn unmod_avg(&self) -> f64 {
-        (self.dice_count * self.dice_type) as f64 / 2.0 + 0.5
+        self.dice_count as f64 * self.faces.mean()
     }
 
     /// Rolls n dice, without producing text
     #[must_use]
     pub fn roll_n_quiet(&self, n: usize) -> Vec<isize> {
-        let mut rng = thread_rng();
-        let dist = Uniform::<isize>::new(1, self.dice_type as isize + 1);
         let mut rolls = Vec::with_capacity(n);
         let mut buf = vec![0; 1 + self.advantage.unsigned_abs()];
 
@@ -47,7 +223,7 @@ impl DiceRoller {
         for _ in 0..n {
             // Refill the buffer with rolls
             for num in buf.iter_mut() {
-                *num = rng.sample(dist);
+                *num = with_rng(|rng| self.faces.sample(rng));
             }
             rolls.push(match self.advantage.cmp(&0) {
                 Ordering::Equal => buf[0],
@@ -75,9 +251,15 @@ impl DiceRoller {
         rolls
     }
 
+    /// Number of faces of a single die
     #[must_use]
     pub fn dice_type(&self) -> usize {
-        self.dice_type
+        self.faces.face_count()
+    }
+
+    #[must_use]
+    pub fn faces(&self) -> &Faces {
+        &self.faces
     }
 
     #[must_use]
@@ -96,22 +278,27 @@ impl DiceRoller {
 }
 
 impl FromStr for DiceRoller {
-    type Err = String;
+    type Err = DiceError;
 
     fn from_str(value: &str) -> Result<Self, Self::Err> {
         let value = value.trim();
         // Determine the length of the number used to set the number of dice
         let count_len = value.chars().take_while(|c| c.is_numeric()).count();
-        // Try to parse the number of dice, on fail use 1
-        let dice_count = value[0..count_len].parse().unwrap_or(1);
+        // Parse the number of dice, which is 1 if it is left out
+        let dice_count = match &value[0..count_len] {
+            "" => 1,
+            count => count.parse().unwrap_or(usize::MAX),
+        };
+        let max_dice = eval::limits().max_dice;
+        if dice_count > max_dice {
+            return Err(DiceError::resource_limit(format!(
+                "Too many dice! At most {max_dice} are allowed"
+            )));
+        }
         // Check if d is present
         let mut chars = value.chars().skip(count_len);
-        if chars
-            .next()
-            .ok_or_else(|| "Dice string too short!".to_string())?
-            != 'd'
-        {
-            return Err("Improper dice definition! [no d]".to_string());
+        if chars.next().ok_or("Dice string too short!")? != 'd' {
+            return Err("Improper dice definition! [no d]".into());
         }
 
         // Handling advantage flags
@@ -126,33 +313,20 @@ impl FromStr for DiceRoller {
             };
         }
 
-        // Determine where the dice type should start
-        let pre_type_len = count_len + 1 + adv_flag_count;
-        // Determine how long the dice type is
-        let type_len = value
-            .chars()
-            .skip(pre_type_len)
-            .take_while(|c| c.is_numeric())
-            .count();
-        // Then parse it, 20 seems like sensible default
-        let dice_type = value[pre_type_len..(pre_type_len + type_len)]
-            .parse()
-            .unwrap_or(20);
-
-        if dice_type > MAX_DIE {
-            return Err("Die size too large!".to_string());
-        }
-
-        if dice_type == 0 {
-            return Err("0-dice are not supported!".to_string());
+        // Everything after the d and advantage flags describes the faces
+        let faces: Faces = value[count_len + 1 + adv_flag_count..].parse()?;
+        // Make sure the total of all dice fits in a value
+        let largest = faces.largest_magnitude();
+        if Value::try_from(dice_count)
+            .ok()
+            .and_then(|count| count.checked_mul(largest))
+            .is_none()
+        {
+            return Err(DiceError::overflow());
         }
 
-        // if dice_count > MAX_DICE_COUNT {
-        //     return Err("Too many dice!".to_string());
-        // }
-
         let roller = DiceRoller {
-            dice_type,
+            faces,
             dice_count,
             advantage,
         };
@@ -163,12 +337,10 @@ impl FromStr for DiceRoller {
 
 impl Rollable for DiceRoller {
     fn roll(&self) -> super::RollOut {
-        let mut rng = thread_rng();
-        let dist = Uniform::<isize>::new(1, self.dice_type as isize + 1);
         let mut roll_total = 0;
         let mut buf = vec![0; 1 + self.advantage.unsigned_abs()];
         let mut out_txt = Layouter::default();
-        let do_txt = self.dice_count < MAX_TEXT_DICE;
+        let do_txt = self.dice_count < eval::limits().max_text_dice;
 
         if self.dice_count > 1 {
             out_txt.append("[");
@@ -178,10 +350,10 @@ impl Rollable for DiceRoller {
         for count in 0..self.dice_count {
             // Refill the buffer with rolls
             for num in &mut buf {
-                *num = rng.sample(dist);
+                *num = with_rng(|rng| self.faces.sample(rng));
             }
             let mut used_i = 0;
-            roll_total += match self.advantage.cmp(&0) {
+            let roll = match self.advantage.cmp(&0) {
                 Ordering::Equal => buf[0],
                 Ordering::Greater => {
                     let roll;
@@ -202,13 +374,14 @@ impl Rollable for DiceRoller {
                     *roll
                 }
             };
+            roll_total = eval::add(roll_total, roll);
 
             // Add the text for this one diceroll
             if do_txt {
                 out_txt.append("[");
                 for (i, elem) in buf
                     .iter()
-                    .map(ToString::to_string)
+                    .map(|face| self.faces.label(*face))
                     .enumerate()
                     .intersperse((usize::MAX, " ".to_string()))
                 {
@@ -237,16 +410,13 @@ impl Rollable for DiceRoller {
         RollOut {
             value: roll_total,
             txt: out_txt,
+            ..Default::default()
         }
     }
 
     fn dist(&self) -> super::prob_dist::ProbDist {
         // Build distribution for single die
-        let density: f64 = 1.0f64 / (self.dice_type as f64);
-        let dist: BTreeMap<_, _> = (1..=self.dice_type)
-            .map(|res| (res as isize, density))
-            .collect();
-        let mut dist = ProbDist::try_from(dist).expect("Bad single die probability distribution!");
+        let mut dist = self.faces.dist();
         // Extend it for advantage
         dist.apply_advantage(self.advantage);
 
@@ -257,15 +427,13 @@ impl Rollable for DiceRoller {
     }
 
     fn roll_quiet(&self) -> isize {
-        let mut rng = thread_rng();
-        let dist = Uniform::<isize>::new(1, self.dice_type as Value + 1);
         let mut buf = vec![0; 1 + self.advantage.unsigned_abs()].into_boxed_slice();
 
         // Perform the dice rolls, with (dis)advantage
         let rolls = (0..self.dice_count).map(|_| {
             // Refill the buffer with rolls
             for num in buf.iter_mut() {
-                *num = rng.sample(dist);
+                *num = with_rng(|rng| self.faces.sample(rng));
             }
             // Then take the correct value based on the advantage
             match self.advantage.cmp(&0) {
@@ -275,6 +443,6 @@ impl Rollable for DiceRoller {
             }
         });
 
-        rolls.sum()
+        eval::sum(rolls)
     }
 }
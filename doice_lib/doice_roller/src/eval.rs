@@ -0,0 +1,111 @@
+use std::{
+    cell::{Cell, RefCell},
+    time::Duration,
+};
+
+use crate::{DiceError, Value};
+
+/// Bounds on the work of parsing, rolling and computing the distribution of an expression,
+/// which allow evaluating untrusted input safely. Exceeding them results in an `ErrorKind::ResourceLimit` error,
+/// or in an approximation where one is available
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EvalLimits {
+    /// Most dice rolled at once, like the 10 in 10d6
+    pub max_dice: usize,
+    /// Most faces a single die may have
+    pub max_faces: usize,
+    /// Deepest nesting of parentheses and braces in an expression
+    pub max_depth: usize,
+    /// Most outcomes a distribution may have
+    pub max_support: usize,
+    /// Time computing a distribution may take. Sums of many dice are approximated after a fifth of it
+    pub time_budget: Duration,
+    /// Beyond this many terms, sums of dice are approximated using the Central Limit Theorem (CLT)
+    pub clt_threshold: usize,
+    /// Rolls of this many dice or more leave the individual dice out of their text
+    pub max_text_dice: usize,
+    /// Roll texts with more sections than this are abbreviated when displayed
+    pub max_text_sections: usize,
+}
+
+impl Default for EvalLimits {
+    fn default() -> Self {
+        EvalLimits {
+            max_dice: 1_000_000,
+            max_faces: 1_000_000,
+            max_depth: 64,
+            max_support: 1_000_000,
+            time_budget: Duration::from_millis(10_000),
+            clt_threshold: 1000,
+            max_text_dice: 1000,
+            max_text_sections: 100,
+        }
+    }
+}
+
+thread_local! {
+    /// The first error reported during the current evaluation on this thread, see `checked`
+    static ERROR: RefCell<Option<DiceError>> = const { RefCell::new(None) };
+    /// The limits of the current evaluation on this thread, see `with_limits`
+    static LIMITS: Cell<EvalLimits> = Cell::new(EvalLimits::default());
+}
+
+/// The limits of the evaluation running on this thread
+pub(crate) fn limits() -> EvalLimits {
+    LIMITS.with(Cell::get)
+}
+
+/// Runs f under the given limits
+pub(crate) fn with_limits<T>(limits: EvalLimits, f: impl FnOnce() -> T) -> T {
+    let outer = LIMITS.with(|current| current.replace(limits));
+    let out = f();
+    LIMITS.with(|current| current.set(outer));
+    out
+}
+
+/// Records an error that occurred while rolling or computing a distribution.
+/// `Rollable` has no way to return errors, so evaluation carries on with a fallback value, and `checked` picks the error up
+pub(crate) fn report(err: DiceError) {
+    ERROR.with(|error| {
+        error.borrow_mut().get_or_insert(err);
+    });
+}
+
+/// Runs f, resulting in the first error reported while it ran, if any
+pub(crate) fn checked<T>(f: impl FnOnce() -> T) -> Result<T, DiceError> {
+    let outer = ERROR.with(RefCell::take);
+    let out = f();
+    match ERROR.with(|error| error.replace(outer)) {
+        Some(err) => Err(err),
+        None => Ok(out),
+    }
+}
+
+/// Adds, reporting an overflow and saturating if the result does not fit
+pub(crate) fn add(lhs: Value, rhs: Value) -> Value {
+    lhs.checked_add(rhs).unwrap_or_else(|| {
+        report(DiceError::overflow());
+        lhs.saturating_add(rhs)
+    })
+}
+
+/// Multiplies, reporting an overflow and saturating if the result does not fit
+pub(crate) fn mul(lhs: Value, rhs: Value) -> Value {
+    lhs.checked_mul(rhs).unwrap_or_else(|| {
+        report(DiceError::overflow());
+        lhs.saturating_mul(rhs)
+    })
+}
+
+/// Negates, reporting an overflow for `Value::MIN`
+pub(crate) fn neg(value: Value) -> Value {
+    value.checked_neg().unwrap_or_else(|| {
+        report(DiceError::overflow());
+        Value::MAX
+    })
+}
+
+/// Sums values, reporting an overflow if the sum does not fit
+pub(crate) fn sum(values: impl IntoIterator<Item = Value>) -> Value {
+    values.into_iter().fold(0, add)
+}
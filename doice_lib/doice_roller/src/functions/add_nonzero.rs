@@ -1,7 +1,6 @@
 use std::str::FromStr;
 
 use crate::{
-    bruteforce::BruteForceProbDist,
     structure::{expression::Expression, lin_comb::LinComb},
     utils::split_once_parenth,
     DiceError, Layouter, ProbDist, RollOut, Rollable,
@@ -42,6 +41,13 @@ impl Rollable for AddNonZero {
     }
 
     fn dist(&self) -> ProbDist {
-        self.bruteforce_probdist()
+        let added = self.added.dist();
+        ProbDist::from_parameter_distribution(&self.base.dist(), |base| {
+            if base == 0 {
+                ProbDist::constant(0)
+            } else {
+                added.clone() + base
+            }
+        })
     }
 }
@@ -0,0 +1,245 @@
+use std::str::FromStr;
+
+use crate::{
+    eval,
+    structure::lin_comb::LinComb,
+    utils::{ceil_div, floor_div, round_div, split_once_parenth, split_parenth},
+    DiceError, Expression, Layouter, ProbDist, RollOut, Rollable, Value,
+};
+
+use super::FunctionInit;
+
+/// How the result of a division is rounded
+#[derive(Clone, Copy, Debug)]
+enum Rounding {
+    Floor,
+    Ceil,
+    Round,
+}
+
+impl Rounding {
+    fn name(self) -> &'static str {
+        match self {
+            Rounding::Floor => "floor",
+            Rounding::Ceil => "ceil",
+            Rounding::Round => "round",
+        }
+    }
+
+    fn divide(self, lhs: Value, rhs: Value) -> Value {
+        match self {
+            Rounding::Floor => floor_div(lhs, rhs),
+            Rounding::Ceil => ceil_div(lhs, rhs),
+            Rounding::Round => round_div(lhs, rhs),
+        }
+    }
+}
+
+/// A division with explicit rounding, like ceil(d6/2)
+#[derive(Clone, Debug)]
+pub struct Division<const ROUNDING: u8> {
+    numerator: Expression,
+    denominator: Expression,
+}
+
+pub type Floor = Division<0>;
+pub type Ceil = Division<1>;
+pub type Round = Division<2>;
+
+impl<const ROUNDING: u8> Division<ROUNDING> {
+    const ROUNDING: Rounding = match ROUNDING {
+        0 => Rounding::Floor,
+        1 => Rounding::Ceil,
+        _ => Rounding::Round,
+    };
+}
+
+impl<const ROUNDING: u8> FunctionInit for Division<ROUNDING> {
+    const DOC: &'static str = match ROUNDING {
+        0 => "Divides, rounding down. Division by zero results in 0.\nUsage: floor(expr / expr)",
+        1 => "Divides, rounding up. Division by zero results in 0.\nUsage: ceil(expr / expr)",
+        _ => "Divides, rounding to the nearest integer with halves rounded up. Division by zero results in 0.\nUsage: round(expr / expr)",
+    };
+
+    fn generate(input: &str) -> Result<Expression, DiceError> {
+        let name = Self::ROUNDING.name();
+        let (numerator, denominator) = split_once_parenth(input, '/')
+            .ok_or_else(|| format!("{name}: expected a division like {name}(a/b)"))?;
+
+        Ok(Self {
+            numerator: LinComb::from_str(numerator)?.into(),
+            denominator: LinComb::from_str(denominator)?.into(),
+        }
+        .into())
+    }
+}
+
+impl<const ROUNDING: u8> Rollable for Division<ROUNDING> {
+    fn roll(&self) -> RollOut {
+        let numerator = self.numerator.roll();
+        let denominator = self.denominator.roll();
+
+        let mut txt = Layouter::from(Self::ROUNDING.name());
+        txt.append("(");
+        txt += numerator.txt;
+        txt.append("/");
+        txt += denominator.txt;
+        txt.append(")");
+
+        RollOut {
+            value: Self::ROUNDING.divide(numerator.value, denominator.value),
+            txt,
+            ..Default::default()
+        }
+    }
+
+    fn dist(&self) -> ProbDist {
+        self.numerator
+            .dist()
+            .map_pairs(&self.denominator.dist(), |lhs, rhs| {
+                Self::ROUNDING.divide(lhs, rhs)
+            })
+    }
+
+    fn roll_quiet(&self) -> Value {
+        Self::ROUNDING.divide(self.numerator.roll_quiet(), self.denominator.roll_quiet())
+    }
+}
+
+fn abs(value: Value) -> Value {
+    if value < 0 {
+        eval::neg(value)
+    } else {
+        value
+    }
+}
+
+/// The absolute value of an expression
+#[derive(Clone, Debug)]
+pub struct Abs {
+    expr: Expression,
+}
+
+impl FunctionInit for Abs {
+    const DOC: &'static str = "The absolute value of the expression.\nUsage: abs(expr)";
+
+    fn generate(input: &str) -> Result<Expression, DiceError> {
+        Ok(Abs {
+            expr: LinComb::from_str(input)?.into(),
+        }
+        .into())
+    }
+}
+
+impl Rollable for Abs {
+    fn roll(&self) -> RollOut {
+        let out = self.expr.roll();
+        let mut txt = Layouter::from("|");
+        txt += out.txt;
+        txt.append("|");
+
+        RollOut {
+            value: abs(out.value),
+            txt,
+            ..Default::default()
+        }
+    }
+
+    fn dist(&self) -> ProbDist {
+        self.expr.dist().map_outcomes(abs)
+    }
+
+    fn roll_quiet(&self) -> Value {
+        abs(self.expr.roll_quiet())
+    }
+}
+
+/// An expression limited to a range of values
+#[derive(Clone, Debug)]
+pub struct Clamp {
+    expr: Expression,
+    min: Value,
+    max: Value,
+}
+
+impl FunctionInit for Clamp {
+    const DOC: &'static str = "Limits the expression to at least min and at most max, like half damage with a minimum of 1: clamp(2d6/2, 1, 6).\nUsage: clamp(expr, min, max)";
+
+    fn generate(input: &str) -> Result<Expression, DiceError> {
+        let [expr, min, max] = split_parenth(input, ',')[..] else {
+            return Err("clamp: expected an expression, a minimum and a maximum".into());
+        };
+        let bound = |bound: &str| {
+            bound
+                .trim()
+                .parse::<Value>()
+                .map_err(|_| format!("clamp: invalid bound {}", bound.trim()))
+        };
+        let (min, max) = (bound(min)?, bound(max)?);
+        if min > max {
+            return Err("clamp: the minimum may not exceed the maximum".into());
+        }
+
+        Ok(Clamp {
+            expr: LinComb::from_str(expr)?.into(),
+            min,
+            max,
+        }
+        .into())
+    }
+}
+
+impl Rollable for Clamp {
+    fn roll(&self) -> RollOut {
+        let out = self.expr.roll();
+        let mut txt = Layouter::from("clamp(");
+        txt += out.txt;
+        txt.append(&format!(", {}, {})", self.min, self.max));
+
+        RollOut {
+            value: out.value.clamp(self.min, self.max),
+            txt,
+            ..Default::default()
+        }
+    }
+
+    fn dist(&self) -> ProbDist {
+        self.expr
+            .dist()
+            .map_outcomes(|outcome| outcome.clamp(self.min, self.max))
+    }
+
+    fn roll_quiet(&self) -> Value {
+        self.expr.roll_quiet().clamp(self.min, self.max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Roll, Rollable};
+
+    fn dist(src: &str) -> Vec<(isize, f64)> {
+        let dist = src.parse::<Roll>().unwrap().dist();
+        dist.iter().map(|(&k, &v)| (k, v)).collect()
+    }
+
+    #[test]
+    fn rounding_test() {
+        assert_eq!(dist("floor(7/2)"), [(3, 1.0)]);
+        assert_eq!(dist("ceil(7/2)"), [(4, 1.0)]);
+        assert_eq!(dist("round(7/2)"), [(4, 1.0)]);
+        assert_eq!(dist("round(-7/2)"), [(-3, 1.0)]);
+        assert_eq!(dist("ceil(-7/2)"), [(-3, 1.0)]);
+        assert_eq!(dist("ceil(d4/2)").len(), 2);
+        assert!("ceil(d4)".parse::<Roll>().is_err());
+    }
+
+    #[test]
+    fn half_damage_test() {
+        // Half of a d4 rounded down, but at least 1
+        let half = dist("clamp(d4/2, 1, 10)");
+        assert_eq!(half, [(1, 0.75), (2, 0.25)]);
+        assert_eq!(dist("abs(d2 - 2)"), [(0, 0.5), (1, 0.5)]);
+        assert!("clamp(d6, 3, 1)".parse::<Roll>().is_err());
+    }
+}
@@ -1,6 +1,6 @@
 use std::str::FromStr;
 
-use crate::{BruteForceProbDist, DiceRoller, Expression, Layouter, RollOut, Rollable, Value};
+use crate::{eval, DiceRoller, Expression, Layouter, ProbDist, RollOut, Rollable, Value};
 
 use super::{crit::Crit, FunctionInit};
 
@@ -19,30 +19,21 @@ pub struct Attack {
 impl Rollable for Attack {
     fn roll(&self) -> RollOut {
         let to_hit_roll = self.d20.roll_quiet();
-        let to_hit = to_hit_roll + self.to_hit_bonus;
+        let to_hit = eval::add(to_hit_roll, self.to_hit_bonus);
         let mut out_txt = Layouter::new();
         let ac = self.ac;
 
         // Handle to hit
         let damage = match to_hit_roll {
-            // Miss
-            1 => 0,
-            // Maybe hit
-            2..=19 => {
-                if to_hit >= ac as Value {
-                    self.rolls.roll_quiet() + self.dmg_bonus as Value
-                } else {
-                    0
-                }
-            }
+            // Hit
+            2..=19 if to_hit >= ac => eval::add(self.rolls.roll_quiet(), self.dmg_bonus),
             // Crit
-            20 => {
-                self.critters.iter().map(|c| c.roll_quiet()).sum::<Value>()
-                    + self.dmg_bonus as Value
-            }
-            _ => {
-                panic!("Invalid d20 roll.")
-            }
+            20 => eval::add(
+                eval::sum(self.critters.iter().map(|c| c.roll_quiet())),
+                self.dmg_bonus,
+            ),
+            // Miss
+            _ => 0,
         };
 
         out_txt.append(&format!(
@@ -53,11 +44,24 @@ impl Rollable for Attack {
         RollOut {
             value: damage,
             txt: out_txt,
+            ..Default::default()
         }
     }
 
-    fn dist(&self) -> crate::ProbDist {
-        self.bruteforce_probdist()
+    /// Mixes the damage distributions of a miss, a hit and a crit, weighed by the to hit roll
+    fn dist(&self) -> ProbDist {
+        let hit = self.rolls.dist() + self.dmg_bonus;
+        let crit = self
+            .critters
+            .iter()
+            .fold(ProbDist::default(), |acc, c| acc + &c.dist())
+            + self.dmg_bonus;
+
+        ProbDist::from_parameter_distribution(&self.d20.dist(), |to_hit_roll| match to_hit_roll {
+            20 => crit.clone(),
+            2..=19 if eval::add(to_hit_roll, self.to_hit_bonus) >= self.ac => hit.clone(),
+            _ => ProbDist::constant(0),
+        })
     }
 }
 
@@ -80,11 +84,11 @@ impl FunctionInit for Attack {
             false
         };
 
-        let crit_vec = vec![Crit::generate(dmg_roll).unwrap()];
+        let crit_vec = vec![Crit::generate(dmg_roll)?];
         Ok(Attack {
-            rolls: DiceRoller::from_str(dmg_roll).unwrap().into(),
+            rolls: DiceRoller::from_str(dmg_roll)?.into(),
             dmg_bonus: dmg_bonus.trim().parse().or(Err("invalid dmg bonus"))?,
-            d20: if adv { "d|" } else { "d" }.parse().unwrap(),
+            d20: if adv { "d|" } else { "d" }.parse()?,
             ac: ac_txt.trim().parse().or(Err("invalid ac"))?,
             to_hit_bonus: to_hit_bonus
                 .trim()
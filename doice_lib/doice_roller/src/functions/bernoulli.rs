@@ -1,8 +1,8 @@
 use std::collections::BTreeMap;
 
-use rand::{distributions::Uniform, thread_rng, Rng};
+use rand::{distributions::Uniform, Rng};
 
-use crate::{ProbDist, RollOut, Rollable};
+use crate::{rng::with_rng, ProbDist, RollOut, Rollable};
 
 use super::FunctionInit;
 
@@ -25,7 +25,10 @@ impl FunctionInit for Bernoulli {
     fn generate(input: &str) -> Result<crate::structure::expression::Expression, crate::DiceError> {
         let input = input.trim();
         // If valid probability has been provided, use it
-        if let Ok(p) = input.parse() {
+        if let Ok(p) = input.parse::<f64>() {
+            if !(0.0..=1.0).contains(&p) {
+                return Err("ber: p must be a probability between 0 and 1".into());
+            }
             Ok(Self { p }.into())
         // Otherwise use default
         } else {
@@ -37,31 +40,30 @@ impl FunctionInit for Bernoulli {
 impl Rollable for Bernoulli {
     fn roll(&self) -> RollOut {
         let dist = Uniform::new(0.0, 1.0);
-        let mut rng = thread_rng();
         // If success, return 1
-        if self.p > rng.sample(dist) {
+        if self.p > with_rng(|rng| rng.sample(dist)) {
             RollOut {
                 value: 1,
                 txt: "[1]".into(),
+                ..Default::default()
             }
         // Otherwise, return 0
         } else {
             RollOut {
                 value: 0,
                 txt: "[0]".into(),
+                ..Default::default()
             }
         }
     }
 
-    /// May panic if p is not within 0.0..=1.0
     fn dist(&self) -> crate::ProbDist {
-        ProbDist::try_from(BTreeMap::from([(0, 1.0 - self.p), (1, self.p)])).unwrap()
+        ProbDist::try_from(BTreeMap::from([(0, 1.0 - self.p), (1, self.p)])).unwrap_or_default()
     }
 
     fn roll_quiet(&self) -> crate::Value {
         let dist = Uniform::new(0.0, 1.0);
-        let mut rng = thread_rng();
         // If success, return 1, otherwise return 0
-        (self.p > rng.sample(dist)).into()
+        (self.p > with_rng(|rng| rng.sample(dist))).into()
     }
 }
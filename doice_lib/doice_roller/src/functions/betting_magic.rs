@@ -43,7 +43,7 @@ impl Rollable for BettingMagic {
 
         crate::RollOut {
             value: point as isize,
-            txt: Default::default(),
+            ..Default::default()
         }
     }
 
@@ -107,7 +107,7 @@ impl Rollable for BettingMagic2 {
         if point == 0 {
             return RollOut {
                 value: own_chips + self.late_chips,
-                txt: Default::default(),
+                ..Default::default()
             };
         }
 
@@ -142,7 +142,7 @@ impl Rollable for BettingMagic2 {
 
         RollOut {
             value: (point + self.late_chips) * sadist.roll_quiet(),
-            txt: Default::default(),
+            ..Default::default()
         }
     }
 
@@ -206,7 +206,7 @@ impl Rollable for BettingMagic3 {
         if point == 0 {
             return RollOut {
                 value: own_chips + self.late_chips,
-                txt: Default::default(),
+                ..Default::default()
             };
         }
 
@@ -246,7 +246,7 @@ impl Rollable for BettingMagic3 {
 
         RollOut {
             value: (point + self.late_chips) * sadist.roll_quiet(),
-            txt: Default::default(),
+            ..Default::default()
         }
     }
 
@@ -1,62 +1,376 @@
-use crate::{DiceError, ProbDist, Rollable, RollOut};
-use crate::bruteforce::BruteForceProbDist;
+use std::{
+    collections::{BTreeMap, HashMap},
+    str::FromStr,
+};
+
+use egui::Color32;
+use itertools::Itertools;
+
+use crate::functions::deck::{Card, Deck};
 use crate::functions::FunctionInit;
-use crate::functions::outcomes::Outcomes;
 use crate::structure::expression::Expression;
+use crate::utils::split_parenth;
+use crate::{DiceError, Layouter, ProbDist, RollOut, Rollable, Value};
+
+const MAX_DECKS: usize = 8;
+/// Final hand value standing for a bust
+const BUST: Value = 0;
+/// Final hand value standing for a natural blackjack, which beats any other 21
+const NATURAL: Value = 22;
+
+/// Number of cards of every value left in the shoe, with aces at index 0 and all tens at index 9
+type Shoe = [u8; 10];
+
+/// When a hand stops drawing cards
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Strategy {
+    stand_on: Value,
+    /// Whether to keep hitting on a soft hand worth exactly `stand_on`, like a dealer hitting soft 17
+    hit_soft: bool,
+}
+
+impl Strategy {
+    fn stands(self, hand: Hand) -> bool {
+        let total = hand.total();
+        total > self.stand_on || (total == self.stand_on && !(self.hit_soft && hand.is_soft()))
+    }
+}
+
+impl Default for Strategy {
+    fn default() -> Self {
+        Strategy {
+            stand_on: 17,
+            hit_soft: false,
+        }
+    }
+}
+
+impl FromStr for Strategy {
+    type Err = DiceError;
+
+    /// Parses a value to stand on, like 17 or s17, or h17 to also hit on a soft 17
+    fn from_str(src: &str) -> Result<Self, Self::Err> {
+        let src = src.trim();
+        let (stand_on, hit_soft) = match src.strip_prefix('h') {
+            Some(stand_on) => (stand_on, true),
+            None => (src.strip_prefix('s').unwrap_or(src), false),
+        };
+
+        Ok(Strategy {
+            stand_on: stand_on
+                .parse()
+                .map_err(|_| format!("blackjack: invalid strategy {src}"))?,
+            hit_soft,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Hand {
+    /// Value of the hand with all aces counted as 1
+    hard: Value,
+    has_ace: bool,
+    cards: usize,
+}
+
+impl Hand {
+    /// Adds a card worth 1 (an ace) through 10
+    fn add(self, value: Value) -> Self {
+        Hand {
+            hard: self.hard + value,
+            has_ace: self.has_ace || value == 1,
+            cards: self.cards + 1,
+        }
+    }
+
+    /// A hand is soft if one of its aces counts as 11
+    fn is_soft(self) -> bool {
+        self.has_ace && self.hard + 10 <= 21
+    }
+
+    fn total(self) -> Value {
+        if self.is_soft() {
+            self.hard + 10
+        } else {
+            self.hard
+        }
+    }
 
+    /// The total, or `BUST` or `NATURAL`
+    fn final_value(self) -> Value {
+        match self.total() {
+            total if total > 21 => BUST,
+            21 if self.cards == 2 => NATURAL,
+            total => total,
+        }
+    }
+
+    /// Whether the hand is done drawing cards
+    fn is_done(self, strategy: Strategy) -> bool {
+        self.cards >= 2 && (self.total() > 21 || strategy.stands(self))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Mode {
+    /// The final value of the player's hand
+    Hand,
+    /// 1 for a win, 0 for a push and -1 for a loss
+    Result,
+}
+
+/// A single hand of blackjack, played from a shuffled shoe
 #[derive(Debug, Clone)]
 pub struct Blackjack {
-    rounds: usize,
-    initial: isize,
-    outcomes: Expression,
+    mode: Mode,
+    player: Strategy,
+    dealer: Strategy,
+    decks: usize,
+    shoe: Deck,
+}
+
+/// Result of a hand for the player, given the final values of both hands
+fn settle(player: Value, dealer: Value) -> Value {
+    match (player, dealer) {
+        (BUST, _) => -1,
+        (_, BUST) => 1,
+        (player, dealer) => (player - dealer).signum(),
+    }
+}
+
+/// Value of a card for blackjack, with aces worth 1
+fn card_value(card: &Card) -> Value {
+    card.value.min(10)
 }
 
 impl Blackjack {
-    fn sum_total(&self, rolls: &[isize]) -> isize {
-        let total = rolls.iter().sum::<isize>() + self.initial;
-        if total > 21 {
-            0
-        } else {
-            total
+    fn outcome(&self, player: Value, dealer: Value) -> Value {
+        match self.mode {
+            Mode::Hand if player == NATURAL => 21,
+            Mode::Hand => player,
+            Mode::Result => settle(player, dealer),
+        }
+    }
+
+    /// Probabilities of the final values of the dealer's hand, when drawn from the shoe.
+    /// The hand is determined by the cards missing from the shoe, so the shoe alone suffices as key
+    fn dealer_dist(
+        &self,
+        shoe: Shoe,
+        hand: Hand,
+        memo: &mut HashMap<Shoe, Vec<(Value, f64)>>,
+    ) -> Vec<(Value, f64)> {
+        if hand.is_done(self.dealer) {
+            return vec![(hand.final_value(), 1.0)];
+        }
+        if let Some(dist) = memo.get(&shoe) {
+            return dist.clone();
+        }
+
+        let mut dist = BTreeMap::new();
+        for_each_card(shoe, |value, next_shoe, prob| {
+            for (outcome, outcome_prob) in self.dealer_dist(next_shoe, hand.add(value), memo) {
+                *dist.entry(outcome).or_insert(0.0) += prob * outcome_prob;
+            }
+        });
+        let dist = dist.into_iter().collect_vec();
+        memo.insert(shoe, dist.clone());
+        dist
+    }
+
+    /// Plays out every way the player's hand can go, adding the outcomes to out.
+    /// As the player's strategy does not depend on the dealer's cards, the player can draw all cards first
+    fn player_dist(
+        &self,
+        shoe: Shoe,
+        hand: Hand,
+        prob: f64,
+        dealer_memo: &mut HashMap<Shoe, Vec<(Value, f64)>>,
+        out: &mut BTreeMap<Value, f64>,
+    ) {
+        if hand.is_done(self.player) {
+            let player = hand.final_value();
+            if player == BUST || self.mode == Mode::Hand {
+                *out.entry(self.outcome(player, BUST)).or_insert(0.0) += prob;
+                return;
+            }
+            let dealer = dealer_memo
+                .get(&shoe)
+                .cloned()
+                .unwrap_or_else(|| self.dealer_dist(shoe, Hand::default(), &mut HashMap::new()));
+            for &(dealer, dealer_prob) in &dealer {
+                *out.entry(self.outcome(player, dealer)).or_insert(0.0) += prob * dealer_prob;
+            }
+            dealer_memo.insert(shoe, dealer);
+            return;
+        }
+
+        for_each_card(shoe, |value, next_shoe, card_prob| {
+            self.player_dist(
+                next_shoe,
+                hand.add(value),
+                prob * card_prob,
+                dealer_memo,
+                out,
+            );
+        });
+    }
+
+    /// Draws cards from the top of the shoe until the strategy stands
+    fn play<'a>(
+        strategy: Strategy,
+        shoe: &mut impl Iterator<Item = &'a Card>,
+    ) -> (Hand, Vec<&'a Card>) {
+        let mut hand = Hand::default();
+        let mut cards = Vec::new();
+        while !hand.is_done(strategy) {
+            let Some(card) = shoe.next() else { break };
+            hand = hand.add(card_value(card));
+            cards.push(card);
+        }
+        (hand, cards)
+    }
+}
+
+/// Calls f for every card value left in the shoe, with the shoe after drawing it and the probability of drawing it
+fn for_each_card(shoe: Shoe, mut f: impl FnMut(Value, Shoe, f64)) {
+    let remaining: u32 = shoe.iter().map(|&count| u32::from(count)).sum();
+    for (index, &count) in shoe.iter().enumerate() {
+        if count == 0 {
+            continue;
         }
+        let mut next = shoe;
+        next[index] -= 1;
+        f(
+            index as Value + 1,
+            next,
+            f64::from(count) / f64::from(remaining),
+        );
+    }
+}
+
+fn append_hand(txt: &mut Layouter, name: &str, hand: Hand, cards: &[&Card]) {
+    txt.append(&format!(
+        "{name}: {} = ",
+        cards.iter().map(|card| &card.name).join(" ")
+    ));
+    match hand.final_value() {
+        BUST => txt.append_colored("bust", Color32::RED),
+        NATURAL => txt.append_colored("blackjack", Color32::GREEN),
+        total => txt.append(&total.to_string()),
     }
 }
 
 impl FunctionInit for Blackjack {
-    const DOC: &'static str = "Returns the outcome of n rounds of blackjack.\nUsage: blackjack(n, (initial hand value))";
+    const DOC: &'static str = "Plays a hand of blackjack from a shuffled shoe, resulting in the final value of the player's hand (0 when bust),\nor with result, in 1 for a win, 0 for a push and -1 for a loss.\nStrategies stand on a value, like 17 or s17, while h17 also hits a soft 17. Both default to s17.\nUsage: blackjack((hand/result), (player strategy), (dealer strategy), (decks=1))";
 
     fn generate(input: &str) -> Result<Expression, DiceError> {
-        let (rounds_txt, initial) = match input.split_once(',') {
-            Some((rounds, initial)) => (rounds, initial.trim().parse().or(Err("Invalid initial value."))?),
-            None => (input.trim(), 0),
+        let args = split_parenth(input, ',');
+        if args.len() > 4 {
+            return Err("blackjack: too many arguments".into());
+        }
+        let arg = |index: usize| {
+            args.get(index)
+                .map(|arg| arg.trim())
+                .filter(|arg| !arg.is_empty())
+        };
+
+        let mode = match arg(0) {
+            None | Some("hand") => Mode::Hand,
+            Some("result") => Mode::Result,
+            Some(_) => return Err("blackjack: expected hand or result".into()),
+        };
+        let player = arg(1).map_or(Ok(Strategy::default()), str::parse)?;
+        let dealer = arg(2).map_or(Ok(Strategy::default()), str::parse)?;
+        let decks = match arg(3) {
+            Some(decks) => decks
+                .parse()
+                .map_err(|_| "blackjack: invalid number of decks")?,
+            None => 1,
         };
+        if !(1..=MAX_DECKS).contains(&decks) {
+            return Err(format!("blackjack: the number of decks must be 1 to {MAX_DECKS}").into());
+        }
 
         Ok(Blackjack {
-            rounds: rounds_txt.parse::<usize>().or(Err("Invalid number of rounds."))?,
-            initial,
-            outcomes: Outcomes::generate("2,3,4,5,6,7,8,9,10,10,10,10,11")?,
-        }.into())
+            mode,
+            player,
+            dealer,
+            decks,
+            shoe: Deck::standard().repeat(decks),
+        }
+        .into())
     }
 }
 
 impl Rollable for Blackjack {
     fn roll(&self) -> RollOut {
-        let mut rolls = (0..self.rounds).map(|_| self.outcomes.roll_quiet()).collect::<Vec<_>>();
-        while rolls.iter().sum::<isize>() + self.initial > 21isize && rolls.contains(&11) {
-            *rolls.iter_mut().find(|&&mut x| x == 11).unwrap() = 1;
-        }
+        let mut shoe = self.shoe.shuffled().into_iter();
+        let (player, player_cards) = Self::play(self.player, &mut shoe);
+        let mut txt = Layouter::new();
+        txt.append("[");
+        append_hand(&mut txt, "Player", player, &player_cards);
 
-        let total = self.sum_total(&rolls);
+        // The dealer does not need to play against a busted player
+        let dealer = if player.final_value() == BUST {
+            BUST
+        } else {
+            let (dealer, dealer_cards) = Self::play(self.dealer, &mut shoe);
+            txt.append("; ");
+            append_hand(&mut txt, "Dealer", dealer, &dealer_cards);
+            dealer.final_value()
+        };
+
+        let value = self.outcome(player.final_value(), dealer);
+        if self.mode == Mode::Result {
+            txt.append("; ");
+            match value {
+                1 => txt.append_colored("win", Color32::GREEN),
+                0 => txt.append("push"),
+                _ => txt.append_colored("loss", Color32::RED),
+            }
+        }
+        txt.append("]");
 
         RollOut {
-            value: total,
-            txt: format!("Blackjack: {:?}", rolls).into(),
+            value,
+            txt,
+            ..Default::default()
         }
     }
 
+    /// Plays out every possible order of the cards, remembering the dealer's outcomes for every composition of the shoe
     fn dist(&self) -> ProbDist {
-        self.bruteforce_probdist()
+        let decks = self.decks as u8;
+        let mut shoe = [4 * decks; 10];
+        shoe[9] = 16 * decks;
+
+        let mut out = BTreeMap::new();
+        self.player_dist(shoe, Hand::default(), 1.0, &mut HashMap::new(), &mut out);
+        ProbDist::try_from(out).unwrap_or_default()
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use crate::{Roll, Rollable};
 
+    #[test]
+    fn single_card_deck_test() {
+        // Standing on anything, the player's hand is the first two cards of a single deck
+        let dist = "blackjack(hand, 2)".parse::<Roll>().unwrap().dist();
+        // Only two twos make 4, an ace and a ten is a blackjack
+        assert!((dist[&4] - 4.0 / 52.0 * 3.0 / 51.0).abs() < 1e-12);
+        assert!((dist[&21] - 2.0 * 4.0 / 52.0 * 16.0 / 51.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn house_edge_test() {
+        // Mimicking the dealer loses, as the player busts first
+        let dist = "blackjack(result, 17, h17)".parse::<Roll>().unwrap().dist();
+        assert!((dist.values().sum::<f64>() - 1.0).abs() < 1e-9);
+        assert!(dist.expectation() < -0.03 && dist.expectation() > -0.1);
+        assert!("blackjack(result, x17)".parse::<Roll>().is_err());
+        assert!("blackjack(hand, 17, 17, 9)".parse::<Roll>().is_err());
+    }
+}
@@ -1,6 +1,4 @@
-use crate::{
-    BruteForceProbDist, DiceError, DiceRoller, Expression, Layouter, ProbDist, RollOut, Rollable,
-};
+use crate::{eval, DiceError, DiceRoller, Expression, Layouter, ProbDist, RollOut, Rollable};
 
 use super::FunctionInit;
 
@@ -33,23 +31,32 @@ impl Rollable for Crit {
         let init_roll = self.roller.roll();
         txt_out += init_roll.txt;
         let val_out = if init_roll.value > self.avg_roll as isize {
-            txt_out.append("*2]");
-            init_roll.value * 2
+            txt_out.append("*2");
+            eval::mul(init_roll.value, 2)
         } else {
             let second_roll = self.roller.roll();
             txt_out += second_roll.txt;
-            init_roll.value + second_roll.value
+            eval::add(init_roll.value, second_roll.value)
         };
         txt_out.append("]");
 
         RollOut {
             value: val_out,
             txt: txt_out,
+            ..Default::default()
         }
     }
 
     /// Calculates the probability distribution of the `Crit` struct.
+    /// An initial roll above average is doubled, any other initial roll gets a second roll added to it.
     fn dist(&self) -> ProbDist {
-        self.bruteforce_probdist()
+        let single = self.roller.dist();
+        ProbDist::from_parameter_distribution(&single, |init| {
+            if init > self.avg_roll as isize {
+                ProbDist::constant(eval::mul(init, 2))
+            } else {
+                single.clone() + init
+            }
+        })
     }
 }
@@ -0,0 +1,367 @@
+use std::{collections::BTreeMap, str::FromStr};
+
+use instant::Instant;
+use itertools::Itertools;
+use rand::seq::SliceRandom;
+
+use crate::{
+    eval,
+    rng::with_rng,
+    utils::{ln_binomial, split_once_parenth, split_parenth},
+    BruteForceProbDist, DiceError, Expression, ProbDist, RollOut, Rollable, Value,
+};
+
+use super::FunctionInit;
+
+/// Decks with more cards than this are refused, as the number of possible hands would no longer fit in a float
+const MAX_DECK: usize = 1000;
+
+const SUITS: [&str; 4] = ["♠", "♥", "♦", "♣"];
+const RANKS: [&str; 13] = [
+    "A", "2", "3", "4", "5", "6", "7", "8", "9", "10", "J", "Q", "K",
+];
+
+const MAJOR_ARCANA: [&str; 22] = [
+    "The Fool",
+    "The Magician",
+    "The High Priestess",
+    "The Empress",
+    "The Emperor",
+    "The Hierophant",
+    "The Lovers",
+    "The Chariot",
+    "Strength",
+    "The Hermit",
+    "Wheel of Fortune",
+    "Justice",
+    "The Hanged Man",
+    "Death",
+    "Temperance",
+    "The Devil",
+    "The Tower",
+    "The Star",
+    "The Moon",
+    "The Sun",
+    "Judgement",
+    "The World",
+];
+
+/// The cards of the 5e Deck of Many Things, in the order of the table in the DMG
+const MANY_THINGS: [&str; 22] = [
+    "Vizier",
+    "Sun",
+    "Moon",
+    "Star",
+    "Comet",
+    "The Fates",
+    "Throne",
+    "Key",
+    "Knight",
+    "Gem",
+    "Talons",
+    "The Void",
+    "Flames",
+    "Skull",
+    "Idiot",
+    "Donjon",
+    "Ruin",
+    "Euryale",
+    "Rogue",
+    "Balance",
+    "Fool",
+    "Jester",
+];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Card {
+    pub name: String,
+    pub value: Value,
+}
+
+impl Card {
+    fn new(name: impl Into<String>, value: Value) -> Self {
+        Card {
+            name: name.into(),
+            value,
+        }
+    }
+}
+
+/// A collection of cards that are drawn without replacement
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Deck {
+    cards: Vec<Card>,
+}
+
+impl Deck {
+    /// Standard 52 card deck, with aces worth 1 and jacks, queens and kings worth 11, 12 and 13
+    #[must_use]
+    pub fn standard() -> Self {
+        Deck {
+            cards: SUITS
+                .iter()
+                .flat_map(|suit| {
+                    RANKS
+                        .iter()
+                        .zip(1..)
+                        .map(move |(rank, value)| Card::new(format!("{rank}{suit}"), value))
+                })
+                .collect(),
+        }
+    }
+
+    /// The major arcana of a tarot deck, worth 0 (The Fool) through 21 (The World)
+    #[must_use]
+    pub fn tarot() -> Self {
+        Deck {
+            cards: MAJOR_ARCANA
+                .iter()
+                .zip(0..)
+                .map(|(name, value)| Card::new(*name, value))
+                .collect(),
+        }
+    }
+
+    /// The 22 card Deck of Many Things, worth 1 through 22 in the order of the DMG
+    #[must_use]
+    pub fn many_things() -> Self {
+        Deck {
+            cards: MANY_THINGS
+                .iter()
+                .zip(1..)
+                .map(|(name, value)| Card::new(*name, value))
+                .collect(),
+        }
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.cards.len()
+    }
+
+    /// The deck repeated the given number of times, like a shoe of multiple decks
+    #[must_use]
+    pub fn repeat(&self, times: usize) -> Self {
+        Deck {
+            cards: (0..times).flat_map(|_| self.cards.clone()).collect(),
+        }
+    }
+
+    /// All cards of the deck in random order
+    #[must_use]
+    pub fn shuffled(&self) -> Vec<&Card> {
+        let mut cards = self.cards.iter().collect_vec();
+        with_rng(|rng| cards.shuffle(rng));
+        cards
+    }
+
+    /// Draws n distinct cards at random, n must not exceed the size of the deck
+    #[must_use]
+    pub fn draw(&self, n: usize) -> Vec<&Card> {
+        with_rng(|rng| self.cards.choose_multiple(rng, n).collect())
+    }
+
+    /// Distribution of the total value of n cards drawn without replacement.
+    /// For every value, counts the ways to pick some of the cards with that value, like a multivariate hypergeometric distribution.
+    /// Gives up when there are more partial hands than the support limit, or when it takes longer than the time budget
+    #[must_use]
+    pub fn draw_dist(&self, n: usize) -> Option<ProbDist> {
+        let counts = self.cards.iter().map(|card| card.value).counts();
+        let limits = eval::limits();
+        let timestamp = Instant::now();
+
+        // (cards drawn, total value) -> number of ways
+        let mut ways = BTreeMap::from([((0, 0), 1.0)]);
+        for (&value, &count) in &counts {
+            if ways.len() > limits.max_support || timestamp.elapsed() > limits.time_budget {
+                return None;
+            }
+            let mut next = BTreeMap::new();
+            for (&(drawn, total), &prev_ways) in &ways {
+                for k in 0..=count.min(n - drawn) {
+                    let key = (drawn + k, eval::add(total, eval::mul(k as Value, value)));
+                    *next.entry(key).or_insert(0.0) +=
+                        prev_ways * ln_binomial(count, k).exp().round();
+                }
+            }
+            ways = next;
+        }
+
+        let hands = ln_binomial(self.len(), n).exp();
+        let dist = ways
+            .into_iter()
+            .filter(|((drawn, _), _)| *drawn == n)
+            .map(|((_, total), ways)| (total, ways / hands))
+            .collect::<BTreeMap<_, _>>();
+        let mut dist = ProbDist::try_from(dist).unwrap_or_default();
+        dist.proper_scale();
+        Some(dist)
+    }
+}
+
+impl FromStr for Deck {
+    type Err = DiceError;
+
+    /// Parses either the name of a predefined deck (standard, tarot or many), or a list of cards.
+    /// Cards are given as name=value, as a plain value, or as a plain name, which is then worth its position in the deck
+    fn from_str(src: &str) -> Result<Self, Self::Err> {
+        let deck = match src.trim() {
+            "" | "standard" => Deck::standard(),
+            "tarot" => Deck::tarot(),
+            "many" => Deck::many_things(),
+            src => Deck {
+                cards: split_parenth(src, ',')
+                    .into_iter()
+                    .zip(1..)
+                    .map(|(card, position)| {
+                        let card = card.trim();
+                        match card.split_once('=') {
+                            Some((name, value)) => Ok(Card::new(
+                                name.trim(),
+                                value
+                                    .trim()
+                                    .parse()
+                                    .map_err(|_| format!("deck: invalid value for card {name}"))?,
+                            )),
+                            None if card.is_empty() => Err("deck: empty card name".into()),
+                            None => Ok(Card::new(card, card.parse().unwrap_or(position))),
+                        }
+                    })
+                    .collect::<Result<_, DiceError>>()?,
+            },
+        };
+
+        if deck.len() > MAX_DECK {
+            return Err(format!("deck: decks may hold at most {MAX_DECK} cards").into());
+        }
+        Ok(deck)
+    }
+}
+
+/// Draws cards from a deck without replacement, resulting in their total value
+#[derive(Clone, Debug)]
+pub struct Draw {
+    deck: Deck,
+    count: usize,
+}
+
+impl Draw {
+    fn new(deck: Deck, count: usize) -> Result<Self, DiceError> {
+        if count > deck.len() {
+            return Err(format!(
+                "draw: cannot draw {count} cards from a deck of {}",
+                deck.len()
+            )
+            .into());
+        }
+        Ok(Draw { deck, count })
+    }
+
+    pub const SINGLE_DOC: &'static str = "Draws a single card, resulting in its value. Without cards, a standard 52 card deck is used.\nUsage: deck(standard/tarot/many) or deck(name1=value1, name2=value2, ...)";
+
+    /// Generates a draw of a single card, for deck(...)
+    pub fn generate_single(input: &str) -> Result<Expression, DiceError> {
+        Ok(Draw::new(input.parse()?, 1)?.into())
+    }
+}
+
+impl FunctionInit for Draw {
+    const DOC: &'static str = "Draws n cards without replacement, resulting in their total value.\nThe deck is either standard (52 cards, ace 1 to king 13), tarot (major arcana, 0 to 21), many (Deck of Many Things, 1 to 22),\nor deck(name=value, ...) with custom cards.\nUsage: draw(deck, n)";
+
+    fn generate(input: &str) -> Result<Expression, DiceError> {
+        let (deck, count) =
+            split_once_parenth(input, ',').ok_or("draw: expected a deck and a number of cards")?;
+        let deck = deck.trim();
+        let deck = match deck.strip_prefix("deck(") {
+            Some(cards) => cards.strip_suffix(')').ok_or("draw: missing ')' in deck")?,
+            None => deck,
+        };
+        let count = count
+            .trim()
+            .parse()
+            .map_err(|_| "draw: invalid number of cards")?;
+
+        Ok(Draw::new(deck.parse()?, count)?.into())
+    }
+}
+
+impl Rollable for Draw {
+    fn roll(&self) -> RollOut {
+        let cards = self.deck.draw(self.count);
+        let mut out = RollOut {
+            value: eval::sum(cards.iter().map(|card| card.value)),
+            ..Default::default()
+        };
+        out.txt.append(&format!(
+            "[{}]",
+            cards.iter().map(|card| &card.name).join(", ")
+        ));
+        out
+    }
+
+    /// With too many different hands to go through, the distribution is sampled instead
+    fn dist(&self) -> ProbDist {
+        self.deck
+            .draw_dist(self.count)
+            .unwrap_or_else(|| self.bruteforce_probdist())
+    }
+
+    fn roll_quiet(&self) -> Value {
+        eval::sum(self.deck.draw(self.count).iter().map(|card| card.value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use instant::Instant;
+    use itertools::Itertools;
+
+    use super::Deck;
+    use crate::{seed, EvalLimits, Roll, Rollable};
+
+    #[test]
+    fn draw_dist_test() {
+        let dist = "draw(deck(1, 2, 3), 2)".parse::<Roll>().unwrap().dist();
+        for total in 3..=5 {
+            assert!((dist[&total] - 1.0 / 3.0).abs() < 1e-12);
+        }
+
+        // Drawing two aces: 4/52 * 3/51
+        let dist = Deck::standard().draw_dist(2).unwrap();
+        assert!((dist[&2] - 4.0 / 52.0 * 3.0 / 51.0).abs() < 1e-12);
+        assert!((dist.expectation() - 14.0).abs() < 1e-9);
+
+        // Too many partial hands, so it is sampled instead, though the 121 totals are within the limit
+        let limits = EvalLimits {
+            max_support: 500,
+            ..Default::default()
+        };
+        let roll = Roll::parse_with_limits("draw(tarot, 10)", limits).unwrap();
+        assert!(roll.dist().approximation().is_some());
+        assert!(Deck::tarot().draw_dist(10).is_some());
+
+        // The distribution is only computed when asked for, so parsing stays quick
+        let cards = (1..=300).map(|value| value.to_string()).join(", ");
+        let start = Instant::now();
+        assert!(format!("draw(deck({cards}), 150)").parse::<Roll>().is_ok());
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn without_replacement_test() {
+        seed(31);
+        let roll = "draw(deck(Sun=1, Moon=10, Star=100), 3)"
+            .parse::<Roll>()
+            .unwrap();
+        for _ in 0..100 {
+            let out = roll.roll();
+            assert_eq!(out.value, 111);
+            let txt: String = out.txt.sections.into_iter().map(|(txt, _)| txt).collect();
+            assert!(txt.contains("Sun") && txt.contains("Moon") && txt.contains("Star"));
+        }
+        assert!("draw(tarot, 23)".parse::<Roll>().is_err());
+        assert_eq!("deck(many)".parse::<Roll>().unwrap().dist().len(), 22);
+    }
+}
@@ -0,0 +1,389 @@
+use std::{collections::BTreeMap, fmt::Debug, marker::PhantomData};
+
+use itertools::Itertools;
+
+use crate::{
+    eval,
+    structure::num_expresson::NumericExpression,
+    utils::{ln_binomial, split_parenth, PROB_CUTOFF},
+    DiceError, Expression, ProbDist, RollOut, Rollable, Value,
+};
+
+use super::FunctionInit;
+
+/// A family of probability distributions, such as the binomial distributions, determined by a fixed set of parameters
+pub trait Family: Clone + Debug + Send + Sync + 'static {
+    /// Name of the function, used in error messages
+    const NAME: &'static str;
+    const DOC: &'static str;
+    const PARAMS: usize;
+
+    /// Checks whether the parameters describe a valid distribution, describing the problem if they do not
+    fn check(params: &[f64]) -> Result<(), &'static str>;
+
+    /// The distribution for a set of parameters that passed `Family::check`
+    fn dist(params: &[f64]) -> ProbDist;
+}
+
+/// A distribution from a family, whose parameters may themselves be random
+#[derive(Clone, Debug)]
+pub struct Parametrized<F: Family> {
+    params: Vec<NumericExpression>,
+    /// The distribution for constant parameters, so it is not recomputed on every roll
+    const_dist: Option<ProbDist>,
+    family: PhantomData<F>,
+}
+
+impl<F: Family> Parametrized<F> {
+    /// Parameters that are invalid, which can only happen when they are random, always result in 0
+    fn dist_for(params: &[f64]) -> ProbDist {
+        match F::check(params) {
+            Ok(()) => F::dist(params),
+            Err(_) => ProbDist::constant(0),
+        }
+    }
+}
+
+impl<F: Family> FunctionInit for Parametrized<F> {
+    const DOC: &'static str = F::DOC;
+
+    fn generate(input: &str) -> Result<Expression, DiceError> {
+        let args = split_parenth(input, ',');
+        if args.len() != F::PARAMS {
+            return Err(format!("{}: expected {} arguments", F::NAME, F::PARAMS).into());
+        }
+        let params: Vec<NumericExpression> =
+            args.into_iter().map(str::parse).collect::<Result<_, _>>()?;
+
+        let const_params: Option<Vec<f64>> = params
+            .iter()
+            .map(|param| match param {
+                NumericExpression::Constant(val) => Some(*val),
+                NumericExpression::Stochastic(_) => None,
+            })
+            .collect();
+        let const_dist = match const_params {
+            Some(const_params) => {
+                F::check(&const_params).map_err(|err| format!("{}: {err}", F::NAME))?;
+                Some(F::dist(&const_params))
+            }
+            None => None,
+        };
+
+        Ok(Parametrized::<F> {
+            params,
+            const_dist,
+            family: PhantomData,
+        }
+        .into())
+    }
+}
+
+impl<F: Family> Rollable for Parametrized<F> {
+    fn roll(&self) -> RollOut {
+        let value = match &self.const_dist {
+            Some(dist) => dist.roll_quiet(),
+            None => {
+                let params = self
+                    .params
+                    .iter()
+                    .map(NumericExpression::evaluate)
+                    .collect_vec();
+                Self::dist_for(&params).roll_quiet()
+            }
+        };
+
+        RollOut {
+            value,
+            txt: format!("[{value}]").into(),
+            ..Default::default()
+        }
+    }
+
+    /// Mixes the distributions for all combinations of parameters
+    fn dist(&self) -> ProbDist {
+        if let Some(dist) = &self.const_dist {
+            return dist.clone();
+        }
+
+        ProbDist::mixture(
+            self.params
+                .iter()
+                .map(NumericExpression::values)
+                .multi_cartesian_product()
+                .map(|combination| {
+                    let (params, probs): (Vec<f64>, Vec<f64>) = combination.into_iter().unzip();
+                    (probs.iter().product(), Self::dist_for(&params))
+                }),
+        )
+    }
+}
+
+/// Interprets a parameter as a number of things
+fn as_count(param: f64) -> Option<usize> {
+    (param >= 0.0 && param.fract() == 0.0 && param <= eval::limits().max_support as f64)
+        .then_some(param as usize)
+}
+
+fn is_probability(param: f64) -> bool {
+    (0.0..=1.0).contains(&param)
+}
+
+/// Turns computed probabilities into a distribution, rescaling it to make up for cut off tails
+fn from_probs(probs: BTreeMap<Value, f64>) -> ProbDist {
+    let mut dist = ProbDist::try_from(probs).unwrap_or_default();
+    dist.proper_scale();
+    dist
+}
+
+/// Number of trials needed to get r successes, with every trial succeeding with probability p
+fn trials_until(r: usize, p: f64) -> ProbDist {
+    if p == 1.0 {
+        return ProbDist::constant(r as Value);
+    }
+
+    let mut probs = BTreeMap::new();
+    let mut total = 0.0;
+    for k in r..(r + eval::limits().max_support) {
+        // P(X = k) = (k - 1 choose r - 1) * p^r * (1 - p)^(k - r)
+        let prob =
+            (ln_binomial(k - 1, r - 1) + r as f64 * p.ln() + (k - r) as f64 * (1.0 - p).ln()).exp();
+        probs.insert(k as Value, prob);
+        total += prob;
+        if 1.0 - total < PROB_CUTOFF {
+            break;
+        }
+    }
+    from_probs(probs)
+}
+
+#[derive(Clone, Debug)]
+pub struct Binomial;
+
+impl Family for Binomial {
+    const NAME: &'static str = "binom";
+    const DOC: &'static str = "Number of successes out of n trials that each succeed with probability p.\nUsage: binom(n, p)";
+    const PARAMS: usize = 2;
+
+    fn check(params: &[f64]) -> Result<(), &'static str> {
+        as_count(params[0]).ok_or("n must be a whole number of trials")?;
+        if !is_probability(params[1]) {
+            return Err("p must be a probability between 0 and 1");
+        }
+        Ok(())
+    }
+
+    fn dist(params: &[f64]) -> ProbDist {
+        let (n, p) = (params[0] as usize, params[1]);
+        match p {
+            _ if p == 0.0 => ProbDist::constant(0),
+            _ if p == 1.0 => ProbDist::constant(n as Value),
+            _ => from_probs(
+                (0..=n)
+                    .map(|k| {
+                        let ln_prob =
+                            ln_binomial(n, k) + k as f64 * p.ln() + (n - k) as f64 * (1.0 - p).ln();
+                        (k as Value, ln_prob.exp())
+                    })
+                    .collect(),
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Geometric;
+
+impl Family for Geometric {
+    const NAME: &'static str = "geom";
+    const DOC: &'static str = "Number of trials needed for the first success, when each trial succeeds with probability p.\nUsage: geom(p)";
+    const PARAMS: usize = 1;
+
+    fn check(params: &[f64]) -> Result<(), &'static str> {
+        if params[0] > 0.0 && params[0] <= 1.0 {
+            Ok(())
+        } else {
+            Err("p must be a probability above 0 and at most 1")
+        }
+    }
+
+    fn dist(params: &[f64]) -> ProbDist {
+        trials_until(1, params[0])
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct NegBinomial;
+
+impl Family for NegBinomial {
+    const NAME: &'static str = "negbin";
+    const DOC: &'static str = "Number of trials needed for r successes, when each trial succeeds with probability p.\nUsage: negbin(r, p)";
+    const PARAMS: usize = 2;
+
+    fn check(params: &[f64]) -> Result<(), &'static str> {
+        match as_count(params[0]) {
+            Some(r) if r > 0 => {}
+            _ => return Err("r must be a whole number of successes above 0"),
+        }
+        Geometric::check(&params[1..])
+    }
+
+    fn dist(params: &[f64]) -> ProbDist {
+        trials_until(params[0] as usize, params[1])
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Hypergeometric;
+
+impl Family for Hypergeometric {
+    const NAME: &'static str = "hypergeom";
+    const DOC: &'static str = "Number of successes when drawing n items without replacement from a population of size N containing K successes.\nUsage: hypergeom(N, K, n)";
+    const PARAMS: usize = 3;
+
+    fn check(params: &[f64]) -> Result<(), &'static str> {
+        let counts = params.iter().map(|param| as_count(*param)).collect_vec();
+        match counts[..] {
+            [Some(total), Some(successes), Some(draws)] if successes <= total && draws <= total => {
+                Ok(())
+            }
+            _ => Err("N, K and n must be whole numbers, with K and n at most N"),
+        }
+    }
+
+    fn dist(params: &[f64]) -> ProbDist {
+        let (total, successes, draws) =
+            (params[0] as usize, params[1] as usize, params[2] as usize);
+        let ln_ways = ln_binomial(total, draws);
+        let min = (draws + successes).saturating_sub(total);
+        let max = successes.min(draws);
+        from_probs(
+            (min..=max)
+                .map(|k| {
+                    let ln_prob = ln_binomial(successes, k)
+                        + ln_binomial(total - successes, draws - k)
+                        - ln_ways;
+                    (k as Value, ln_prob.exp())
+                })
+                .collect(),
+        )
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Uniform;
+
+impl Family for Uniform {
+    const NAME: &'static str = "uniform";
+    const DOC: &'static str =
+        "Every whole number from a up to and including b is equally likely.\nUsage: uniform(a, b)";
+    const PARAMS: usize = 2;
+
+    fn check(params: &[f64]) -> Result<(), &'static str> {
+        let (a, b) = (params[0], params[1]);
+        if a.fract() != 0.0 || b.fract() != 0.0 || a > b {
+            Err("a and b must be whole numbers, with a at most b")
+        } else if b - a >= eval::limits().max_support as f64 {
+            Err("the range is too large")
+        } else {
+            Ok(())
+        }
+    }
+
+    fn dist(params: &[f64]) -> ProbDist {
+        let (a, b) = (params[0] as Value, params[1] as Value);
+        let prob = 1.0 / (b - a + 1) as f64;
+        from_probs((a..=b).map(|outcome| (outcome, prob)).collect())
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Normal;
+
+impl Family for Normal {
+    const NAME: &'static str = "normal";
+    const DOC: &'static str = "Normal distribution with mean mu and deviation sigma, discretized to whole numbers.\nUsage: normal(mu, sigma)";
+    const PARAMS: usize = 2;
+
+    fn check(params: &[f64]) -> Result<(), &'static str> {
+        let (mu, sigma) = (params[0], params[1]);
+        if !mu.is_finite() || !sigma.is_finite() || sigma < 0.0 {
+            Err("mu must be a number and sigma must not be negative")
+        } else if 16.0 * sigma >= eval::limits().max_support as f64 {
+            Err("sigma is too large")
+        } else {
+            Ok(())
+        }
+    }
+
+    fn dist(params: &[f64]) -> ProbDist {
+        let (mu, sigma) = (params[0], params[1]);
+        if sigma == 0.0 {
+            return ProbDist::constant(mu.round() as Value);
+        }
+        let range = ((mu - 8.0 * sigma).floor() as Value)
+            ..((mu + 8.0 * sigma).ceil() as Value).saturating_add(1);
+        ProbDist::normal(mu, sigma.powi(2), range)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Roll, Rollable};
+
+    fn dist(src: &str) -> crate::ProbDist {
+        src.parse::<Roll>().unwrap().dist()
+    }
+
+    #[test]
+    fn moments_test() {
+        let binom = dist("binom(10, 0.3)");
+        assert!((binom.expectation() - 3.0).abs() < 1e-9);
+        assert!((binom.var() - 2.1).abs() < 1e-9);
+        assert!((binom[&0] - 0.7f64.powi(10)).abs() < 1e-12);
+
+        let geom = dist("geom(0.25)");
+        assert!((geom.expectation() - 4.0).abs() < 1e-6);
+        assert!((geom[&1] - 0.25).abs() < 1e-12);
+
+        let negbin = dist("negbin(3, 0.5)");
+        assert!((negbin.expectation() - 6.0).abs() < 1e-6);
+
+        // Drawing 5 cards, counting hearts
+        let hypergeom = dist("hypergeom(52, 13, 5)");
+        assert!((hypergeom.expectation() - 1.25).abs() < 1e-9);
+        assert!(hypergeom.keys().copied().eq(0..=5));
+
+        let uniform = dist("uniform(-2, 2)");
+        assert_eq!(uniform.len(), 5);
+        assert!((uniform.expectation()).abs() < 1e-12);
+
+        let normal = dist("normal(10, 3)");
+        assert!((normal.expectation() - 10.0).abs() < 1e-6);
+        assert!((normal.sigma() - 3.0).abs() < 0.05);
+    }
+
+    #[test]
+    fn stochastic_params_test() {
+        // n is 1 or 2, so P(0) = 0.5 * 0.5 + 0.5 * 0.25
+        let binom = dist("binom(d2, 0.5)");
+        assert!((binom[&0] - 0.375).abs() < 1e-12);
+        assert!((binom.values().sum::<f64>() - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn invalid_params_test() {
+        for src in [
+            "binom(3, 1.5)",
+            "binom(-1, 0.5)",
+            "geom(0)",
+            "negbin(0, 0.5)",
+            "hypergeom(10, 11, 2)",
+            "uniform(3, 1)",
+            "normal(0, -1)",
+            "uniform(1)",
+        ] {
+            assert!(src.parse::<Roll>().is_err(), "{src} should not parse");
+        }
+    }
+}
@@ -1,12 +1,17 @@
+use std::collections::{BTreeMap, HashMap};
+
 use crate::{
-    dice_roller::DiceRoller, layouter::LINE_ORANGE, BruteForceProbDist, DiceError, Expression,
-    ProbDist, RollOut, Rollable,
+    dice_roller::DiceRoller, layouter::reroll_line, BruteForceProbDist, DiceError, Expression,
+    ProbDist, RollOut, Rollable, Value,
 };
 use egui::TextFormat;
 use itertools::Itertools;
 
 use super::FunctionInit;
 
+/// Above this (rough) number of operations, the exact distribution is deemed too expensive and is bruteforced instead
+const MAX_EXACT_WORK: usize = 50_000_000;
+
 #[derive(Clone, Debug)]
 pub struct Empower {
     roll: DiceRoller,
@@ -75,15 +80,85 @@ impl Rollable for Empower {
         out
     }
 
+    /// The lowest below-average dice are the ones rerolled, so the dice are distributed over their faces from low to high,
+    /// keeping track of how many dice have been placed, how many rerolls have been used, and the sum of the kept dice
     fn dist(&self) -> ProbDist {
-        self.bruteforce_probdist()
+        let n = self.roll.dice_count();
+        let faces = self.single_roll.dist();
+        let work = n
+            .saturating_mul(n)
+            .saturating_mul(faces.len().pow(2))
+            .saturating_mul(self.prof + 1);
+        if work > MAX_EXACT_WORK {
+            return self.bruteforce_probdist();
+        }
+
+        let avg = self.single_roll.unmod_avg() as Value;
+        let binom = binomial_table(n);
+
+        // (dice placed, rerolls used, sum of kept dice) -> probability
+        let mut states = HashMap::from([((0, 0, 0), 1.0)]);
+        for (&face, &face_prob) in faces.iter() {
+            let mut next = HashMap::new();
+            for (&(placed, rerolled, kept), &prob) in &states {
+                let remaining = n - placed;
+                for (count, ways) in binom[remaining].iter().enumerate() {
+                    let new_rerolls = if face < avg {
+                        count.min(self.prof - rerolled)
+                    } else {
+                        0
+                    };
+                    let key = (
+                        placed + count,
+                        rerolled + new_rerolls,
+                        kept + (count - new_rerolls) as Value * face,
+                    );
+                    *next.entry(key).or_insert(0.0) += prob * ways * face_prob.powi(count as i32);
+                }
+            }
+            states = next;
+        }
+
+        // Every state with all dice placed gets its rerolls added to the kept sum
+        let mut rerolled = vec![ProbDist::default()];
+        for _ in 0..self.prof.min(n) {
+            let next = rerolled.last().unwrap().clone() + &faces;
+            rerolled.push(next);
+        }
+        let mut out = BTreeMap::new();
+        for ((placed, rerolls, kept), prob) in states {
+            if placed != n {
+                continue;
+            }
+            for (outcome, reroll_prob) in rerolled[rerolls].iter() {
+                *out.entry(outcome + kept).or_insert(0.0) += prob * reroll_prob;
+            }
+        }
+        ProbDist::try_from(out).unwrap_or_default()
+    }
+}
+
+/// Pascal's triangle up to n, table[a][b] = a choose b
+fn binomial_table(n: usize) -> Vec<Vec<f64>> {
+    let mut table = vec![vec![1.0]];
+    for a in 1..=n {
+        let prev = &table[a - 1];
+        let row = (0..=a)
+            .map(|b| {
+                let left = if b > 0 { prev[b - 1] } else { 0.0 };
+                let right = prev.get(b).copied().unwrap_or(0.0);
+                left + right
+            })
+            .collect();
+        table.push(row);
     }
+    table
 }
 
 fn reroll(single_roll: &DiceRoller, roll: &mut RollOut) {
     // Strikethrough the old
     roll.txt.sections[1].1 = TextFormat {
-        strikethrough: LINE_ORANGE,
+        strikethrough: reroll_line(),
         ..Default::default()
     };
 
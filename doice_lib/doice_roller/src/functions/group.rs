@@ -0,0 +1,405 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+    str::FromStr,
+};
+
+use itertools::Itertools;
+
+use crate::{
+    dice_roller::DiceRoller, eval, Approximation, BruteForceProbDist, DiceError, Expression,
+    Layouter, ProbDist, RollOut, Rollable, Value,
+};
+
+use super::List;
+
+/// The exact distribution of a group that keeps results is given up on beyond this many combinations of kept results,
+/// in favour of bruteforcing it
+const MAX_KEPT_STATES: usize = 100_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Comparison {
+    Less,
+    LessEq,
+    Eq,
+    GreaterEq,
+    Greater,
+}
+
+impl Comparison {
+    fn symbol(self) -> &'static str {
+        match self {
+            Comparison::Less => "<",
+            Comparison::LessEq => "<=",
+            Comparison::Eq => "=",
+            Comparison::GreaterEq => ">=",
+            Comparison::Greater => ">",
+        }
+    }
+
+    fn holds(self, lhs: Value, rhs: Value) -> bool {
+        match self {
+            Comparison::Less => lhs < rhs,
+            Comparison::LessEq => lhs <= rhs,
+            Comparison::Eq => lhs == rhs,
+            Comparison::GreaterEq => lhs >= rhs,
+            Comparison::Greater => lhs > rhs,
+        }
+    }
+}
+
+/// What a group does with the results of its members
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Modifier {
+    /// Keeps the highest or lowest results, like kh2 or kl1
+    Keep { highest: bool, count: usize },
+    /// Drops the highest or lowest results, like dh1 or dl2
+    Drop { highest: bool, count: usize },
+    /// Counts the results that compare favourably to a target, like >=10
+    Count(Comparison, Value),
+}
+
+impl FromStr for Modifier {
+    type Err = DiceError;
+
+    /// Parses k/kh, kl, d/dl and dh followed by an optional count, or a comparison followed by a target
+    fn from_str(src: &str) -> Result<Self, Self::Err> {
+        let src = src.trim();
+        let err = || format!("group: invalid modifier {src}");
+
+        let comparisons = [
+            ("<=", Comparison::LessEq),
+            (">=", Comparison::GreaterEq),
+            ("<", Comparison::Less),
+            (">", Comparison::Greater),
+            ("=", Comparison::Eq),
+        ];
+        if let Some((target, comparison)) = comparisons
+            .iter()
+            .find_map(|(symbol, comparison)| Some((src.strip_prefix(symbol)?, *comparison)))
+        {
+            let target = target.trim().parse().map_err(|_| err())?;
+            return Ok(Modifier::Count(comparison, target));
+        }
+
+        let kinds = [
+            ("kh", true, true),
+            ("kl", true, false),
+            ("k", true, true),
+            ("dh", false, true),
+            ("dl", false, false),
+            ("d", false, false),
+        ];
+        let (count, keep, highest) = kinds
+            .iter()
+            .find_map(|(prefix, keep, highest)| Some((src.strip_prefix(prefix)?, *keep, *highest)))
+            .ok_or_else(err)?;
+        let count = match count.trim() {
+            "" => 1,
+            count => count.parse().map_err(|_| err())?,
+        };
+
+        Ok(if keep {
+            Modifier::Keep { highest, count }
+        } else {
+            Modifier::Drop { highest, count }
+        })
+    }
+}
+
+impl Display for Modifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let side = |highest: bool| if highest { 'h' } else { 'l' };
+        match self {
+            Modifier::Keep { highest, count } => write!(f, "k{}{count}", side(*highest)),
+            Modifier::Drop { highest, count } => write!(f, "d{}{count}", side(*highest)),
+            Modifier::Count(comparison, target) => write!(f, "{}{target}", comparison.symbol()),
+        }
+    }
+}
+
+/// A list of expressions whose results are kept, dropped or counted as a whole, like {2d6, 1d8, 1d4}kh2
+#[derive(Clone, Debug)]
+pub struct Group {
+    list: List,
+    modifier: Modifier,
+}
+
+impl Group {
+    /// Parses an expression starting with braces, which is a plain list unless the braces are followed by a modifier
+    pub(crate) fn from_braces(src: &str) -> Result<Expression, DiceError> {
+        let src = src.trim();
+        let mut depth = 0;
+        let end = src
+            .char_indices()
+            .find(|(_, c)| {
+                match c {
+                    '{' => depth += 1,
+                    '}' => depth -= 1,
+                    _ => {}
+                }
+                depth == 0
+            })
+            .map(|(i, _)| i)
+            .ok_or("list: missing '}'")?;
+
+        let (list, modifier) = src.split_at(end + 1);
+        let list = list.parse::<List>()?;
+        if modifier.trim().is_empty() {
+            return Ok(list.into());
+        }
+        Ok(Group {
+            list,
+            modifier: modifier.parse()?,
+        }
+        .into())
+    }
+
+    /// Parses dice followed by a modifier, like 4d6kh3 or 3d20>=15, as a group of single dice.
+    /// Gives None if the dice are not followed by a modifier
+    pub(crate) fn from_dice(src: &str) -> Option<Result<Expression, DiceError>> {
+        let src = src.trim();
+        let (count, dice) = src.split_once('d')?;
+        let faces = dice.trim_start_matches(['&', '|']);
+        let faces_len = match faces.chars().next() {
+            Some('{') => faces.find('}').map_or(faces.len(), |i| i + 1),
+            Some('%' | 'F' | 'f') => 1,
+            _ => faces
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(faces.len()),
+        };
+        let modifier = &faces[faces_len..];
+        if modifier.trim().is_empty() {
+            return None;
+        }
+        let dice = &src[..src.len() - modifier.len()];
+        Some(Self::from_dice_parts(dice, count.len(), modifier))
+    }
+
+    /// Repeats a single one of the dice for every die rolled, with the dice count ending at count_len
+    fn from_dice_parts(
+        dice: &str,
+        count_len: usize,
+        modifier: &str,
+    ) -> Result<Expression, DiceError> {
+        // Parsing the dice holds their count to the dice limit
+        let dice_count = dice.parse::<DiceRoller>()?.dice_count();
+        let die: Expression = dice[count_len..].parse::<DiceRoller>()?.into();
+        Ok(Group {
+            list: List::repeated(&die, dice_count),
+            modifier: modifier.parse()?,
+        }
+        .into())
+    }
+
+    /// Whether every result counts towards the value of the group, in the order of the results
+    fn active(&self, values: &[Value]) -> Vec<bool> {
+        let n = values.len();
+        let (highest, count) = match self.modifier {
+            Modifier::Count(comparison, target) => {
+                return values
+                    .iter()
+                    .map(|&value| comparison.holds(value, target))
+                    .collect();
+            }
+            Modifier::Keep { highest, count } => (highest, count.min(n)),
+            Modifier::Drop { highest, count } => (!highest, n - count.min(n)),
+        };
+
+        // Among equal results, the first ones are kept, as the sort is stable
+        let kept = if highest {
+            (0..n)
+                .sorted_by_key(|&i| Reverse(values[i]))
+                .take(count)
+                .collect_vec()
+        } else {
+            (0..n)
+                .sorted_by_key(|&i| values[i])
+                .take(count)
+                .collect_vec()
+        };
+        (0..n).map(|i| kept.contains(&i)).collect()
+    }
+
+    fn value(&self, values: &[Value]) -> Value {
+        let active = values.iter().zip(self.active(values));
+        match self.modifier {
+            Modifier::Count(..) => active.filter(|(_, active)| *active).count() as Value,
+            _ => eval::sum(
+                active
+                    .filter(|(_, active)| *active)
+                    .map(|(value, _)| *value),
+            ),
+        }
+    }
+
+    /// Number of successes, from the probabilities of the members to succeed
+    fn count_dist(&self, comparison: Comparison, target: Value) -> ProbDist {
+        let dists = self.list.dists();
+        let mut successes = vec![1.0];
+        for dist in &dists {
+            let p = dist
+                .iter()
+                .filter(|(&outcome, _)| comparison.holds(outcome, target))
+                .map(|(_, prob)| prob)
+                .sum::<f64>()
+                .min(1.0);
+            let mut next = vec![0.0; successes.len() + 1];
+            for (count, prob) in successes.iter().enumerate() {
+                next[count] += prob * (1.0 - p);
+                next[count + 1] += prob * p;
+            }
+            successes = next;
+        }
+
+        let dist: BTreeMap<_, _> = successes
+            .into_iter()
+            .enumerate()
+            .map(|(count, prob)| (count as Value, prob))
+            .filter(|(_, prob)| *prob > 0.0)
+            .collect();
+        let mut dist = ProbDist::try_from(dist).unwrap_or_default();
+        dist.set_approximation(Approximation::of_all(&dists));
+        dist
+    }
+
+    /// Sum of the kept results, by going through the members while tracking the best results so far.
+    /// Gives up when there are too many combinations of best results
+    fn keep_dist(&self, highest: bool, count: usize) -> Option<ProbDist> {
+        // Sorted with the worst kept result first
+        let dists = self.list.dists();
+        let mut kept: HashMap<Vec<Value>, f64> = HashMap::from([(Vec::new(), 1.0)]);
+        for dist in &dists {
+            let mut next = HashMap::new();
+            for (results, prob) in &kept {
+                for (&outcome, &outcome_prob) in dist.iter() {
+                    let mut results = results.clone();
+                    let index = results.partition_point(|&result| {
+                        if highest {
+                            result < outcome
+                        } else {
+                            result > outcome
+                        }
+                    });
+                    results.insert(index, outcome);
+                    if results.len() > count {
+                        results.remove(0);
+                    }
+                    *next.entry(results).or_insert(0.0) += prob * outcome_prob;
+                }
+            }
+            if next.len() > MAX_KEPT_STATES {
+                return None;
+            }
+            kept = next;
+        }
+
+        let mut dist = BTreeMap::new();
+        for (results, prob) in kept {
+            *dist.entry(eval::sum(results)).or_insert(0.0) += prob;
+        }
+        let mut dist = ProbDist::try_from(dist).unwrap_or_default();
+        dist.set_approximation(Approximation::of_all(&dists));
+        Some(dist)
+    }
+}
+
+impl Rollable for Group {
+    fn roll(&self) -> RollOut {
+        let results = self.list.roll_entries();
+        let values = results.iter().map(|(_, res)| res.value).collect_vec();
+
+        let mut txt = Layouter::from("{");
+        for (index, ((name, mut res), active)) in
+            results.into_iter().zip(self.active(&values)).enumerate()
+        {
+            if index > 0 {
+                txt.append(", ");
+            }
+            if let Some(name) = name {
+                res.txt.append_front(&format!("{name}: "));
+            }
+            if !active {
+                res.txt.strikethrough();
+            }
+            txt += res.txt;
+        }
+        txt.append(&format!("}}{}", self.modifier));
+
+        RollOut {
+            value: self.value(&values),
+            txt,
+            components: self.list.names().zip(values).collect(),
+        }
+    }
+
+    fn dist(&self) -> ProbDist {
+        let n = self.list.len();
+        let (highest, count) = match self.modifier {
+            Modifier::Count(comparison, target) => return self.count_dist(comparison, target),
+            Modifier::Keep { highest, count } => (highest, count.min(n)),
+            Modifier::Drop { highest, count } => (!highest, n - count.min(n)),
+        };
+        self.keep_dist(highest, count)
+            .unwrap_or_else(|| self.bruteforce_probdist())
+    }
+
+    fn roll_quiet(&self) -> Value {
+        self.value(&self.list.roll_values())
+    }
+
+    fn component_dists(&self) -> Vec<(String, ProbDist)> {
+        self.list.component_dists()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{seed, Roll, Rollable};
+
+    #[test]
+    fn keep_test() {
+        // Keeping the highest of two d6 is rolling with advantage
+        let kept = "{d6, d6}kh1".parse::<Roll>().unwrap().dist();
+        let adv = "d|6".parse::<Roll>().unwrap().dist();
+        for (outcome, prob) in adv.iter() {
+            assert!((kept[outcome] - prob).abs() < 1e-9);
+        }
+
+        // Dropping the lowest of two is the same as keeping the highest
+        let dropped = "{2d6, 1d8, 1d4}dl1".parse::<Roll>().unwrap().dist();
+        let kept = "{2d6, 1d8, 1d4}kh2".parse::<Roll>().unwrap().dist();
+        assert_eq!(dropped.len(), kept.len());
+        for (outcome, prob) in kept.iter() {
+            assert!((dropped[outcome] - prob).abs() < 1e-9);
+        }
+        assert_eq!((kept.min(), kept.max()), (Some(3), Some(20)));
+
+        // Among equal results, the first ones are kept
+        for src in ["{5, 1, 5}kh1", "{5, 1, 5}dl2", "{1, 5, 1}kl1"] {
+            let txt = src.parse::<Roll>().unwrap().roll().txt;
+            let struck = txt
+                .sections
+                .iter()
+                .map(|(txt, format)| (txt.trim(), format.strikethrough.width > 0.0))
+                .filter(|(txt, _)| !txt.is_empty() && txt.chars().all(|c| c.is_ascii_digit()))
+                .collect::<Vec<_>>();
+            assert!(!struck[0].1, "{src}: {struck:?}");
+            assert!(struck[1].1 && struck[2].1, "{src}: {struck:?}");
+        }
+    }
+
+    #[test]
+    fn count_test() {
+        seed(37);
+        let roll = "{d20+5, d20+5, d20+5}>=15".parse::<Roll>().unwrap();
+        let dist = roll.dist();
+        // Every attack hits with probability 11/20
+        assert!((dist[&3] - (11.0f64 / 20.0).powi(3)).abs() < 1e-9);
+        for _ in 0..20 {
+            assert!((0..=3).contains(&roll.roll().value));
+        }
+        assert!("{d6, d6}kx1".parse::<Roll>().is_err());
+    }
+}
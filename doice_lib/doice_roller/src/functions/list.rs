@@ -0,0 +1,524 @@
+use std::{collections::BTreeMap, str::FromStr};
+
+use instant::Instant;
+use itertools::Itertools;
+
+use crate::{
+    eval,
+    structure::lin_comb::LinComb,
+    utils::{find_parenth, ln_binomial, split_parenth},
+    Approximation, BruteForceProbDist, DiceError, Expression, Layouter, ProbDist, RollOut,
+    Rollable, Value,
+};
+
+use super::FunctionInit;
+
+/// Several expressions that are rolled independently, written like {3d6, 2d6} or {fire: 3d6, cold: 2d6}.
+/// Its components are the results of the entries, and its value is their sum
+#[derive(Clone, Debug)]
+pub struct List {
+    entries: Vec<(Option<String>, Expression)>,
+}
+
+impl List {
+    pub(super) fn repeated(expr: &Expression, count: usize) -> Self {
+        List {
+            entries: vec![(None, expr.clone()); count],
+        }
+    }
+
+    /// Parses the argument of a function taking a list, which is either a list, a repeat, or multiple expressions separated by commas
+    pub(crate) fn from_args(src: &str) -> Result<Self, DiceError> {
+        let src = src.trim();
+        if src.starts_with('{') {
+            return src.parse();
+        }
+        if let Some(args) = src.strip_prefix("repeat(") {
+            let args = args.strip_suffix(')').ok_or("repeat: missing ')'")?;
+            return List::from_repeat(args);
+        }
+        List::from_entries(src)
+    }
+
+    /// Parses the arguments of repeat(expr, n)
+    fn from_repeat(src: &str) -> Result<Self, DiceError> {
+        let [expr, count] = split_parenth(src, ',')[..] else {
+            return Err("repeat: expected an expression and a count".into());
+        };
+        let expr: Expression = LinComb::from_str(expr)?.into();
+        let count = count.trim().parse().map_err(|_| "repeat: invalid count")?;
+        if count == 0 {
+            return Err("repeat: the count must be at least 1".into());
+        }
+        // Every entry is rolled like a die, so lists are held to the dice limit
+        let max_list = eval::limits().max_dice;
+        if count > max_list {
+            return Err(DiceError::resource_limit(format!(
+                "repeat: the count may be at most {max_list}"
+            )));
+        }
+        Ok(List::repeated(&expr, count))
+    }
+
+    /// Parses entries separated by commas, optionally named like fire: 3d6
+    fn from_entries(src: &str) -> Result<Self, DiceError> {
+        let entries = split_parenth(src, ',')
+            .into_iter()
+            .map(|entry| {
+                let (name, expr) = match find_parenth(entry, ':') {
+                    Some(i) => {
+                        let name = entry[..i].trim();
+                        if name.is_empty() {
+                            return Err("list: empty name".into());
+                        }
+                        (Some(name.to_owned()), &entry[i + 1..])
+                    }
+                    None => (None, entry),
+                };
+                Ok((name, LinComb::from_str(expr)?.into()))
+            })
+            .collect::<Result<Vec<_>, DiceError>>()?;
+        let max_list = eval::limits().max_dice;
+        if entries.len() > max_list {
+            return Err(DiceError::resource_limit(format!(
+                "list: lists may hold at most {max_list} entries"
+            )));
+        }
+        Ok(List { entries })
+    }
+
+    pub(super) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Names of the components, numbered from 1 for unnamed entries
+    pub(super) fn names(&self) -> impl Iterator<Item = String> + '_ {
+        self.entries
+            .iter()
+            .zip(1..)
+            .map(|((name, _), index)| name.clone().unwrap_or_else(|| index.to_string()))
+    }
+
+    pub(super) fn dists(&self) -> Vec<ProbDist> {
+        self.entries.iter().map(|(_, expr)| expr.dist()).collect()
+    }
+
+    pub(super) fn roll_values(&self) -> Vec<Value> {
+        self.entries
+            .iter()
+            .map(|(_, expr)| expr.roll_quiet())
+            .collect()
+    }
+
+    /// Rolls every entry, with its name if it has one
+    pub(super) fn roll_entries(&self) -> Vec<(Option<&str>, RollOut)> {
+        self.entries
+            .iter()
+            .map(|(name, expr)| (name.as_deref(), expr.roll()))
+            .collect()
+    }
+}
+
+impl FromStr for List {
+    type Err = DiceError;
+
+    fn from_str(src: &str) -> Result<Self, Self::Err> {
+        let inner = src
+            .trim()
+            .strip_prefix('{')
+            .and_then(|src| src.strip_suffix('}'))
+            .ok_or("list: missing '}'")?;
+        if inner.trim().is_empty() {
+            return Err("list: a list needs at least one entry".into());
+        }
+        List::from_entries(inner)
+    }
+}
+
+impl Rollable for List {
+    fn roll(&self) -> RollOut {
+        let mut out = RollOut::default();
+        out.txt.append("{");
+        for (((name, expr), component), index) in self.entries.iter().zip(self.names()).zip(0..) {
+            if index > 0 {
+                out.txt.append(", ");
+            }
+            if let Some(name) = name {
+                out.txt.append(&format!("{name}: "));
+            }
+            let res = expr.roll();
+            out.txt += res.txt;
+            out.value = eval::add(out.value, res.value);
+            out.components.push((component, res.value));
+        }
+        out.txt.append("}");
+        out
+    }
+
+    fn dist(&self) -> ProbDist {
+        self.dists()
+            .iter()
+            .fold(ProbDist::constant(0), |acc, dist| acc + dist)
+    }
+
+    fn roll_quiet(&self) -> Value {
+        eval::sum(self.roll_values())
+    }
+
+    fn component_dists(&self) -> Vec<(String, ProbDist)> {
+        self.names().zip(self.dists()).collect()
+    }
+}
+
+impl FunctionInit for List {
+    const DOC: &'static str = "Rolls an expression a number of times, resulting in a list of the results.\nLists can also be written as {3d6, 2d6} or {fire: 3d6, cold: 2d6}, and have the sum of their entries as value.\nFollowed by kh2, kl1, dh1 or dl1, the highest or lowest entries are kept or dropped, and followed by a comparison like >=15, the entries meeting it are counted.\nDice take the same modifiers directly, 4d6kh3 and 3d20>=15 rolling their dice as a list of single dice.\nUsage: repeat(expr, n) or {expr1, expr2, ...}(modifier)";
+
+    fn generate(input: &str) -> Result<Expression, DiceError> {
+        Ok(List::from_repeat(input)?.into())
+    }
+}
+
+/// Turns a cumulative distribution, P(X <= x) for every outcome x, into a distribution as accurate as approx
+fn from_cumulative(
+    cumulative: impl IntoIterator<Item = (Value, f64)>,
+    approx: Option<Approximation>,
+) -> ProbDist {
+    let mut prev = 0.0;
+    let dist = cumulative
+        .into_iter()
+        .map(|(outcome, cum_prob)| {
+            let prob = cum_prob - prev;
+            prev = cum_prob;
+            (outcome, prob)
+        })
+        .filter(|(_, prob)| *prob > 0.0)
+        .collect::<BTreeMap<_, _>>();
+    let mut dist = ProbDist::try_from(dist).unwrap_or_default();
+    dist.set_approximation(approx);
+    dist
+}
+
+/// All outcomes of the distributions with P(X <= x) for every one of them.
+/// Gives None if this takes longer than the time budget
+fn cumulative_probs(dists: &[ProbDist]) -> Option<(Vec<Value>, Vec<Vec<f64>>)> {
+    let time_budget = eval::limits().time_budget;
+    let timestamp = Instant::now();
+    let outcomes = dists
+        .iter()
+        .flat_map(|dist| dist.keys().copied())
+        .sorted()
+        .dedup()
+        .collect_vec();
+    let mut cumulative = Vec::with_capacity(dists.len());
+    for dist in dists {
+        if timestamp.elapsed() > time_budget {
+            eval::report(DiceError::resource_limit(
+                "computing the distribution took too long",
+            ));
+            return None;
+        }
+        // Both the outcomes and the distribution are sorted, so a running sum goes through them together
+        let mut probs = dist.iter().peekable();
+        let mut total = 0.0;
+        let cum_probs = outcomes
+            .iter()
+            .map(|outcome| {
+                while let Some((_, prob)) = probs.next_if(|(k, _)| *k <= outcome) {
+                    total += prob;
+                }
+                total
+            })
+            .collect();
+        cumulative.push(cum_probs);
+    }
+    Some((outcomes, cumulative))
+}
+
+/// Sorts the components of a list
+#[derive(Clone, Debug)]
+pub struct Sort {
+    list: List,
+    descending: bool,
+}
+
+impl Sort {
+    fn sort(&self, values: &mut [Value]) {
+        values.sort_unstable();
+        if self.descending {
+            values.reverse();
+        }
+    }
+
+    /// Distributions of the sorted components, which are order statistics of the entries
+    fn sorted_dists(&self) -> Vec<ProbDist> {
+        let dists = self.list.dists();
+        let n = dists.len();
+        let identical = dists
+            .iter()
+            .tuple_windows()
+            .all(|(a, b)| a.iter().eq(b.iter()));
+        if !identical {
+            return (0..n)
+                .map(|index| {
+                    SortedComponent {
+                        sort: self.clone(),
+                        index,
+                    }
+                    .bruteforce_probdist()
+                })
+                .collect();
+        }
+
+        // The k-th lowest value is at most x if at least k + 1 of the values are at most x
+        let Some((outcomes, cumulative)) = cumulative_probs(&dists[..1]) else {
+            return vec![ProbDist::default(); n];
+        };
+        let mut sorted = (0..n)
+            .map(|k| {
+                let cumulative = outcomes.iter().zip(&cumulative[0]).map(|(&outcome, &cum)| {
+                    let at_most = (k + 1..=n)
+                        .map(|j| {
+                            ln_binomial(n, j).exp()
+                                * cum.powi(j as i32)
+                                * (1.0 - cum).powi((n - j) as i32)
+                        })
+                        .sum::<f64>();
+                    (outcome, at_most.min(1.0))
+                });
+                from_cumulative(cumulative, dists[0].approximation())
+            })
+            .collect_vec();
+        if self.descending {
+            sorted.reverse();
+        }
+        sorted
+    }
+}
+
+impl FunctionInit for Sort {
+    const DOC: &'static str = "Sorts the results of a list from low to high, or from high to low with desc.\nUsage: sort(list, (asc/desc))";
+
+    fn generate(input: &str) -> Result<Expression, DiceError> {
+        let (list, order) = match split_parenth(input, ',')[..] {
+            [list] => (list, "asc"),
+            [list, order] => (list, order.trim()),
+            _ => return Err("sort: expected a list and an order".into()),
+        };
+        let descending = match order {
+            "asc" => false,
+            "desc" => true,
+            _ => return Err("sort: the order must be asc or desc".into()),
+        };
+
+        Ok(Sort {
+            list: List::from_args(list)?,
+            descending,
+        }
+        .into())
+    }
+}
+
+impl Rollable for Sort {
+    fn roll(&self) -> RollOut {
+        let mut out = self.list.roll();
+        let mut values = out.components.iter().map(|(_, value)| *value).collect_vec();
+        self.sort(&mut values);
+        out.txt.append(" sorted: ");
+        out.txt.append(&format!("{{{}}}", values.iter().join(", ")));
+        out.components = (1..)
+            .map(|index: usize| index.to_string())
+            .zip(values)
+            .collect();
+        out
+    }
+
+    fn dist(&self) -> ProbDist {
+        self.list.dist()
+    }
+
+    fn roll_quiet(&self) -> Value {
+        self.list.roll_quiet()
+    }
+
+    fn component_dists(&self) -> Vec<(String, ProbDist)> {
+        (1..)
+            .map(|index: usize| index.to_string())
+            .zip(self.sorted_dists())
+            .collect()
+    }
+}
+
+/// A single component of a sorted list, for bruteforcing its distribution
+#[derive(Clone, Debug)]
+struct SortedComponent {
+    sort: Sort,
+    index: usize,
+}
+
+impl Rollable for SortedComponent {
+    fn roll(&self) -> RollOut {
+        let value = self.roll_quiet();
+        RollOut {
+            value,
+            txt: Layouter::from(value.to_string().as_str()),
+            ..Default::default()
+        }
+    }
+
+    fn dist(&self) -> ProbDist {
+        self.bruteforce_probdist()
+    }
+
+    fn roll_quiet(&self) -> Value {
+        let mut values = self.sort.list.roll_values();
+        self.sort.sort(&mut values);
+        values[self.index]
+    }
+}
+
+/// The highest or lowest result in a list
+#[derive(Clone, Debug)]
+pub struct Extreme<const MAX: bool> {
+    list: List,
+}
+
+pub type Max = Extreme<true>;
+pub type Min = Extreme<false>;
+
+impl<const MAX: bool> Extreme<MAX> {
+    const NAME: &'static str = if MAX { "max" } else { "min" };
+
+    fn pick(values: impl Iterator<Item = Value>) -> Value {
+        let value = if MAX { values.max() } else { values.min() };
+        value.unwrap_or_default()
+    }
+}
+
+impl<const MAX: bool> FunctionInit for Extreme<MAX> {
+    const DOC: &'static str = if MAX {
+        "Highest result out of a list, or out of multiple expressions.\nUsage: max(list) or max(expr1, expr2, ...)"
+    } else {
+        "Lowest result out of a list, or out of multiple expressions.\nUsage: min(list) or min(expr1, expr2, ...)"
+    };
+
+    fn generate(input: &str) -> Result<Expression, DiceError> {
+        Ok(Extreme::<MAX> {
+            list: List::from_args(input)?,
+        }
+        .into())
+    }
+}
+
+impl<const MAX: bool> Rollable for Extreme<MAX> {
+    fn roll(&self) -> RollOut {
+        let res = self.list.roll();
+        let mut txt = Layouter::from(Self::NAME);
+        txt += res.txt;
+        RollOut {
+            value: Self::pick(res.components.into_iter().map(|(_, value)| value)),
+            txt,
+            ..Default::default()
+        }
+    }
+
+    /// As the entries are independent, P(max <= x) is the product of P(entry <= x) over all entries,
+    /// and P(min > x) is the product of P(entry > x)
+    fn dist(&self) -> ProbDist {
+        let dists = self.list.dists();
+        let Some((outcomes, cumulative)) = cumulative_probs(&dists) else {
+            return ProbDist::default();
+        };
+        let extremes = outcomes.iter().enumerate().map(|(i, &outcome)| {
+            let cum_prob = if MAX {
+                cumulative.iter().map(|cum| cum[i]).product()
+            } else {
+                1.0 - cumulative.iter().map(|cum| 1.0 - cum[i]).product::<f64>()
+            };
+            (outcome, cum_prob)
+        });
+        from_cumulative(extremes, Approximation::of_all(&dists))
+    }
+
+    fn roll_quiet(&self) -> Value {
+        Self::pick(self.list.roll_values().into_iter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use instant::Instant;
+
+    use crate::{seed, ErrorKind, EvalLimits, Roll, Rollable};
+
+    #[test]
+    fn components_test() {
+        seed(34);
+        let roll = "{fire: 3d6, cold: 2d6} + 5".parse::<Roll>().unwrap();
+        let out = roll.roll();
+        let names = out
+            .components
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["fire", "cold"]);
+        let total: isize = out.components.iter().map(|(_, value)| value).sum();
+        assert_eq!(out.value, total + 5);
+
+        let dists = roll.component_dists();
+        assert!((dists[0].1.expectation() - 10.5).abs() < 1e-9);
+        assert!((dists[1].1.expectation() - 7.0).abs() < 1e-9);
+        assert!((roll.dist().expectation() - 22.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn sort_test() {
+        let roll = "sort(repeat(d6, 3), desc)".parse::<Roll>().unwrap();
+        for _ in 0..20 {
+            let out = roll.roll();
+            assert!(out.components.windows(2).all(|w| w[0].1 >= w[1].1));
+        }
+
+        // The highest of 3d6 is the same as max
+        let dists = roll.component_dists();
+        let max = "max(repeat(d6, 3))".parse::<Roll>().unwrap().dist();
+        assert!(dists[0]
+            .1
+            .iter()
+            .zip(max.iter())
+            .all(|(a, b)| a.0 == b.0 && (a.1 - b.1).abs() < 1e-12));
+        assert!((max[&1] - 1.0 / 216.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn extreme_test() {
+        // min(d4, d6) is 1 unless both are higher
+        let dist = "min(d4, d6)".parse::<Roll>().unwrap().dist();
+        assert!((dist[&1] - (1.0 - 3.0 / 4.0 * 5.0 / 6.0)).abs() < 1e-12);
+        assert!(dist.keys().copied().eq(1..=4));
+        assert!("repeat(d6, 0)".parse::<Roll>().is_err());
+
+        // A running sum per entry keeps many outcomes quick
+        let start = Instant::now();
+        let dist = "max(repeat(d20000, 20))".parse::<Roll>().unwrap().dist();
+        assert_eq!(dist.max(), Some(20_000));
+        assert!(start.elapsed() < Duration::from_secs(10));
+    }
+
+    #[test]
+    fn list_limit_test() {
+        let limits = EvalLimits {
+            max_dice: 3,
+            ..Default::default()
+        };
+        let kind = |src| {
+            Roll::parse_with_limits(src, limits)
+                .err()
+                .map(|err| err.kind())
+        };
+        assert_eq!(kind("repeat(d6, 3)"), None);
+        assert_eq!(kind("repeat(1, 4)"), Some(ErrorKind::ResourceLimit));
+        assert_eq!(kind("{1, 2, 3, 4}kh1"), Some(ErrorKind::ResourceLimit));
+    }
+}
@@ -36,6 +36,7 @@ impl Rollable for Mirror {
         RollOut {
             value: sign * expr_out.value,
             txt,
+            ..Default::default()
         }
     }
 
@@ -1,4 +1,7 @@
 use self::crit::Crit;
+use self::distributions::{
+    Binomial, Geometric, Hypergeometric, NegBinomial, Normal, Parametrized, Uniform,
+};
 
 use super::{DiceError, Expression, Rollable};
 
@@ -17,6 +20,17 @@ mod poisson;
 mod outcomes;
 mod blackjack;
 mod panic;
+mod save;
+mod distributions;
+mod deck;
+mod table;
+mod list;
+mod arithmetic;
+mod group;
+
+pub use table::{load_tables, load_tables_from_str, register_table, Table};
+pub(crate) use group::Group;
+pub(crate) use list::List;
 
 pub trait FunctionInit: Rollable {
     const DOC: &'static str;
@@ -44,6 +58,25 @@ const FUNCTION_GENERATORS: &[(&str, FunctionGenerator)] = &[
     ("outcomes", outcomes::Outcomes::generate),
     ("blackjack", blackjack::Blackjack::generate),
     ("panic", panic::Panic::generate),
+    ("save", save::Save::generate),
+    ("binom", Parametrized::<Binomial>::generate),
+    ("geom", Parametrized::<Geometric>::generate),
+    ("negbin", Parametrized::<NegBinomial>::generate),
+    ("hypergeom", Parametrized::<Hypergeometric>::generate),
+    ("uniform", Parametrized::<Uniform>::generate),
+    ("normal", Parametrized::<Normal>::generate),
+    ("draw", deck::Draw::generate),
+    ("deck", deck::Draw::generate_single),
+    ("table", Table::generate),
+    ("repeat", List::generate),
+    ("sort", list::Sort::generate),
+    ("max", list::Max::generate),
+    ("min", list::Min::generate),
+    ("floor", arithmetic::Floor::generate),
+    ("ceil", arithmetic::Ceil::generate),
+    ("round", arithmetic::Round::generate),
+    ("abs", arithmetic::Abs::generate),
+    ("clamp", arithmetic::Clamp::generate),
 ];
 
 pub const FUNCTION_DOCS: &[(&str, &str)] = &[
@@ -53,9 +86,28 @@ pub const FUNCTION_DOCS: &[(&str, &str)] = &[
     ("Sum", sum::Sum::DOC),
     ("Critical attack damage", Crit::DOC),
     ("Attack", attack::Attack::DOC),
+    ("Saving throw", save::Save::DOC),
     ("Mirror", mirror::Mirror::DOC),
     ("Bernoulli / Coin toss", bernoulli::Bernoulli::DOC),
     ("Poisson", poisson::Poisson::DOC),
+    ("Binomial", Parametrized::<Binomial>::DOC),
+    ("Geometric", Parametrized::<Geometric>::DOC),
+    ("Negative binomial", Parametrized::<NegBinomial>::DOC),
+    ("Hypergeometric", Parametrized::<Hypergeometric>::DOC),
+    ("Uniform", Parametrized::<Uniform>::DOC),
+    ("Normal", Parametrized::<Normal>::DOC),
+    ("Draw cards", deck::Draw::DOC),
+    ("Single card", deck::Draw::SINGLE_DOC),
+    ("Random table", Table::DOC),
+    ("Repeat / lists", List::DOC),
+    ("Sort", list::Sort::DOC),
+    ("Maximum", list::Max::DOC),
+    ("Minimum", list::Min::DOC),
+    ("Round down", arithmetic::Floor::DOC),
+    ("Round up", arithmetic::Ceil::DOC),
+    ("Round to nearest", arithmetic::Round::DOC),
+    ("Absolute value", arithmetic::Abs::DOC),
+    ("Clamp", arithmetic::Clamp::DOC),
     ("Add nonzero", add_nonzero::AddNonZero::DOC),
     ("List of outcomes", outcomes::Outcomes::DOC),
     ("Blackjack", blackjack::Blackjack::DOC),
@@ -6,18 +6,19 @@ use crate::structure::expression::Expression;
 pub struct Panic {}
 
 impl FunctionInit for Panic {
-    const DOC: &'static str = "Will halt and catch fire.\nUsage: panic()";
+    const DOC: &'static str =
+        "Would halt and catch fire, but fails to parse instead.\nUsage: panic()";
 
     fn generate(_input: &str) -> Result<Expression, DiceError> {
-        panic!("The user wants to see the world BURN.")
+        Err("panic: the user wants to see the world BURN, but it refuses to".into())
     }
 }
 impl Rollable for Panic {
     fn roll(&self) -> RollOut {
-        todo!()
+        RollOut::default()
     }
 
     fn dist(&self) -> ProbDist {
-        todo!()
+        ProbDist::default()
     }
 }
\ No newline at end of file
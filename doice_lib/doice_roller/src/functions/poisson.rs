@@ -1,70 +1,159 @@
 use std::collections::BTreeMap;
 
-use std::ops::Mul;
 use crate::functions::FunctionInit;
-use crate::{DiceError, ProbDist, Rollable, RollOut};
 use crate::structure::expression::Expression;
+use crate::{eval, DiceError, ProbDist, RollOut, Rollable, Value};
 
 use crate::structure::num_expresson::NumericExpression;
-
-fn factorial(n: usize) -> f64 {
-    if n == 0 {
-        1.0
-    } else {
-        (1..=n).map(|i| i as f64).fold(n as f64, f64::mul)
-    }
-}
+use crate::utils::{ln_factorial, PROB_CUTOFF};
 
 #[derive(Clone, Default, Debug)]
 pub struct Poisson {
     avg_events: NumericExpression,
+    /// The distribution for a constant average, so it is not recomputed on every roll
+    const_dist: Option<ProbDist>,
 }
 
 impl Poisson {
-    fn calc_prob(num_events: usize, avg_events: f64) -> f64 {
-        let lambda = avg_events;
-        let k = num_events;
-        (lambda.powi(k as i32)*(-lambda).exp()) / factorial(k)
+    /// ln(P(X = k)) for a poisson distribution with the given average
+    fn ln_prob(num_events: usize, avg_events: f64) -> f64 {
+        num_events as f64 * avg_events.ln() - avg_events - ln_factorial(num_events)
     }
 
-    fn recalc_dist(&self, avg_events: f64) -> ProbDist {
-        // TODO: Make this work for more than 20 events.
-        let mut dist = BTreeMap::<isize, f64>::new();
-        for i in 0..20 {
-            dist.insert(i, dbg!(Self::calc_prob(i as usize, avg_events)));
+    /// Computes the distribution by walking away from the mode in both directions,
+    /// until the probabilities drop below the cutoff
+    fn recalc_dist(avg_events: f64) -> ProbDist {
+        if avg_events <= 0.0 {
+            return ProbDist::constant(0);
+        }
+        if avg_events > eval::limits().max_support as f64 {
+            eval::report(too_many_events());
+            return ProbDist::default();
+        }
+
+        let mode = avg_events.floor() as usize;
+        let ln_mode = Self::ln_prob(mode, avg_events);
+        let ln_avg = avg_events.ln();
+        let ln_cutoff = PROB_CUTOFF.ln();
+        let mut dist = BTreeMap::<Value, f64>::new();
+        dist.insert(mode as Value, ln_mode.exp());
+
+        // ln(P(k - 1)) = ln(P(k)) + ln(k) - ln(lambda)
+        let mut ln_prob = ln_mode;
+        for k in (1..=mode).rev() {
+            ln_prob += (k as f64).ln() - ln_avg;
+            if ln_prob < ln_cutoff {
+                break;
+            }
+            dist.insert(k as Value - 1, ln_prob.exp());
         }
-        dist.try_into().expect("Bad math in recalc_dist")
+
+        // ln(P(k + 1)) = ln(P(k)) + ln(lambda) - ln(k + 1)
+        let mut ln_prob = ln_mode;
+        for k in mode.. {
+            ln_prob += ln_avg - ((k + 1) as f64).ln();
+            if ln_prob < ln_cutoff {
+                break;
+            }
+            dist.insert(k as Value + 1, ln_prob.exp());
+        }
+
+        let mut dist = ProbDist::try_from(dist).unwrap_or_default();
+        dist.proper_scale();
+        dist
     }
 }
 
+fn too_many_events() -> DiceError {
+    DiceError::resource_limit(format!(
+        "poisson: the average number of events may be at most {}",
+        eval::limits().max_support
+    ))
+}
+
 impl Rollable for Poisson {
     fn roll(&self) -> RollOut {
-        self.recalc_dist(self.avg_events.evaluate()).roll()
+        match &self.const_dist {
+            Some(dist) => dist.roll(),
+            None => Self::recalc_dist(self.avg_events.evaluate()).roll(),
+        }
     }
 
     fn dist(&self) -> ProbDist {
         match &self.avg_events {
-            NumericExpression::Constant(lambda) => {self.recalc_dist(*lambda)}
+            NumericExpression::Constant(lambda) => self
+                .const_dist
+                .clone()
+                .unwrap_or_else(|| Self::recalc_dist(*lambda)),
+            // Mix the distributions for all possible averages
             NumericExpression::Stochastic(expr) => {
-                let mut accumulator = BTreeMap::<isize, f64>::new();
-                for (lambda, scale) in expr.dist().inner() {
-                    let dist = self.recalc_dist(lambda as f64);
-                    for (outcome, prob) in dist.inner() {
-                        accumulator.entry(outcome).and_modify(|p| *p += prob*scale).or_insert(prob*scale);
-                    }
-                }
-                accumulator.try_into().expect("Mathematical error in the Poisson dist.")
+                ProbDist::from_parameter_distribution(&expr.dist(), |lambda| {
+                    Self::recalc_dist(lambda as f64)
+                })
             }
         }
     }
 }
 
 impl FunctionInit for Poisson {
-    const DOC: &'static str = "Poisson distribution for a given average number of events.";
+    const DOC: &'static str = "Poisson distribution for a given average number of events, which may itself be random.\nA random average that is not positive always results in 0 events.\nUsage: poisson(avg events)";
 
     fn generate(input: &str) -> Result<Expression, DiceError> {
+        let avg_events: NumericExpression = input.parse()?;
+        let const_dist = match avg_events {
+            NumericExpression::Constant(lambda) if lambda < 0.0 || !lambda.is_finite() => {
+                return Err("poisson: the average number of events must be positive".into());
+            }
+            NumericExpression::Constant(lambda) if lambda > eval::limits().max_support as f64 => {
+                return Err(too_many_events());
+            }
+            NumericExpression::Constant(lambda) => Some(Self::recalc_dist(lambda)),
+            NumericExpression::Stochastic(_) => None,
+        };
+
         Ok(Poisson {
-            avg_events: input.parse()?,
-        }.into())
+            avg_events,
+            const_dist,
+        }
+        .into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Poisson;
+    use crate::{utils::ln_factorial, Roll, Rollable};
+
+    #[test]
+    fn ln_factorial_test() {
+        assert_eq!(ln_factorial(0), 0.0);
+        assert!((ln_factorial(5) - 120f64.ln()).abs() < 1e-12);
+        // Both sides of the Stirling threshold should agree
+        let exact: f64 = (2..=300).map(|i| (i as f64).ln()).sum();
+        assert!((ln_factorial(300) - exact).abs() < 1e-9);
+    }
+
+    #[test]
+    fn known_values_test() {
+        let dist = Poisson::recalc_dist(1.0);
+        let e_inv = (-1.0f64).exp();
+        assert!((dist[&0] - e_inv).abs() < 1e-12);
+        assert!((dist[&1] - e_inv).abs() < 1e-12);
+        assert!((dist[&2] - e_inv / 2.0).abs() < 1e-12);
+        assert!((dist[&5] - e_inv / 120.0).abs() < 1e-12);
+
+        // Large averages should neither underflow nor be cut off at 20 events
+        let dist = Poisson::recalc_dist(1000.0);
+        assert!((dist.expectation() - 1000.0).abs() < 1e-6);
+        assert!((dist.var() - 1000.0).abs() < 1e-3);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn stochastic_avg_test() {
+        let dist = "poisson(d2)".parse::<Roll>().unwrap().dist();
+        let expected = 0.5 * ((-1.0f64).exp() + (-2.0f64).exp());
+        assert!((dist[&0] - expected).abs() < 1e-12);
+        assert!((dist.expectation() - 1.5).abs() < 1e-9);
+        assert!("poisson(-2)".parse::<Roll>().is_err());
+    }
+}
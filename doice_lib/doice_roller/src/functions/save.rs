@@ -0,0 +1,194 @@
+use std::{collections::BTreeMap, str::FromStr};
+
+use crate::{
+    eval, layouter::Layouter, structure::lin_comb::LinComb, utils::split_parenth, DiceError,
+    DiceRoller, Expression, ProbDist, RollOut, Rollable, Value,
+};
+
+use egui::Color32;
+
+use super::FunctionInit;
+
+/// What happens to the damage of a target depending on its saving throw
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum OnSuccess {
+    /// Half damage on a success, full damage on a failure
+    #[default]
+    Half,
+    /// No damage on a success, full damage on a failure
+    None,
+    /// No damage on a success, half damage on a failure
+    Evasion,
+}
+
+impl OnSuccess {
+    /// Damage taken by a target that succeeded its save
+    fn success(self, dmg: Value) -> Value {
+        match self {
+            OnSuccess::Half => dmg / 2,
+            OnSuccess::None | OnSuccess::Evasion => 0,
+        }
+    }
+
+    /// Damage taken by a target that failed its save
+    fn failure(self, dmg: Value) -> Value {
+        match self {
+            OnSuccess::Half | OnSuccess::None => dmg,
+            OnSuccess::Evasion => dmg / 2,
+        }
+    }
+}
+
+impl FromStr for OnSuccess {
+    type Err = DiceError;
+
+    fn from_str(src: &str) -> Result<Self, Self::Err> {
+        match src.trim() {
+            "half" => Ok(OnSuccess::Half),
+            "none" => Ok(OnSuccess::None),
+            "evasion" => Ok(OnSuccess::Evasion),
+            _ => Err("save: on success must be one of half, none or evasion".into()),
+        }
+    }
+}
+
+/// Damage dealt by an effect that allows a saving throw, such as fireball.
+/// The damage is rolled once and applied to every target, each of which saves separately.
+#[derive(Clone, Debug)]
+pub struct Save {
+    dmg: Expression,
+    save_bonus: Value,
+    dc: Value,
+    on_success: OnSuccess,
+    d20: DiceRoller,
+    targets: usize,
+}
+
+impl Save {
+    /// Probability that a single target succeeds its saving throw
+    fn success_chance(&self) -> f64 {
+        let d20 = self.d20.dist();
+        d20.iter()
+            .filter(|(roll, _)| roll.saturating_add(self.save_bonus) >= self.dc)
+            .map(|(_, prob)| prob)
+            .sum()
+    }
+}
+
+impl FunctionInit for Save {
+    const DOC: &'static str = "Rolls damage once for an effect that allows a saving throw, and sums the damage taken by all targets.\nOn success a target takes half (half), no (none) damage, or no damage and half on a failure (evasion).\nUsage: save(dmg roll, save bonus, dc, half/none/evasion, (adv/dis), (targets=1))";
+
+    fn generate(input: &str) -> Result<Expression, DiceError> {
+        let args = split_parenth(input, ',');
+        if args.len() < 4 {
+            return Err("save: expected at least a dmg roll, save bonus, dc and on success".into());
+        }
+
+        let mut advantage = 0;
+        let mut targets = 1;
+        for arg in &args[4..] {
+            match arg.trim() {
+                "adv" => advantage = 1,
+                "dis" => advantage = -1,
+                other => {
+                    targets = other.parse().map_err(|_| {
+                        "save: invalid optional argument, expected adv, dis or a number of targets"
+                    })?;
+                }
+            }
+        }
+
+        if targets == 0 {
+            return Err("save: there must be at least one target".into());
+        }
+        let max_targets = eval::limits().max_dice;
+        if targets > max_targets {
+            return Err(DiceError::resource_limit(format!(
+                "save: there may be at most {max_targets} targets"
+            )));
+        }
+
+        Ok(Save {
+            dmg: LinComb::from_str(args[0])?.into(),
+            save_bonus: args[1]
+                .trim()
+                .parse()
+                .map_err(|_| "save: invalid save bonus")?,
+            dc: args[2].trim().parse().map_err(|_| "save: invalid dc")?,
+            on_success: args[3].parse()?,
+            d20: DiceRoller::new(20, 1, advantage),
+            targets,
+        }
+        .into())
+    }
+}
+
+impl Rollable for Save {
+    fn roll(&self) -> RollOut {
+        let dmg = self.dmg.roll();
+        let mut txt = Layouter::from("[");
+        txt += dmg.txt;
+        txt.append(" vs DC ");
+        txt.append(&self.dc.to_string());
+        txt.append(":");
+
+        let mut total = 0;
+        for _ in 0..self.targets {
+            let save = self.d20.roll_quiet().saturating_add(self.save_bonus);
+            txt.append(" ");
+            if save >= self.dc {
+                total = eval::add(total, self.on_success.success(dmg.value));
+                txt.append_colored(&save.to_string(), Color32::GREEN);
+            } else {
+                total = eval::add(total, self.on_success.failure(dmg.value));
+                txt.append_colored(&save.to_string(), Color32::RED);
+            }
+        }
+        txt.append("]");
+
+        RollOut { value: total, txt, ..Default::default() }
+    }
+
+    fn roll_quiet(&self) -> Value {
+        let dmg = self.dmg.roll_quiet();
+        eval::sum((0..self.targets).map(|_| {
+            if self.d20.roll_quiet().saturating_add(self.save_bonus) >= self.dc {
+                self.on_success.success(dmg)
+            } else {
+                self.on_success.failure(dmg)
+            }
+        }))
+    }
+
+    /// The number of successful saves is binomially distributed, and independent of the damage roll
+    fn dist(&self) -> ProbDist {
+        let p = self.success_chance();
+        let n = self.targets;
+
+        // P(k successes) for k in 0..=n
+        let mut successes = Vec::with_capacity(n + 1);
+        let mut binom = 1.0;
+        for k in 0..=n {
+            successes.push(binom * p.powi(k as i32) * (1.0 - p).powi((n - k) as i32));
+            binom *= (n - k) as f64 / (k + 1) as f64;
+        }
+
+        let dmg_dist = self.dmg.dist();
+        let mut out = BTreeMap::new();
+        for (&dmg, &dmg_prob) in dmg_dist.iter() {
+            let success = self.on_success.success(dmg);
+            let failure = self.on_success.failure(dmg);
+            for (k, &k_prob) in successes.iter().enumerate() {
+                let total = eval::add(
+                    eval::mul(k as Value, success),
+                    eval::mul((n - k) as Value, failure),
+                );
+                *out.entry(total).or_insert(0.0) += dmg_prob * k_prob;
+            }
+        }
+
+        let mut out = ProbDist::try_from(out).unwrap_or_default();
+        out.set_approximation(dmg_dist.approximation());
+        out
+    }
+}
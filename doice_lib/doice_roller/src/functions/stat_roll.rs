@@ -2,8 +2,8 @@ use egui::TextFormat;
 use itertools::Itertools;
 
 use crate::{
-    dice_roller::DiceRoller, layouter::LINE, BruteForceProbDist, DiceError, Expression, Layouter,
-    ProbDist, RollOut, Rollable,
+    dice_roller::DiceRoller, layouter::line, DiceError, Expression, Layouter, ProbDist, RollOut,
+    Rollable, SampleDist,
 };
 
 use super::FunctionInit;
@@ -29,7 +29,7 @@ impl Rollable for StatRoller {
         let mut total: isize = roll_outs.iter_mut().map(|out| out.value).sum();
         let min = roll_outs.iter_mut().min_by_key(|out| out.value).unwrap();
         min.txt.sections[1].1 = TextFormat {
-            strikethrough: LINE,
+            strikethrough: line(),
             ..Default::default()
         };
         total -= min.value;
@@ -46,10 +46,17 @@ impl Rollable for StatRoller {
         RollOut {
             value: total,
             txt: out_txt,
+            ..Default::default()
         }
     }
 
+    /// Enumerates all 6^4 possible rolls
     fn dist(&self) -> ProbDist {
-        self.bruteforce_probdist()
+        let mut counts = SampleDist::new();
+        for rolls in (0..4).map(|_| 1..=6).multi_cartesian_product() {
+            let total: isize = rolls.iter().sum();
+            counts.add_sample(total - rolls.iter().min().unwrap());
+        }
+        counts.into()
     }
 }
@@ -3,8 +3,8 @@ use std::str::FromStr;
 use itertools::Itertools;
 
 use crate::{
-    structure::lin_comb::LinComb, utils::split_once_parenth, DiceError, Expression, Layouter,
-    ProbDist, RollOut, Rollable,
+    eval, structure::lin_comb::LinComb, utils::split_once_parenth, DiceError, Expression, Layouter,
+    ProbDist, RollOut, Rollable, Value,
 };
 
 use super::FunctionInit;
@@ -42,11 +42,19 @@ impl Rollable for Sum {
         let mut out = RollOut::default();
         out.txt.append("[");
 
-        let n = self.n.roll_quiet();
+        let mut n = self.n.roll_quiet();
+        let max_dice = eval::limits().max_dice;
+        if n > max_dice as Value {
+            eval::report(DiceError::resource_limit(format!(
+                "sum: at most {max_dice} repetitions are allowed"
+            )));
+            n = 0;
+        }
 
         out = Itertools::intersperse_with((0..n).map(|_| self.expr.roll()), || RollOut {
             value: 0,
             txt: space.clone(),
+            ..Default::default()
         })
         .fold(out, |acc, elem| acc + elem);
 
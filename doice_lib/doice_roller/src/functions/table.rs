@@ -0,0 +1,276 @@
+use std::{collections::BTreeMap, fs, ops::RangeInclusive, path::Path, str::FromStr, sync::RwLock};
+
+use serde::Deserialize;
+
+use crate::{eval, DiceError, Expression, ProbDist, Roll, RollOut, Rollable, Value};
+
+use super::FunctionInit;
+
+/// Tables that can be rolled on by name, see `register_table`
+static TABLES: RwLock<BTreeMap<String, Table>> = RwLock::new(BTreeMap::new());
+
+/// A random table, mapping ranges of rolls to text entries
+#[derive(Clone, Debug)]
+pub struct Table {
+    roll: Roll,
+    entries: Vec<(RangeInclusive<Value>, String)>,
+}
+
+impl Table {
+    /// Creates a table from its entries, rolled on using roll.
+    /// Without a roll, a die covering all entries is used
+    pub fn new(
+        roll: Option<&str>,
+        entries: Vec<(RangeInclusive<Value>, String)>,
+    ) -> Result<Self, DiceError> {
+        let min = entries.iter().map(|(range, _)| *range.start()).min();
+        let max = entries.iter().map(|(range, _)| *range.end()).max();
+        let (Some(min), Some(max)) = (min, max) else {
+            return Err("table: a table needs at least one entry".into());
+        };
+        let roll = match roll {
+            Some(roll) => roll.to_owned(),
+            None if min == 1 => format!("d{max}"),
+            None => format!("uniform({min}, {max})"),
+        };
+        // Tables defined inside an expression are held to the limits of that expression
+        let roll = Roll::parse_with_limits(&roll, eval::limits())?;
+
+        Ok(Table { roll, entries })
+    }
+
+    /// The entry for a roll, if any
+    #[must_use]
+    pub fn entry(&self, roll: Value) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(range, _)| range.contains(&roll))
+            .map(|(_, entry)| entry.as_str())
+    }
+}
+
+/// Parses a single value or a range like 1-3
+fn parse_range(src: &str) -> Result<RangeInclusive<Value>, DiceError> {
+    let src = src.trim();
+    let err = || format!("table: invalid range {src}");
+    // Skip the first character, so a leading minus sign is not taken for a range
+    let split = src
+        .char_indices()
+        .skip(1)
+        .find(|(_, c)| *c == '-')
+        .map(|(i, _)| i);
+    let (start, end) = match split {
+        Some(i) => (&src[..i], &src[i + 1..]),
+        None => (src, src),
+    };
+    let start = start.trim().parse().map_err(|_| err())?;
+    let end = end.trim().parse().map_err(|_| err())?;
+    if start > end {
+        return Err(err().into());
+    }
+    Ok(start..=end)
+}
+
+/// Splits src at commas outside of quotes and parentheses
+fn split_entries(src: &str) -> Vec<&str> {
+    let mut out = Vec::new();
+    let mut parenth = 0;
+    let mut quoted = false;
+    let mut start = 0;
+    for (i, c) in src.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '(' if !quoted => parenth += 1,
+            ')' if !quoted => parenth -= 1,
+            ',' if !quoted && parenth == 0 => {
+                out.push(&src[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    out.push(&src[start..]);
+    out
+}
+
+impl FromStr for Table {
+    type Err = DiceError;
+
+    /// Parses the inline form of a table, an optional roll followed by entries like 1-3: "goblin"
+    fn from_str(src: &str) -> Result<Self, Self::Err> {
+        let mut args = split_entries(src).into_iter().peekable();
+        let roll = args.next_if(|arg| !arg.contains(':'));
+
+        let entries = args
+            .map(|entry| {
+                let (range, txt) = entry
+                    .split_once(':')
+                    .ok_or_else(|| format!("table: entry {} lacks ':'", entry.trim()))?;
+                let txt = txt.trim();
+                let txt = txt
+                    .strip_prefix('"')
+                    .and_then(|txt| txt.strip_suffix('"'))
+                    .unwrap_or(txt);
+                Ok((parse_range(range)?, txt.to_owned()))
+            })
+            .collect::<Result<_, DiceError>>()?;
+
+        Table::new(roll, entries)
+    }
+}
+
+#[derive(Deserialize)]
+struct TableSource {
+    #[serde(default)]
+    roll: Option<String>,
+    /// Ranges of rolls mapped to entries, ranges may be written as numbers or as strings like "1-3"
+    entries: serde_yaml::Mapping,
+}
+
+impl TryFrom<TableSource> for Table {
+    type Error = DiceError;
+
+    fn try_from(source: TableSource) -> Result<Self, Self::Error> {
+        let entries = source
+            .entries
+            .into_iter()
+            .map(|(range, txt)| {
+                let range = match range {
+                    serde_yaml::Value::Number(num) => parse_range(&num.to_string())?,
+                    serde_yaml::Value::String(range) => parse_range(&range)?,
+                    _ => return Err("table: ranges must be numbers or strings like 1-3".into()),
+                };
+                let txt = match txt {
+                    serde_yaml::Value::String(txt) => txt,
+                    serde_yaml::Value::Number(num) => num.to_string(),
+                    _ => return Err("table: entries must be text".into()),
+                };
+                Ok((range, txt))
+            })
+            .collect::<Result<_, DiceError>>()?;
+
+        Table::new(source.roll.as_deref(), entries)
+    }
+}
+
+/// Makes the table available to table(name)
+pub fn register_table(name: &str, table: Table) {
+    TABLES
+        .write()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .insert(name.to_owned(), table);
+}
+
+/// Registers all tables in a YAML or JSON document, which maps table names to tables like
+/// `{"loot": {"roll": "d6", "entries": {"1-5": "nothing", "6": "a gem"}}}`, returning the names of the tables
+pub fn load_tables_from_str(src: &str) -> Result<Vec<String>, DiceError> {
+    // YAML is a superset of JSON, so this reads both
+    let sources: BTreeMap<String, TableSource> =
+        serde_yaml::from_str(src).map_err(|err| format!("table: {err}"))?;
+    let tables = sources
+        .into_iter()
+        .map(|(name, source)| Ok((name, Table::try_from(source)?)))
+        .collect::<Result<Vec<_>, DiceError>>()?;
+
+    Ok(tables
+        .into_iter()
+        .map(|(name, table)| {
+            register_table(&name, table);
+            name
+        })
+        .collect())
+}
+
+/// Registers all tables in a YAML or JSON file, see `load_tables_from_str`
+pub fn load_tables(path: impl AsRef<Path>) -> Result<Vec<String>, DiceError> {
+    let path = path.as_ref();
+    let src = fs::read_to_string(path)
+        .map_err(|err| format!("table: could not read {}: {err}", path.display()))?;
+    load_tables_from_str(&src)
+}
+
+impl FunctionInit for Table {
+    const DOC: &'static str = "Rolls on a random table, resulting in the roll with its entry shown in the text.\nEither uses a table loaded by name, such as from a file passed to doice with --tables, or one given inline with an optional roll, by default a die covering all entries.\nUsage: table(name) or table((roll), 1-3: \"entry\", 4: \"entry\", ...)";
+
+    fn generate(input: &str) -> Result<Expression, DiceError> {
+        let input = input.trim();
+        if input.contains(':') {
+            return Ok(input.parse::<Table>()?.into());
+        }
+
+        let tables = TABLES
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let table = tables
+            .get(input)
+            .ok_or_else(|| format!("table: no table named {input}"))?;
+        Ok(table.clone().into())
+    }
+}
+
+impl Rollable for Table {
+    fn roll(&self) -> RollOut {
+        let mut out = self.roll.roll();
+        out.txt.append(": ");
+        out.txt.append(self.entry(out.value).unwrap_or("no entry"));
+        out
+    }
+
+    fn dist(&self) -> ProbDist {
+        self.roll.dist()
+    }
+
+    fn roll_quiet(&self) -> Value {
+        self.roll.roll_quiet()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::load_tables_from_str;
+    use crate::{seed, EvalLimits, Roll, Rollable};
+
+    fn roll_txt(src: &str) -> (isize, String) {
+        let out = src.parse::<Roll>().unwrap().roll();
+        let txt = out.txt.sections.into_iter().map(|(txt, _)| txt).collect();
+        (out.value, txt)
+    }
+
+    #[test]
+    fn inline_table_test() {
+        seed(33);
+        for _ in 0..50 {
+            let (value, txt) = roll_txt("table(1-3: \"goblin, small\", 4-6: orc)");
+            let entry = if value <= 3 { "goblin, small" } else { "orc" };
+            assert!(txt.ends_with(entry), "{txt}");
+        }
+
+        let dist = "table(2d6, 2-6: miss, 7-12: hit)"
+            .parse::<Roll>()
+            .unwrap()
+            .dist();
+        assert!((dist.expectation() - 7.0).abs() < 1e-9);
+        assert!("table(3-1: backwards)".parse::<Roll>().is_err());
+
+        let limits = EvalLimits {
+            max_dice: 10,
+            ..Default::default()
+        };
+        assert!(Roll::parse_with_limits("table(20d6, 1-120: many)", limits).is_err());
+    }
+
+    #[test]
+    fn loaded_table_test() {
+        let names = load_tables_from_str(
+            r#"{"loot": {"roll": "d4", "entries": {"1-3": "nothing", "4": "a gem"}}}"#,
+        )
+        .unwrap();
+        assert_eq!(names, ["loot"]);
+        load_tables_from_str("surge:\n  entries:\n    1: fireball\n    2-3: nothing\n").unwrap();
+
+        let (value, txt) = roll_txt("table(loot)");
+        assert!(txt.ends_with(if value == 4 { "a gem" } else { "nothing" }));
+        assert_eq!("table(surge)".parse::<Roll>().unwrap().dist().len(), 3);
+        assert!("table(missing)".parse::<Roll>().is_err());
+    }
+}
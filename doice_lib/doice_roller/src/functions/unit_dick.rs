@@ -1,12 +1,14 @@
 use itertools::Itertools;
 
-use crate::{DiceError, Expression, ProbDist, RollOut, Rollable, SampleDist};
+use crate::{eval, DiceError, Expression, ProbDist, RollOut, Rollable, SampleDist};
 
 use super::FunctionInit;
 
 const GIRTH: isize = 20;
 const LENGTH: f64 = 5.0;
 const SCALE: f64 = 1000.0;
+/// Longer ones would overflow the sample counts
+const MAX_LENGTH: f64 = 1e9;
 
 #[derive(Clone, Default, Debug)]
 pub struct UnitDick {
@@ -19,14 +21,27 @@ impl FunctionInit for UnitDick {
 
     fn generate(input: &str) -> Result<Expression, DiceError> {
         if let Some((length, girth)) = input.split(',').collect_tuple() {
-            let length = length
+            let length: f64 = length
                 .trim()
                 .parse()
                 .map_err(|_| "could not parse length in dick")?;
-            let girth = girth
+            if !(0.0..=MAX_LENGTH).contains(&length) {
+                return Err(format!("length in dick must be 0 to {MAX_LENGTH}").into());
+            }
+            let girth: isize = girth
                 .trim()
                 .parse()
                 .map_err(|_| "could not parse girth in dick")?;
+            if girth < 1 {
+                return Err("girth in dick must be positive".into());
+            }
+            // The distribution is about four times as wide as it is girthy
+            let max_girth = eval::limits().max_faces / 4;
+            if girth as usize > max_girth {
+                return Err(DiceError::resource_limit(format!(
+                    "girth in dick may be at most {max_girth}"
+                )));
+            }
             let mut dist = SampleDist::default();
             generate_ball(&mut dist, -2 * girth + 1, girth);
             generate_phallus(&mut dist, girth, length);
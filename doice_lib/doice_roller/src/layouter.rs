@@ -1,19 +1,59 @@
-use std::ops::{Add, AddAssign};
+use std::{
+    fmt::Display,
+    ops::{Add, AddAssign},
+    sync::{PoisonError, RwLock},
+};
 
 use {
     egui::epaint::{text::LayoutJob, Color32, Stroke},
     egui::TextFormat,
 };
 
-pub const LINE: Stroke = Stroke {
-    width: 2.0,
-    color: Color32::RED,
-};
+const LINE_WIDTH: f32 = 2.0;
 
-pub const LINE_ORANGE: Stroke = Stroke {
-    width: 2.0,
-    color: Color32::GOLD,
-};
+static ACCENTS: RwLock<Accents> = RwLock::new(Accents::DEFAULT);
+
+/// Colors used to mark dice in the text of a roll, see `set_accents`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Accents {
+    /// Strikes through dice that were dropped, like the lowest die of stat()
+    pub strikethrough: Color32,
+    /// Strikes through dice that were rerolled, like the low dice of emp()
+    pub reroll: Color32,
+}
+
+impl Accents {
+    pub const DEFAULT: Accents = Accents {
+        strikethrough: Color32::RED,
+        reroll: Color32::GOLD,
+    };
+}
+
+impl Default for Accents {
+    fn default() -> Self {
+        Accents::DEFAULT
+    }
+}
+
+/// Sets the colors used in the text of all later rolls, on every thread
+pub fn set_accents(accents: Accents) {
+    *ACCENTS.write().unwrap_or_else(PoisonError::into_inner) = accents;
+}
+
+/// The colors currently used in the text of rolls
+pub fn accents() -> Accents {
+    *ACCENTS.read().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// The strikethrough of dropped dice
+pub(crate) fn line() -> Stroke {
+    Stroke::new(LINE_WIDTH, accents().strikethrough)
+}
+
+/// The strikethrough of rerolled dice
+pub(crate) fn reroll_line() -> Stroke {
+    Stroke::new(LINE_WIDTH, accents().reroll)
+}
 
 /// Makes it slightly easier to manipulate egui-compatible formatted text
 #[derive(Default, Clone)]
@@ -56,7 +96,7 @@ impl Layouter {
         self.sections.push((
             String::from(txt),
             TextFormat {
-                strikethrough: LINE,
+                strikethrough: line(),
                 ..Default::default()
             },
         ));
@@ -68,13 +108,20 @@ impl Layouter {
             (
                 String::from(txt),
                 TextFormat {
-                    strikethrough: LINE,
+                    strikethrough: line(),
                     ..Default::default()
                 },
             ),
         );
     }
 
+    /// Applies strikethrough to all text so far
+    pub fn strikethrough(&mut self) {
+        for (_, format) in &mut self.sections {
+            format.strikethrough = line();
+        }
+    }
+
     /// See `Vec::pop`
     pub fn pop(&mut self) -> Option<(String, TextFormat)> {
         self.sections.pop()
@@ -131,7 +178,7 @@ impl From<&str> for Layouter {
 impl From<String> for Layouter {
     fn from(value: String) -> Self {
         Layouter {
-            sections: vec![(value, TextFormat::default())]
+            sections: vec![(value, TextFormat::default())],
         }
     }
 }
@@ -145,3 +192,12 @@ impl From<Layouter> for LayoutJob {
         out
     }
 }
+
+impl Display for Layouter {
+    /// Writes the plain text, leaving out all formatting
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.sections
+            .iter()
+            .try_for_each(|(txt, _)| f.write_str(txt))
+    }
+}
@@ -7,10 +7,12 @@ use std::{
 
 /// Contains the logic for functional expressions, and the definitions of all functions with corresponding docs
 mod functions;
-pub use functions::FUNCTION_DOCS;
+pub use functions::{
+    load_tables, load_tables_from_str, register_table, Table, FUNCTION_DOCS,
+};
 /// Defines the `ProbDist` type
 mod prob_dist;
-pub use prob_dist::ProbDist;
+pub use prob_dist::{Approximation, ProbDist};
 /// Defines the `SampleDist` type
 mod sample_dist;
 pub use sample_dist::SampleDist;
@@ -19,19 +21,27 @@ mod structure;
 use structure::{expression::Expression, lin_comb::LinComb, nop::Nothing};
 /// Defines the `Layouter` type
 mod layouter;
-pub use layouter::Layouter;
-/// Contains the logic enabling the bruteforcing of probability distributions of rollable things
+pub use layouter::{accents, set_accents, Accents, Layouter};
+/// Contains the logic enabling the bruteforcing of probability distributions of rollable things, by adaptive Monte Carlo sampling
 mod bruteforce;
 use bruteforce::BruteForceProbDist;
+pub use bruteforce::{with_cancel_token, CancelToken, MonteCarlo, MonteCarloResult};
 /// Contains the logic for rolling dice
 mod dice_roller;
-pub use dice_roller::DiceRoller;
+pub use dice_roller::{DiceRoller, Faces};
 /// Defines the `DiceError` type
 mod dice_error;
-pub use dice_error::DiceError;
+pub use dice_error::{DiceError, ErrorKind};
 /// Contains and exposes old stuff, may be removed later
 pub mod legacy;
+/// Contains the random number generator used for rolling, which can be seeded for reproducible rolls
+mod rng;
+pub use rng::{seed, with_seed};
+/// Reports errors like overflow during evaluation, which `Rollable` cannot return, and holds the limits of evaluation
+mod eval;
+pub use eval::EvalLimits;
 mod utils;
+use utils::nesting_depth;
 
 #[cfg(test)]
 mod test;
@@ -44,16 +54,17 @@ pub type Value = isize;
 pub struct RollOut {
     pub value: Value,
     pub txt: Layouter,
+    /// The named values making up a multi-valued roll, like {fire: 3d6, cold: 2d6}, whose value is their sum.
+    /// Empty for rolls with a single value
+    pub components: Vec<(String, Value)>,
 }
 
 impl Add for RollOut {
     type Output = Self;
 
-    /// Adds the numeric, and appends the text fields
+    /// Adds the numeric, and appends the text fields and components
     fn add(mut self, rhs: RollOut) -> Self::Output {
-        self.txt.append(" ");
-        self.txt += rhs.txt;
-        self.value += rhs.value;
+        self += rhs;
         self
     }
 }
@@ -62,7 +73,8 @@ impl AddAssign for RollOut {
     fn add_assign(&mut self, rhs: Self) {
         self.txt.append(" ");
         self.txt += rhs.txt;
-        self.value += rhs.value;
+        self.value = eval::add(self.value, rhs.value);
+        self.components.extend(rhs.components);
     }
 }
 
@@ -78,6 +90,12 @@ pub trait Rollable: DynClone + Send + Sync + Debug {
     fn roll_quiet(&self) -> Value {
         self.roll().value
     }
+
+    /// Calculate the probability distributions of the components of a multi-valued rollable, matching `RollOut::components`.
+    /// Empty by default, for rollables with a single value
+    fn component_dists(&self) -> Vec<(String, ProbDist)> {
+        Vec::new()
+    }
 }
 
 /// Wrapper around Expression that exposes the public API
@@ -85,13 +103,39 @@ pub trait Rollable: DynClone + Send + Sync + Debug {
 pub struct Roll {
     /// The base of the tree
     root: Expression,
+    /// The limits the expression was parsed with, which also apply to rolling it and computing its distribution
+    limits: EvalLimits,
 }
 
 impl Roll {
     /// Instantiates a roll wrapping the provided expression
     #[must_use]
     pub fn from_expr(expr: Expression) -> Self {
-        Roll { root: expr }
+        Roll {
+            root: expr,
+            limits: EvalLimits::default(),
+        }
+    }
+
+    /// Attempts to parse the str into an expression, within the given limits rather than the default ones
+    pub fn parse_with_limits(src: &str, limits: EvalLimits) -> Result<Self, DiceError> {
+        if nesting_depth(src) > limits.max_depth {
+            return Err(DiceError::resource_limit(format!(
+                "Expression nested too deeply! At most {} levels are allowed",
+                limits.max_depth
+            )));
+        }
+        let root = eval::with_limits(limits, || LinComb::from_str(src))?;
+        Ok(Roll {
+            root: root.into(),
+            limits,
+        })
+    }
+
+    /// The limits that apply to the roll
+    #[must_use]
+    pub fn limits(&self) -> EvalLimits {
+        self.limits
     }
 
     /// Adds text to the roll, for display purposes
@@ -102,6 +146,23 @@ impl Roll {
             txt: txt.into(),
         }
     }
+
+    /// Rolls the expression, failing on errors like division by zero or overflow,
+    /// where `Rollable::roll` carries on with a fallback value
+    pub fn try_roll(&self) -> Result<RollOut, DiceError> {
+        eval::checked(|| self.roll())
+    }
+
+    /// Rolls the expression quietly, failing like `Roll::try_roll`
+    pub fn try_roll_quiet(&self) -> Result<Value, DiceError> {
+        eval::checked(|| self.roll_quiet())
+    }
+
+    /// Obtains the probability distribution of the expression, failing like `Roll::try_roll`
+    /// if any possible outcome runs into such an error
+    pub fn try_dist(&self) -> Result<ProbDist, DiceError> {
+        eval::checked(|| self.dist())
+    }
 }
 
 impl Add<isize> for Roll {
@@ -115,6 +176,7 @@ impl Add<isize> for Roll {
         new_root.add_term(rhs.into());
         Roll {
             root: new_root.into(),
+            limits: self.limits,
         }
     }
 }
@@ -122,11 +184,9 @@ impl Add<isize> for Roll {
 impl FromStr for Roll {
     type Err = DiceError;
 
-    /// Attempts to parse the str into an expression
+    /// Attempts to parse the str into an expression, within the default limits
     fn from_str(src: &str) -> Result<Self, Self::Err> {
-        Ok(Roll {
-            root: LinComb::from_str(src)?.into(),
-        })
+        Self::parse_with_limits(src, EvalLimits::default())
     }
 }
 
@@ -148,28 +208,30 @@ impl From<TextRoll> for Roll {
 impl Rollable for Roll {
     /// Rolls the expression
     fn roll(&self) -> RollOut {
-        self.root.roll()
+        eval::with_limits(self.limits, || self.root.roll())
     }
 
     /// Obtains and sanitizes the probability distribution of the expression
     fn dist(&self) -> ProbDist {
-        let mut dist = self.root.dist();
+        let mut dist = eval::with_limits(self.limits, || self.root.dist());
         dist.remove_null();
         dist
     }
 
     /// Rolls the expression, quietly
     fn roll_quiet(&self) -> isize {
-        self.root.roll_quiet()
+        eval::with_limits(self.limits, || self.root.roll_quiet())
+    }
+
+    fn component_dists(&self) -> Vec<(String, ProbDist)> {
+        eval::with_limits(self.limits, || self.root.component_dists())
     }
 }
 
 impl Default for Roll {
     /// Instantiates an empty roll that always returns 0
     fn default() -> Self {
-        Roll {
-            root: Nothing::new().into(),
-        }
+        Roll::from_expr(Nothing::new().into())
     }
 }
 
@@ -204,7 +266,10 @@ impl Add<isize> for TextRoll {
         let mut new_root = LinComb::from(expr);
         new_root.add_term(rhs.into());
         TextRoll {
-            roll: Roll::from_expr(new_root.into()),
+            roll: Roll {
+                root: new_root.into(),
+                limits: self.roll.limits,
+            },
             txt: self.txt,
         }
     }
@@ -234,18 +299,20 @@ impl TryFrom<&str> for TextRoll {
 impl Rollable for TextRoll {
     /// Rolls the expression
     fn roll(&self) -> RollOut {
-        self.root.roll()
+        self.roll.roll()
     }
 
     /// Obtains and sanitizes the probability distribution of the expression
     fn dist(&self) -> ProbDist {
-        let mut dist = self.root.dist();
-        dist.remove_null();
-        dist
+        self.roll.dist()
     }
 
     /// Rolls the expression, quietly
     fn roll_quiet(&self) -> isize {
-        self.root.roll_quiet()
+        self.roll.roll_quiet()
+    }
+
+    fn component_dists(&self) -> Vec<(String, ProbDist)> {
+        self.roll.component_dists()
     }
 }
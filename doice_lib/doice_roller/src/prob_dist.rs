@@ -1,31 +1,72 @@
 use std::{
     collections::BTreeMap,
-    ops::{Add, Deref, Div, Mul, Neg, Range},
-    time::Duration,
+    ops::{Add, Deref, Div, Mul, Neg, Range, Rem},
 };
 
 use instant::Instant;
-use rand::random;
+use itertools::Itertools;
+use rand::Rng;
 
 #[cfg(feature = "rayon")]
-use rayon::prelude::*;
+use {
+    rayon::prelude::*,
+    std::sync::atomic::{AtomicBool, Ordering},
+};
 
-use crate::{DiceError, RollOut, Value};
+use crate::{
+    eval,
+    rng::with_rng,
+    utils::{floor_div, floor_rem, int_pow},
+    DiceError, RollOut, Value,
+};
 
 use super::{Roll, Rollable, SampleDist};
 
-const ADD_TIMEOUT: u64 = 2000; //[ms]
 const TIMEOUT_CHECK_INTERVAL: usize = 20_000; //[iterations]
-/// Will approximate a summation with more than these terms using the Central Limit Theorem (CLT)
-const CLT_THRESHOLD: usize = 1000; // [terms]
+
+/// Summary of the accuracy of a distribution that was approximated by sampling
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Approximation {
+    /// Number of samples the approximation is based on
+    pub samples: usize,
+    /// Largest estimated standard error of the probability of any single outcome
+    pub max_error: f64,
+    /// Estimated standard error of the expectation
+    pub mean_error: f64,
+}
+
+impl Approximation {
+    /// The accuracy of a distribution derived from two others, which is only exact if both of them are
+    #[must_use]
+    pub fn combine(lhs: Option<Self>, rhs: Option<Self>) -> Option<Self> {
+        match (lhs, rhs) {
+            (Some(lhs), Some(rhs)) => Some(Approximation {
+                samples: lhs.samples.min(rhs.samples),
+                max_error: lhs.max_error.max(rhs.max_error),
+                mean_error: lhs.mean_error.hypot(rhs.mean_error),
+            }),
+            (approx, None) | (None, approx) => approx,
+        }
+    }
+
+    /// The accuracy of a distribution derived from all of dists
+    #[must_use]
+    pub fn of_all<'a>(dists: impl IntoIterator<Item = &'a ProbDist>) -> Option<Self> {
+        dists
+            .into_iter()
+            .map(ProbDist::approximation)
+            .fold(None, Approximation::combine)
+    }
+}
 
 /// Type representing a valid probability mass function
+/// If it was approximated by sampling instead of computed exactly, it carries an `Approximation`
 #[derive(Clone, Debug)]
-pub struct ProbDist(BTreeMap<Value, f64>);
+pub struct ProbDist(BTreeMap<Value, f64>, Option<Approximation>);
 
 impl Rollable for ProbDist {
     fn roll(&self) -> RollOut {
-        let roll: usize = random();
+        let roll: usize = with_rng(|rng| rng.gen());
         let mut roll: f64 = roll as f64 / usize::MAX as f64;
         let mut final_outcome = 0;
         for (outcome, prob) in self.iter() {
@@ -39,6 +80,7 @@ impl Rollable for ProbDist {
         RollOut {
             value: final_outcome,
             txt: final_outcome.to_string().into(),
+            ..Default::default()
         }
     }
 
@@ -58,6 +100,16 @@ impl ProbDist {
         self.0
     }
 
+    /// The accuracy of the distribution if it was approximated by sampling, None if it is exact
+    #[must_use]
+    pub fn approximation(&self) -> Option<Approximation> {
+        self.1
+    }
+
+    pub fn set_approximation(&mut self, approx: Option<Approximation>) {
+        self.1 = approx;
+    }
+
     /// Generates a normal distribution for the given range of outcomes
     #[must_use]
     pub fn normal(mean: f64, variance: f64, range: Range<Value>) -> Self {
@@ -71,7 +123,7 @@ impl ProbDist {
             );
         }
 
-        let mut out = ProbDist(out);
+        let mut out = ProbDist(out, None);
         out.proper_scale();
         out
     }
@@ -109,12 +161,12 @@ impl ProbDist {
         self.moment(1)
     }
 
-    /// Equal to E(X^n)
+    /// Equal to E(X^n), computed in floats as X^n easily overflows
     #[must_use]
     pub fn moment(&self, n: u32) -> f64 {
         self.0
             .iter()
-            .map(|(outcome, prob)| outcome.pow(n) as f64 * prob)
+            .map(|(&outcome, prob)| (outcome as f64).powi(n as i32) * prob)
             .sum()
     }
 
@@ -170,6 +222,9 @@ impl ProbDist {
     }
 
     fn apply_disadvantage(&mut self, disadv: usize) {
+        if self.is_empty() {
+            return;
+        }
         for _ in 0..disadv {
             // Initialization
             let cumul_dist = self.get_cumulative_prob(); // = P(X <= x)
@@ -193,6 +248,9 @@ impl ProbDist {
     }
 
     fn apply_positive_advantage(&mut self, adv: usize) {
+        if self.is_empty() {
+            return;
+        }
         for _ in 0..adv {
             let mut total_above = 0.0;
             let mut next = BTreeMap::new();
@@ -217,7 +275,7 @@ impl ProbDist {
             }
 
             // Next should now contain the probability weight function with advantage applied
-            *self = ProbDist(next);
+            self.0 = next;
         }
     }
 
@@ -266,43 +324,114 @@ impl ProbDist {
             }
         }
 
-        ProbDist(out)
+        ProbDist(out, Approximation::combine(self.1, rhs.1))
     }
 
-    /// Ignores negative numbers for now
+    /// Ignores negative numbers for now.
+    /// Errors reported while convoluting, like exceeding the limits, are reported again on this thread
     pub fn rep_auto_convolution(&self, rep: &ProbDist) -> Result<ProbDist, DiceError> {
-        Ok(rep
+        // The limits of this thread have to be passed on to the threads doing the convoluting
+        let limits = eval::limits();
+        let parts = rep
             .par_iter()
             .filter(|(outcome, _)| outcome.is_positive())
             .map(|(outcome, prob)| {
-                // Create an autoconvoluted version of yourself
-                let mut tmp = self.clone() * (*outcome as usize);
-                // Then scale it based on the probability of this multiplicity occurring
-                for val in tmp.0.values_mut() {
-                    *val *= *prob;
-                }
-                tmp
+                eval::with_limits(limits, || {
+                    eval::checked(|| {
+                        // Create an autoconvoluted version of yourself
+                        let mut tmp = self.clone() * (*outcome as usize);
+                        // Then scale it based on the probability of this multiplicity occurring
+                        for val in tmp.0.values_mut() {
+                            *val *= *prob;
+                        }
+                        tmp
+                    })
+                })
             })
-            .reduce(
-                || ProbDist(BTreeMap::new()),
-                |mut a, b| {
-                    // Then fold all maps back into one
-                    // By adding all data from b to a
-                    for (k, v) in b.iter() {
-                        a.0.entry(*k).and_modify(|p| *p += v).or_insert(*v);
-                    }
-                    a
-                },
-            ))
+            .collect::<Result<Vec<_>, _>>()
+            .inspect_err(|err| eval::report(err.clone()))?;
+
+        // Then fold all maps back into one
+        let mut out = ProbDist(BTreeMap::new(), None);
+        for part in parts {
+            for (k, v) in part.iter() {
+                out.0.entry(*k).and_modify(|p| *p += v).or_insert(*v);
+            }
+            out.1 = Approximation::combine(out.1, part.1);
+        }
+        out.1 = Approximation::combine(out.1, rep.1);
+        Ok(out)
     }
 
+    /// Mixes the distributions produced by the generator for every outcome of param_dist,
+    /// weighing each by the probability of that outcome
     pub fn from_parameter_distribution(
         param_dist: &ProbDist,
-        _generator: impl FnMut(isize) -> ProbDist,
+        mut generator: impl FnMut(isize) -> ProbDist,
     ) -> Self {
-        let _acc = BTreeMap::<isize, f64>::new();
-        for (_param, _scale) in param_dist.iter() {}
-        todo!("There must be some better way to do all these BTreeMap transformations, I'm getting tired of them.")
+        let mut out = Self::mixture(
+            param_dist
+                .iter()
+                .map(|(&param, &scale)| (scale, generator(param))),
+        );
+        out.1 = Approximation::combine(out.1, param_dist.1);
+        out
+    }
+
+    /// Mixes the given distributions, weighing each by the probability that comes with it
+    pub fn mixture(parts: impl IntoIterator<Item = (f64, ProbDist)>) -> Self {
+        let mut acc = BTreeMap::<isize, f64>::new();
+        let mut approx = None;
+        for (scale, dist) in parts {
+            approx = Approximation::combine(approx, dist.1);
+            for (outcome, prob) in dist.0 {
+                *acc.entry(outcome).or_insert(0.0) += prob * scale;
+            }
+        }
+        ProbDist(acc, approx)
+    }
+
+    /// Distribution that always produces the given outcome
+    #[must_use]
+    pub fn constant(outcome: Value) -> Self {
+        ProbDist(BTreeMap::from([(outcome, 1.0)]), None)
+    }
+
+    /// Applies f to every outcome, merging outcomes that map to the same value
+    #[must_use]
+    pub fn map_outcomes(&self, mut f: impl FnMut(Value) -> Value) -> Self {
+        let mut out = BTreeMap::new();
+        for (&outcome, &prob) in self.iter() {
+            *out.entry(f(outcome)).or_insert(0.0) += prob;
+        }
+        ProbDist(out, self.1)
+    }
+
+    /// Applies f to every pair of outcomes of self and rhs, which are assumed independent
+    #[must_use]
+    pub fn map_pairs(&self, rhs: &Self, mut f: impl FnMut(Value, Value) -> Value) -> Self {
+        if exceeds_pairs(self, rhs) {
+            return ProbDist::default();
+        }
+        let time_budget = eval::limits().time_budget;
+        let timestamp = Instant::now();
+        let mut out = BTreeMap::new();
+        for (&lhs_outcome, &lhs_prob) in self.iter() {
+            for (i, (&rhs_outcome, &rhs_prob)) in rhs.iter().enumerate() {
+                *out.entry(f(lhs_outcome, rhs_outcome)).or_insert(0.0) += lhs_prob * rhs_prob;
+                if i % TIMEOUT_CHECK_INTERVAL == 0 && timestamp.elapsed() > time_budget {
+                    report_timeout();
+                    return ProbDist::default();
+                }
+            }
+        }
+        limit_support(ProbDist(out, Approximation::combine(self.1, rhs.1)))
+    }
+
+    /// Distribution of self raised to the power rhs, see `int_pow`
+    #[must_use]
+    pub fn pow(&self, rhs: &Self) -> Self {
+        self.map_pairs(rhs, int_pow)
     }
 }
 
@@ -310,7 +439,7 @@ impl Default for ProbDist {
     fn default() -> Self {
         let mut out = BTreeMap::new();
         out.insert(0, 1.0);
-        ProbDist(out)
+        ProbDist(out, None)
     }
 }
 
@@ -338,23 +467,23 @@ impl Add<&Self> for ProbDist {
             (&self, rhs)
         };
 
+        check_extremes(&self, rhs, Value::checked_add);
+        let time_budget = eval::limits().time_budget;
         let timestamp = Instant::now();
         let mut out = BTreeMap::new();
         for (outcome, prob) in shortest.iter() {
             for (i, (k, v)) in longest.iter().enumerate() {
-                out.entry(outcome + k)
+                out.entry(outcome.saturating_add(*k))
                     .and_modify(|e| *e += prob * v)
                     .or_insert(prob * v);
-                if i % TIMEOUT_CHECK_INTERVAL == 0
-                    && timestamp.elapsed() > Duration::from_millis(5 * ADD_TIMEOUT)
-                {
-                    //println!("ProbDist::Add<&Self> timed out!");
+                if i % TIMEOUT_CHECK_INTERVAL == 0 && timestamp.elapsed() > time_budget {
+                    report_timeout();
                     return ProbDist::default();
                 }
             }
         }
 
-        ProbDist(out)
+        limit_support(ProbDist(out, Approximation::combine(self.1, rhs.1)))
     }
 }
 
@@ -364,9 +493,9 @@ impl Add<isize> for ProbDist {
     fn add(self, rhs: Value) -> Self::Output {
         let mut out = BTreeMap::new();
         for (k, v) in self.iter() {
-            out.insert(k + rhs, *v);
+            out.insert(eval::add(*k, rhs), *v);
         }
-        ProbDist(out)
+        ProbDist(out, self.1)
     }
 }
 
@@ -375,20 +504,32 @@ impl Mul<&Self> for ProbDist {
     type Output = Self;
 
     fn mul(self, rhs: &Self) -> Self::Output {
+        check_extremes(&self, rhs, Value::checked_mul);
+        if exceeds_pairs(&self, rhs) {
+            return ProbDist::default();
+        }
         let (shortest, longest) = if self.len() > rhs.len() {
             (rhs, &self)
         } else {
             (&self, rhs)
         };
 
+        // The limits are thread-local, so the workers check the budget of this thread
+        let time_budget = eval::limits().time_budget;
+        let timestamp = Instant::now();
+        let timed_out = AtomicBool::new(false);
         //let mut out = BTreeMap::new();
         let out = shortest
             .par_iter()
             .map(|(&outcome, &prob)| {
+                if timed_out.load(Ordering::Relaxed) || timestamp.elapsed() > time_budget {
+                    timed_out.store(true, Ordering::Relaxed);
+                    return BTreeMap::new();
+                }
                 // Multiply every entry in shortest with the longest map
                 longest
                     .par_iter()
-                    .map(move |(k, v)| (k * outcome, prob * v))
+                    .map(move |(k, v)| (k.saturating_mul(outcome), prob * v))
                     .collect()
             })
             .reduce(BTreeMap::new, |mut a, b| {
@@ -411,8 +552,12 @@ impl Mul<&Self> for ProbDist {
         //             .or_insert(prob * v);
         //     }
         // }
+        if timed_out.into_inner() {
+            report_timeout();
+            return ProbDist::default();
+        }
 
-        ProbDist(out)
+        limit_support(ProbDist(out, Approximation::combine(self.1, rhs.1)))
     }
 }
 
@@ -422,48 +567,119 @@ impl Mul<&Self> for ProbDist {
     type Output = Self;
 
     fn mul(self, rhs: &Self) -> Self::Output {
+        check_extremes(&self, rhs, Value::checked_mul);
+        if exceeds_pairs(&self, rhs) {
+            return ProbDist::default();
+        }
         let (shortest, longest) = if self.len() > rhs.len() {
             (rhs, &self)
         } else {
             (&self, rhs)
         };
 
+        let time_budget = eval::limits().time_budget;
+        let timestamp = Instant::now();
         let mut out = BTreeMap::new();
         for (outcome, prob) in shortest.iter() {
             // For every entry in shortest, add a scaled version of longes to the output
-            for (k, v) in longest.iter() {
-                out.entry(outcome * k)
+            for (i, (k, v)) in longest.iter().enumerate() {
+                out.entry(outcome.saturating_mul(*k))
                     .and_modify(|p| *p += prob * v)
                     .or_insert(prob * v);
+                if i % TIMEOUT_CHECK_INTERVAL == 0 && timestamp.elapsed() > time_budget {
+                    report_timeout();
+                    return ProbDist::default();
+                }
             }
         }
 
-        ProbDist(out)
+        limit_support(ProbDist(out, Approximation::combine(self.1, rhs.1)))
+    }
+}
+
+/// Reports an overflow if combining the extreme outcomes of lhs and rhs with f overflows.
+/// For addition and multiplication, any overflow happens at the extremes
+fn check_extremes(lhs: &ProbDist, rhs: &ProbDist, f: fn(Value, Value) -> Option<Value>) {
+    let extremes = |dist: &ProbDist| [dist.min(), dist.max()].into_iter().flatten();
+    let overflows = extremes(lhs)
+        .cartesian_product(extremes(rhs).collect_vec())
+        .any(|(lhs, rhs)| f(lhs, rhs).is_none());
+    if overflows {
+        eval::report(DiceError::overflow());
     }
 }
 
+/// Reports whether a distribution with this many outcomes exceeds the limits
+fn exceeds_support(len: usize) -> bool {
+    let max_support = eval::limits().max_support;
+    let exceeds = len > max_support;
+    if exceeds {
+        eval::report(DiceError::resource_limit(format!(
+            "the distribution has more than {max_support} outcomes"
+        )));
+    }
+    exceeds
+}
+
+/// Reports whether combining every outcome of lhs with every outcome of rhs exceeds the limits,
+/// so that it can be given up on before going through all the pairs
+fn exceeds_pairs(lhs: &ProbDist, rhs: &ProbDist) -> bool {
+    let max_support = eval::limits().max_support;
+    let exceeds = lhs.len().saturating_mul(rhs.len()) > max_support;
+    if exceeds {
+        eval::report(DiceError::resource_limit(format!(
+            "combining the distributions takes more than {max_support} pairs of outcomes"
+        )));
+    }
+    exceeds
+}
+
+/// Reports that computing a distribution took longer than the time budget
+fn report_timeout() {
+    eval::report(DiceError::resource_limit(
+        "computing the distribution took too long",
+    ));
+}
+
+/// Gives up on a distribution with more outcomes than the limits allow
+fn limit_support(dist: ProbDist) -> ProbDist {
+    if exceeds_support(dist.len()) {
+        return ProbDist::default();
+    }
+    dist
+}
+
 fn approx_as_norm(dist: &ProbDist, rhs: usize) -> ProbDist {
+    let (Some(min), Some(max)) = (dist.min(), dist.max()) else {
+        return ProbDist(BTreeMap::new(), dist.1);
+    };
     let new_mean = rhs as f64 * dist.expectation();
     let new_variance = rhs as f64 * dist.var();
     let new_sigma = new_variance.sqrt();
-    let min = rhs as isize * dist.0.keys().next().unwrap();
-    let max = rhs as isize * dist.0.keys().next_back().unwrap();
+    let min = eval::mul(rhs as isize, min);
+    let max = eval::mul(rhs as isize, max);
     // Find the smallest appropriate range
     let range = if (min.abs_diff(max) as f64) < 8.0 * new_sigma {
-        min..(max + 1)
+        min..max.saturating_add(1)
     } else {
         ((new_mean - 4.0 * new_sigma).floor() as isize)
             ..((new_mean + 4.0 * new_sigma).ceil() as isize)
     };
+    if exceeds_support(range.start.abs_diff(range.end)) {
+        return ProbDist::default();
+    }
 
-    ProbDist::normal(new_mean, new_variance, range)
+    let mut out = ProbDist::normal(new_mean, new_variance, range);
+    out.1 = dist.1;
+    out
 }
 
 impl Mul<usize> for ProbDist {
     type Output = Self;
 
     fn mul(self, rhs: usize) -> Self::Output {
-        if rhs > CLT_THRESHOLD {
+        let limits = eval::limits();
+        if rhs > limits.clt_threshold {
             return approx_as_norm(&self, rhs);
         }
 
@@ -473,7 +689,7 @@ impl Mul<usize> for ProbDist {
         for _count in 0..rhs {
             out = out + &self;
             // If time is running out, approximate as normal dist
-            if timestamp.elapsed() > Duration::from_millis(ADD_TIMEOUT) {
+            if timestamp.elapsed() > limits.time_budget / 5 {
                 return approx_as_norm(&self, rhs);
             }
         }
@@ -482,27 +698,21 @@ impl Mul<usize> for ProbDist {
     }
 }
 
+/// Floor division, where division by zero results in 0
 impl Div<&Self> for ProbDist {
     type Output = Self;
 
     fn div(self, rhs: &Self) -> Self::Output {
-        let (shortest, longest) = if self.len() > rhs.len() {
-            (rhs, &self)
-        } else {
-            (&self, rhs)
-        };
+        self.map_pairs(rhs, floor_div)
+    }
+}
 
-        let mut out = BTreeMap::new();
-        for (outcome, prob) in shortest.iter().filter(|(outcome, _)| **outcome != 0) {
-            // For every entry in shortest, add a scaled version of longes to the output
-            for (k, v) in longest.iter() {
-                out.entry(k / outcome)
-                    .and_modify(|p| *p += prob * v)
-                    .or_insert(prob * v);
-            }
-        }
+/// Remainder of floor division, with the sign of rhs
+impl Rem<&Self> for ProbDist {
+    type Output = Self;
 
-        ProbDist(out)
+    fn rem(self, rhs: &Self) -> Self::Output {
+        self.map_pairs(rhs, floor_rem)
     }
 }
 
@@ -510,7 +720,10 @@ impl Neg for ProbDist {
     type Output = Self;
 
     fn neg(self) -> Self::Output {
-        ProbDist(self.iter().map(|(k, v)| (-k, *v)).collect())
+        ProbDist(
+            self.iter().map(|(k, v)| (eval::neg(*k), *v)).collect(),
+            self.1,
+        )
     }
 }
 
@@ -522,7 +735,7 @@ impl TryFrom<BTreeMap<Value, f64>> for ProbDist {
         let total: f64 = data.values().copied().sum();
         // If it's close enough to 1, declare it a valid ProbDist
         if (total - 1.0).abs() <= 0.01 {
-            Ok(ProbDist(data))
+            Ok(ProbDist(data, None))
         } else {
             Err(())
         }
@@ -0,0 +1,40 @@
+use std::cell::RefCell;
+
+use rand::{rngs::StdRng, SeedableRng};
+
+thread_local! {
+    /// The generator used for all rolls on this thread
+    static RNG: RefCell<StdRng> = RefCell::new(StdRng::from_entropy());
+}
+
+/// Reseeds the random number generator of the current thread, making subsequent rolls on it reproducible.
+/// Rolls on other threads, such as the ones used to bruteforce distributions, are not affected.
+pub fn seed(seed: u64) {
+    RNG.with(|rng| *rng.borrow_mut() = StdRng::seed_from_u64(seed));
+}
+
+/// Runs f with the random number generator of the current thread seeded like `seed`, making only the rolls of f reproducible.
+/// The previous generator is put back afterwards, even if f panics
+pub fn with_seed<T>(seed: u64, f: impl FnOnce() -> T) -> T {
+    /// Puts back the generator it holds when dropped
+    struct Restore(Option<StdRng>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            if let Some(prev) = self.0.take() {
+                RNG.with(|rng| *rng.borrow_mut() = prev);
+            }
+        }
+    }
+
+    let _restore = Restore(Some(
+        RNG.with(|rng| rng.replace(StdRng::seed_from_u64(seed))),
+    ));
+    f()
+}
+
+/// Runs f with the random number generator of the current thread.
+/// f must not roll anything itself, as the generator is borrowed for the duration of the call.
+pub(crate) fn with_rng<T>(f: impl FnOnce(&mut StdRng) -> T) -> T {
+    RNG.with(|rng| f(&mut rng.borrow_mut()))
+}
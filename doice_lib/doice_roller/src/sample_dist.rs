@@ -3,9 +3,9 @@ use std::{
     ops::{Deref, DerefMut},
 };
 
-use rand::{thread_rng, Rng};
+use rand::Rng;
 
-use crate::Value;
+use crate::{rng::with_rng, Value};
 
 use super::{Layouter, ProbDist, Rollable};
 
@@ -56,8 +56,12 @@ impl DerefMut for SampleDist {
 impl Rollable for SampleDist {
     fn roll(&self) -> super::RollOut {
         let total = self.iter().map(|(_, s)| *s).sum();
-        let mut raw_roll = thread_rng().gen_range(1..total);
-        let mut out_roll = *self.first_key_value().unwrap().0;
+        // Without samples, there is nothing to roll but 0
+        let Some((&first, _)) = self.first_key_value() else {
+            return super::RollOut::default();
+        };
+        let mut raw_roll = with_rng(|rng| rng.gen_range(0..total));
+        let mut out_roll = first;
         for (&outcome, &samples) in self.iter() {
             if raw_roll < samples {
                 out_roll = outcome;
@@ -72,6 +76,7 @@ impl Rollable for SampleDist {
         super::RollOut {
             value: out_roll,
             txt: out_txt,
+            ..Default::default()
         }
     }
 
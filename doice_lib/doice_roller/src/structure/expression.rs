@@ -1,6 +1,10 @@
 use std::{fmt::Debug, str::FromStr};
 
-use crate::{dice_roller::DiceRoller, functions::interpret_function, DiceError, Rollable};
+use crate::{
+    dice_roller::DiceRoller,
+    functions::{interpret_function, Group},
+    DiceError, Rollable,
+};
 
 use super::{literal::Literal, nop::Nothing, parenth::Parenth};
 
@@ -10,6 +14,11 @@ impl FromStr for Expression {
     type Err = DiceError;
 
     fn from_str(src: &str) -> Result<Self, Self::Err> {
+        // Braces enclose a list of expressions, which may be followed by a modifier making it a group
+        if src.starts_with('{') {
+            return Group::from_braces(src);
+        }
+
         // If there are parentheses, the expression must either be a function, or just some stuff in parentheses
         if let Some(i) = src.find('(') {
             // Guard to detect unclosed parentheses
@@ -33,6 +42,10 @@ impl FromStr for Expression {
         // If it is not a dice roll, it must be a literal
         let lower = src.to_lowercase();
         if lower.contains('d') && !lower.contains("0x") {
+            // Dice followed by a modifier, like 4d6kh3, form a group of single dice
+            if let Some(group) = Group::from_dice(src) {
+                return group;
+            }
             Ok(DiceRoller::from_str(src)?.into())
         } else {
             Ok(Literal::from_str(src)?.into())
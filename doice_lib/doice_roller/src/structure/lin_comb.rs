@@ -1,7 +1,7 @@
 use itertools::Itertools;
 use std::str::FromStr;
 
-use crate::{prob_dist::ProbDist, DiceError, Expression, RollOut, Rollable};
+use crate::{eval, prob_dist::ProbDist, DiceError, Expression, RollOut, Rollable};
 
 use super::term::{Sign, Term};
 
@@ -90,12 +90,12 @@ impl FromStr for LinComb {
             .char_indices()
             // Filter out parenthesised stuff
             .filter(|(_, c)| {
-                if *c == ')' {
+                if [')', '}'].contains(c) {
                     parenth -= 1;
                     parenth = parenth.max(0);
                 }
                 let par_out = parenth;
-                if *c == '(' {
+                if ['(', '{'].contains(c) {
                     parenth += 1;
                 }
                 par_out == 0
@@ -107,7 +107,8 @@ impl FromStr for LinComb {
                 let last = iter
                     .take_while(|(_, c)| {
                         let take = first || !(['+', '-'].contains(c) && term_fin);
-                        term_fin = !['/', '*'].contains(c);
+                        // A sign right after an operator or comparison belongs to the number after it
+                        term_fin = !['/', '*', '%', '^', '<', '>', '='].contains(c);
                         first = false;
                         take
                     })
@@ -115,18 +116,14 @@ impl FromStr for LinComb {
                 first = true;
                 last
             })
-            .map(|(i, _)| i as isize);
+            // The end of a term is right after its last character
+            .map(|(i, c)| i + c.len_utf8());
 
-        let terms: Vec<_> = [-1]
-            .iter()
-            .copied()
+        let terms: Vec<_> = [0]
+            .into_iter()
             .chain(term_ends)
             .tuple_windows()
-            // start is exclusive, end inclusive
-            .map(|(start, end)| {
-                let s = &src[((start + 1) as usize)..=(end as usize)];
-                s.parse()
-            })
+            .map(|(start, end)| src[start..end].parse())
             .collect::<Result<_, _>>()?;
 
         // let (mut stripped, parenth) = strip_parenth(src);
@@ -155,7 +152,8 @@ impl Rollable for LinComb {
         for term in &self.terms {
             // And add their texts together
             let res = term.roll();
-            out.value += res.value;
+            out.value = eval::add(out.value, res.value);
+            out.components.extend(res.components);
             if let Sign::Positive = term.sign() {
                 out.txt = out.txt + term.sign().as_str() + res.txt;
             } else {
@@ -178,7 +176,11 @@ impl Rollable for LinComb {
     }
 
     fn roll_quiet(&self) -> isize {
-        self.terms.iter().map(Rollable::roll_quiet).sum()
+        eval::sum(self.terms.iter().map(Rollable::roll_quiet))
+    }
+
+    fn component_dists(&self) -> Vec<(String, ProbDist)> {
+        self.terms.iter().flat_map(Rollable::component_dists).collect()
     }
 }
 
@@ -1,4 +1,4 @@
-use std::{collections::BTreeMap, str::FromStr};
+use std::str::FromStr;
 
 use crate::{layouter::Layouter, prob_dist::ProbDist, DiceError, RollOut, Rollable};
 
@@ -20,6 +20,7 @@ impl Rollable for Literal {
         RollOut {
             value: self.value,
             txt: out,
+            ..Default::default()
         }
     }
 
@@ -28,7 +29,7 @@ impl Rollable for Literal {
     }
 
     fn dist(&self) -> ProbDist {
-        ProbDist::try_from(BTreeMap::from([(self.value, 1.0f64)])).expect("Literally Terrible!")
+        ProbDist::constant(self.value)
     }
 }
 
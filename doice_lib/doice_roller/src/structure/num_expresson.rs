@@ -22,6 +22,18 @@ impl NumericExpression {
             NumericExpression::Stochastic(exp) => exp.roll_quiet() as f64,
         }
     }
+
+    /// All values the expression can take, together with their probabilities
+    pub fn values(&self) -> Vec<(f64, f64)> {
+        match self {
+            NumericExpression::Constant(val) => vec![(*val, 1.0)],
+            NumericExpression::Stochastic(exp) => exp
+                .dist()
+                .iter()
+                .map(|(&val, &prob)| (val as f64, prob))
+                .collect(),
+        }
+    }
 }
 
 impl FromStr for NumericExpression {
@@ -13,7 +13,11 @@ impl FromStr for Parenth {
     type Err = DiceError;
 
     fn from_str(src: &str) -> Result<Self, Self::Err> {
-        let contents = &src[1..(src.len() - 1)];
+        let contents = src
+            .trim()
+            .strip_prefix('(')
+            .and_then(|src| src.strip_suffix(')'))
+            .ok_or("Improper parentheses! [no ')' at the end]")?;
         Ok(Parenth {
             expr: LinComb::from_str(contents)?.into(),
         })
@@ -35,4 +39,8 @@ impl Rollable for Parenth {
     fn dist(&self) -> ProbDist {
         self.expr.dist()
     }
+
+    fn component_dists(&self) -> Vec<(String, ProbDist)> {
+        self.expr.component_dists()
+    }
 }
@@ -1,7 +1,11 @@
 use std::str::FromStr;
 
 use crate::{
-    layouter::Layouter, prob_dist::ProbDist, DiceError, Expression, RollOut, Rollable, Value,
+    eval,
+    layouter::Layouter,
+    prob_dist::ProbDist,
+    utils::{floor_div, floor_rem, int_pow},
+    DiceError, Expression, RollOut, Rollable, Value,
 };
 
 use super::{literal::Literal, nop::Nothing};
@@ -30,10 +34,89 @@ impl From<Sign> for &str {
 #[derive(Clone, Debug)]
 enum Operator {
     Mul(Box<Term>),
+    /// Division rounding down
     Div(Box<Term>),
+    /// Remainder of division rounding down
+    Mod(Box<Term>),
+    Pow(Box<Term>),
     Nop(),
 }
 
+impl Operator {
+    fn new(symbol: char, rhs: Term) -> Self {
+        let rhs = Box::new(rhs);
+        match symbol {
+            '*' => Operator::Mul(rhs),
+            '/' => Operator::Div(rhs),
+            '%' => Operator::Mod(rhs),
+            _ => Operator::Pow(rhs),
+        }
+    }
+
+    fn symbol(&self) -> &'static str {
+        match self {
+            Operator::Mul(_) => "*",
+            Operator::Div(_) => "/",
+            Operator::Mod(_) => "%",
+            Operator::Pow(_) => "^",
+            Operator::Nop() => "",
+        }
+    }
+
+    fn rhs(&self) -> Option<&Term> {
+        match self {
+            Operator::Mul(rhs) | Operator::Div(rhs) | Operator::Mod(rhs) | Operator::Pow(rhs) => {
+                Some(rhs)
+            }
+            Operator::Nop() => None,
+        }
+    }
+
+    fn apply(&self, lhs: Value, rhs: Value) -> Value {
+        match self {
+            Operator::Mul(_) => eval::mul(lhs, rhs),
+            Operator::Div(_) => floor_div(lhs, rhs),
+            Operator::Mod(_) => floor_rem(lhs, rhs),
+            Operator::Pow(_) => int_pow(lhs, rhs),
+            Operator::Nop() => lhs,
+        }
+    }
+}
+
+/// The operators in src that are not inside parentheses
+fn operators<'a>(src: &'a str, operators: &'a str) -> impl Iterator<Item = (usize, char)> + 'a {
+    let mut parenth = 0;
+    src.char_indices()
+        // Parenth filter
+        .filter(move |(_, c)| {
+            if [')', '}'].contains(c) {
+                parenth -= 1;
+                parenth = parenth.max(0);
+            }
+            let par_out = parenth;
+            if ['(', '{'].contains(c) {
+                parenth += 1;
+            }
+            par_out == 0
+        })
+        .filter(move |&(i, c)| operators.contains(c) && !(c == '%' && is_die(&src[..i])))
+}
+
+/// Finds the first of the operators in src that is not inside parentheses
+fn find_operator(src: &str, ops: &str) -> Option<(usize, char)> {
+    operators(src, ops).next()
+}
+
+/// Finds the last of the operators in src that is not inside parentheses
+fn rfind_operator(src: &str, ops: &str) -> Option<(usize, char)> {
+    operators(src, ops).last()
+}
+
+/// Whether src ends in the d of a die, in which case a following % makes it a d100 rather than a modulo
+fn is_die(src: &str) -> bool {
+    src.trim_end_matches(['|', '&']).ends_with(['d', 'D'])
+}
+
 #[derive(Clone, Debug)]
 pub struct Term {
     roll: Expression,
@@ -97,44 +180,29 @@ impl FromStr for Term {
 
         // Skip initial + or -
         if "+-".contains(first_char) {
-            (_, src) = src.split_at(1);
+            src = src[1..].trim_start();
         }
 
+        // Exponentiation binds tighter than the other operators, so it is only split at when there are no others.
+        // Products split at their last operator to be left-associative, powers at their first to be right-associative
         let mut roll_txt = src;
-
-        let mut parenth = 0;
-        let op = if let Some((i, c)) = src
-            .char_indices()
-            // Parenth filter
-            .filter(|(_, c)| {
-                if *c == ')' {
-                    parenth -= 1;
-                    parenth = parenth.max(0);
-                }
-                let par_out = parenth;
-                if *c == '(' {
-                    parenth += 1;
-                }
-                par_out == 0
-            })
-            .find(|(_, c)| "/*".contains(*c))
-        {
-            let mut mul_txt: &str;
-            (roll_txt, mul_txt) = src.split_at(i);
-            mul_txt = mul_txt.trim_start_matches(c);
-            match c {
-                '*' => Operator::Mul(Box::new(mul_txt.parse()?)),
-                _ => Operator::Div(Box::new(mul_txt.parse()?)),
+        let op = match rfind_operator(src, "*/%").or_else(|| find_operator(src, "^")) {
+            Some((i, c)) => {
+                let op_txt;
+                (roll_txt, op_txt) = src.split_at(i);
+                Operator::new(c, op_txt[1..].parse()?)
             }
+            None => Operator::Nop(),
+        };
+
+        // The left operand of a product may itself be a product or a power
+        let roll = if find_operator(roll_txt, "*/%^").is_none() {
+            roll_txt.parse()?
         } else {
-            Operator::Nop()
+            roll_txt.parse::<Term>()?.into()
         };
 
-        Ok(Term {
-            roll: roll_txt.parse()?,
-            op,
-            sign,
-        })
+        Ok(Term { roll, op, sign })
     }
 }
 
@@ -142,22 +210,18 @@ impl Rollable for Term {
     fn roll(&self) -> RollOut {
         let rhs = self.roll.roll();
 
-        let mut out = match &self.op {
-            Operator::Mul(expr) => {
+        let mut out = match self.op.rhs() {
+            Some(expr) => {
                 let mut result = expr.roll();
-                result.txt.append_front("*");
-                result.value *= rhs.value;
+                result.txt.append_front(self.op.symbol());
+                result.components.clear();
+                result.value = self.op.apply(rhs.value, result.value);
                 result
             }
-            Operator::Div(expr) => {
-                let mut result = expr.roll();
-                result.txt.append_front("/");
-                result.value = (result.value as f64 / rhs.value as f64).floor() as Value;
-                result
-            }
-            Operator::Nop() => RollOut {
+            None => RollOut {
                 value: rhs.value,
                 txt: Layouter::default(),
+                components: rhs.components,
             },
         };
 
@@ -167,14 +231,16 @@ impl Rollable for Term {
         // Apply the sign
         if let Sign::Negative = self.sign {
             RollOut {
-                value: -out.value,
+                value: eval::neg(out.value),
                 txt: Layouter::from(" - ") + out.txt,
+                components: out
+                    .components
+                    .into_iter()
+                    .map(|(name, value)| (name, eval::neg(value)))
+                    .collect(),
             }
         } else {
-            RollOut {
-                value: out.value,
-                txt: out.txt,
-            }
+            out
         }
     }
 
@@ -182,16 +248,15 @@ impl Rollable for Term {
         let rhs = self.roll.roll_quiet();
 
         // Calculate absolute result
-        let abs_result = match &self.op {
-            Operator::Mul(expr) => rhs * expr.roll_quiet(),
-            Operator::Div(expr) => (expr.roll_quiet() as f64 / rhs as f64).floor() as Value,
-            Operator::Nop() => rhs,
+        let abs_result = match self.op.rhs() {
+            Some(expr) => self.op.apply(rhs, expr.roll_quiet()),
+            None => rhs,
         };
 
         // Apply sign
         match self.sign {
             Sign::Positive => abs_result,
-            Sign::Negative => -abs_result,
+            Sign::Negative => eval::neg(abs_result),
         }
     }
 
@@ -203,7 +268,13 @@ impl Rollable for Term {
                 dist = expr.dist() * &dist;
             }
             Operator::Div(expr) => {
-                dist = expr.dist() / &dist;
+                dist = dist / &expr.dist();
+            }
+            Operator::Mod(expr) => {
+                dist = dist % &expr.dist();
+            }
+            Operator::Pow(expr) => {
+                dist = dist.pow(&expr.dist());
             }
             Operator::Nop() => {}
         }
@@ -213,4 +284,19 @@ impl Rollable for Term {
             Sign::Negative => -dist,
         }
     }
+
+    /// Only terms without an operator keep their components
+    fn component_dists(&self) -> Vec<(String, ProbDist)> {
+        let Operator::Nop() = self.op else {
+            return Vec::new();
+        };
+        self.roll
+            .component_dists()
+            .into_iter()
+            .map(|(name, dist)| match self.sign {
+                Sign::Positive => (name, dist),
+                Sign::Negative => (name, -dist),
+            })
+            .collect()
+    }
 }
@@ -1,3 +1,5 @@
+use std::time::{Duration, Instant};
+
 use super::*;
 
 #[test]
@@ -17,3 +19,283 @@ fn multi_parenth_test() {
     let src = "((dick(3, 10)+5)*dick(3, 20))*(2*d2-3)";
     dbg!(Roll::from_str(src).unwrap());
 }
+
+#[test]
+fn save_test() {
+    let roll = Roll::from_str("save(10, 0, 11, half, 2)").unwrap();
+    let dist = roll.dist();
+    assert_eq!(dist.len(), 3);
+    assert!((dist[&20] - 0.25).abs() < 1e-9);
+    assert!((dist[&15] - 0.5).abs() < 1e-9);
+    assert!((dist[&10] - 0.25).abs() < 1e-9);
+    dbg!(Roll::from_str("save(8d6, 3, 15, evasion, adv)").unwrap().roll().value);
+}
+
+/// Checks that the distribution of src matches a large seeded sample of its rolls
+fn assert_dist_matches_samples(src: &str) {
+    const SAMPLES: usize = 100_000;
+    const TOLERANCE: f64 = 0.01;
+
+    let roll = Roll::from_str(src).unwrap();
+    let exact = roll.dist();
+    let total: f64 = exact.values().sum();
+    assert!((total - 1.0).abs() < 1e-6, "{src}: total probability {total}");
+
+    seed(0xD1CE);
+    let mut samples = SampleDist::new();
+    for _ in 0..SAMPLES {
+        samples.add_sample(roll.roll_quiet());
+    }
+    let sampled = ProbDist::from(samples);
+
+    for outcome in exact.keys().chain(sampled.keys()) {
+        let p_exact = exact.get(outcome).copied().unwrap_or(0.0);
+        let p_sampled = sampled.get(outcome).copied().unwrap_or(0.0);
+        assert!(
+            (p_exact - p_sampled).abs() < TOLERANCE,
+            "{src}: P({outcome}) is {p_exact}, but sampled {p_sampled}"
+        );
+    }
+}
+
+#[test]
+fn exact_dist_test() {
+    assert_dist_matches_samples("crit(2d6)");
+    assert_dist_matches_samples("crit(d|8)");
+    assert_dist_matches_samples("emp(4d6, 2)");
+    assert_dist_matches_samples("emp(3d|8, 4)");
+    assert_dist_matches_samples("stat()");
+    assert_dist_matches_samples("addnz(d4-2, 2d4)");
+    assert_dist_matches_samples("atk(2d6, 3, 5, 15)");
+    assert_dist_matches_samples("atk(1d8, 2, 7, 12, adv)");
+    assert_dist_matches_samples("blackjack()");
+    assert_dist_matches_samples("blackjack(result, h16, h17)");
+    assert_dist_matches_samples("save(4d6, 2, 13, half, 3)");
+    assert_dist_matches_samples("2d6/d3 + d6%4 - d4^2");
+    assert_dist_matches_samples("4dF + d{0,0,1,1,2,3} + d|{-2..2}");
+    assert_dist_matches_samples("{2d6, 1d8, 1d4}kh2 + {d6, d8, d10}dh1");
+    assert_dist_matches_samples("{d20+5, d20+2, d12}>=15");
+    assert_dist_matches_samples("4d6kh3 + 2d|20dl1");
+}
+
+#[test]
+fn dice_modifier_test() {
+    let dist = |src: &str| Roll::from_str(src).unwrap().dist();
+    let stats = dist("4d6kh3");
+    assert_eq!((stats.min(), stats.max()), (Some(3), Some(18)));
+    assert!((stats.expectation() - dist("{d6, d6, d6, d6}kh3").expectation()).abs() < 1e-9);
+    assert_eq!(dist("3d20>=15").max(), Some(3));
+    assert_eq!(dist("2d{1,5}d1").max(), Some(5));
+    assert!(Roll::from_str("4d6kx").is_err());
+    let limits = EvalLimits {
+        max_dice: 1000,
+        ..Default::default()
+    };
+    assert!(Roll::parse_with_limits("2000d6kh1", limits).is_err());
+
+    let roll = Roll::from_str("4d6kh3").unwrap().roll();
+    assert_eq!(roll.txt.to_string().matches(',').count(), 3);
+}
+
+#[test]
+fn checked_arithmetic_test() {
+    fn kind<T>(res: Result<T, DiceError>) -> Option<ErrorKind> {
+        res.err().map(|err| err.kind())
+    }
+    let roll = |src: &str| Roll::from_str(src).unwrap();
+
+    assert_eq!(
+        kind(roll("5/0").try_roll()),
+        Some(ErrorKind::DivisionByZero)
+    );
+    assert_eq!(
+        kind(roll("d6%(d2-1)").try_dist()),
+        Some(ErrorKind::DivisionByZero)
+    );
+    assert_eq!(
+        kind(roll("9223372036854775807 + d2").try_dist()),
+        Some(ErrorKind::Overflow)
+    );
+    assert_eq!(
+        kind(roll("2^70").try_roll_quiet()),
+        Some(ErrorKind::Overflow)
+    );
+    assert_eq!(
+        kind(roll("3037000500*3037000500").try_roll()),
+        Some(ErrorKind::Overflow)
+    );
+    assert_eq!(
+        kind(Roll::from_str("10000000d6")),
+        Some(ErrorKind::ResourceLimit)
+    );
+    assert_eq!(
+        kind(Roll::from_str("2d{9223372036854775807}")),
+        Some(ErrorKind::Overflow)
+    );
+    assert_eq!(
+        kind(roll("sum(d{4611686018427387904}, 1 + d2)").try_dist()),
+        Some(ErrorKind::Overflow)
+    );
+
+    // Outcomes whose squares do not fit in a Value still have a variance
+    let dist = Roll::from_str("d2*4000000000").unwrap().try_dist().unwrap();
+    assert!((dist.sigma() - 2e9).abs() < 1.0);
+
+    // Errors do not linger into later evaluations
+    assert!(roll("d6/2 + 2^10").try_dist().is_ok());
+    assert_eq!(roll("5/0").roll().value, 0);
+}
+
+#[test]
+fn limits_test() {
+    let limits = EvalLimits {
+        max_dice: 100,
+        max_faces: 1000,
+        max_depth: 3,
+        max_support: 5000,
+        ..Default::default()
+    };
+    let kind = |src: &str| {
+        Roll::parse_with_limits(src, limits)
+            .and_then(|roll| roll.try_dist())
+            .err()
+            .map(|err| err.kind())
+    };
+
+    assert_eq!(kind("100d6 + d1000 + ((d4))"), None);
+    assert_eq!(kind("101d6"), Some(ErrorKind::ResourceLimit));
+    assert_eq!(kind("d1001"), Some(ErrorKind::ResourceLimit));
+    assert_eq!(kind("d{1..2000}"), Some(ErrorKind::ResourceLimit));
+    assert_eq!(kind("((((d4))))"), Some(ErrorKind::ResourceLimit));
+    assert_eq!(kind("d1000 * d1000"), Some(ErrorKind::ResourceLimit));
+    // Too many faces to parse are too many faces
+    assert_eq!(
+        kind("d99999999999999999999"),
+        Some(ErrorKind::ResourceLimit)
+    );
+
+    // The limits stick with the roll, but not with the thread
+    let roll = Roll::parse_with_limits("d1000 * d1000", limits).unwrap();
+    assert_eq!(roll.limits(), limits);
+    assert!(roll.try_dist().is_err());
+    assert!(Roll::from_str("d1000 * d1000").unwrap().try_dist().is_ok());
+
+    // Products of large dice are refused before going through all pairs of outcomes
+    let limits = EvalLimits {
+        time_budget: Duration::from_secs(1),
+        ..Default::default()
+    };
+    let start = Instant::now();
+    for src in ["d100000*d100000", "d100000/d100000", "d10000*d10000*d10000"] {
+        let res = Roll::parse_with_limits(src, limits).and_then(|roll| roll.try_dist());
+        assert_eq!(res.err().map(|err| err.kind()), Some(ErrorKind::ResourceLimit));
+    }
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn chain_depth_test() {
+    // Every operator of a chain nests the rest of it, so long chains are held to the depth limit instead of overflowing the stack
+    let chain = |op: &str, n: usize| format!("{}1", format!("1{op}").repeat(n));
+    for op in ["*", "/", "%", "^"] {
+        let err = Roll::from_str(&chain(op, 10_000)).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ResourceLimit, "{op}");
+    }
+    assert_eq!(Roll::from_str(&chain("*", 60)).unwrap().roll().value, 1);
+    // Signs after an operator continue the chain, while other terms start a new one
+    assert!(Roll::from_str(&format!("1{}", "*-1".repeat(10_000))).is_err());
+    let terms = vec![chain("*", 40); 100].join(" + ");
+    assert_eq!(Roll::from_str(&terms).unwrap().roll().value, 100);
+}
+
+#[test]
+fn no_panic_test() {
+    // Inputs that used to panic, some of them found by the fuzz target
+    for src in [
+        "panic()",
+        "ber(2)",
+        "atk(x, 1, 2, 3)",
+        "é",
+        "(d)é",
+        "(d6",
+        "poisson(9223372036854775807)",
+        "sum(d6, 9223372036854775807)",
+        "normal(9223372036854775807, 1)",
+        "crit(d{9223372036854775807})",
+        "draw(deck(9223372036854775807, 9223372036854775807), 2)",
+        "save(d6, 0, 10, half, 9223372036854775807)",
+        "dick(1e300, 9223372036854775807)",
+    ] {
+        if let Ok(roll) = Roll::from_str(src) {
+            let _ = roll.try_roll();
+            let _ = roll.try_dist();
+        }
+    }
+    assert_eq!(SampleDist::new().roll().value, 0);
+}
+
+#[test]
+fn faces_test() {
+    let dist = Roll::from_str("d{0,0,1,1,2,3}").unwrap().dist();
+    assert!((dist[&0] - 1.0 / 3.0).abs() < 1e-9);
+    assert!((dist[&3] - 1.0 / 6.0).abs() < 1e-9);
+    assert_eq!(Roll::from_str("d%").unwrap().dist().len(), 100);
+    assert_eq!(Roll::from_str("d%%7").unwrap().dist().len(), 7);
+    let fudge = Roll::from_str("4dF").unwrap().dist();
+    assert_eq!((fudge.min(), fudge.max()), (Some(-4), Some(4)));
+    assert_eq!(Roll::from_str("d{-3..0}").unwrap().dist().max(), Some(0));
+    assert!(Roll::from_str("d{2..1}").is_err());
+    assert!(Roll::from_str("d{1,x}").is_err());
+}
+
+#[test]
+fn operator_test() {
+    let value = |src: &str| Roll::from_str(src).unwrap().roll().value;
+    assert_eq!(value("10/2"), 5);
+    assert_eq!(value("7%3"), 1);
+    assert_eq!(value("(0-7)%3"), 2);
+    assert_eq!(value("3^2*2"), 18);
+    assert_eq!(value("2^3^2"), 512);
+    assert_eq!(value("2^(0-1)"), 0);
+    assert_eq!(value("2*3/2"), 3);
+    assert_eq!(value("10%4*3"), 6);
+    assert_eq!(value("12/2/3"), 2);
+}
+
+#[test]
+fn monte_carlo_test() {
+    let roll = Roll::from_str("2d6").unwrap();
+    let res = MonteCarlo::default().run(&roll);
+    assert!(res.converged);
+    assert!(res.max_error() <= 0.001);
+    assert!((res.mean - 7.0).abs() < 5.0 * res.mean_error);
+    assert!(roll.dist().approximation().is_none());
+
+    let token = CancelToken::new();
+    token.cancel();
+    let res = with_cancel_token(&token, || MonteCarlo::default().run(&roll));
+    assert!(res.cancelled);
+
+    // Approximations propagate through arithmetic
+    let bet = Roll::from_str("bet(3) + d4").unwrap();
+    assert!(bet.dist().approximation().is_some());
+    // And through functions that combine the distributions of their arguments
+    for src in ["{bet(3), d4}kh1", "{bet(3), d4}>=3", "max(bet(3), d4)"] {
+        let dist = Roll::from_str(src).unwrap().dist();
+        assert!(dist.approximation().is_some(), "{src}");
+    }
+}
+
+#[test]
+fn with_seed_test() {
+    let roll = Roll::from_str("d1000000").unwrap();
+    let seeded = with_seed(3, || roll.roll().value);
+    assert_eq!(with_seed(3, || roll.roll().value), seeded);
+
+    // The seed does not outlive the call
+    seed(1);
+    let expected = roll.roll().value;
+    seed(1);
+    with_seed(3, || roll.roll());
+    assert_eq!(roll.roll().value, expected);
+}
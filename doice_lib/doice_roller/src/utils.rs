@@ -1,13 +1,20 @@
+use crate::{eval, DiceError, Value};
+
+/// Outcomes of distributions with infinite support are left out once their probability drops below this
+pub const PROB_CUTOFF: f64 = 1e-12;
+/// Above this, ln(n!) is approximated using Stirling's series
+const STIRLING_THRESHOLD: usize = 256;
+
 pub fn find_parenth(src: &str, needle: char) -> Option<usize> {
     let mut parenth = 0;
     src.char_indices()
         .filter(|(_, c)| {
-            if *c == ')' {
+            if [')', '}'].contains(c) {
                 parenth -= 1;
                 parenth = parenth.max(0);
             }
             let par_out = parenth;
-            if *c == '(' {
+            if ['(', '{'].contains(c) {
                 parenth += 1;
             }
             par_out == 0
@@ -21,3 +28,144 @@ pub fn split_once_parenth(src: &str, at: char) -> Option<(&str, &str)> {
     let (first, second) = src.split_at(index);
     Some((first, &second[1..second.len()]))
 }
+
+/// Splits src at every occurrence of `at` that is not inside parentheses
+pub fn split_parenth(src: &str, at: char) -> Vec<&str> {
+    let mut out = Vec::new();
+    let mut rest = src;
+    while let Some((first, second)) = split_once_parenth(rest, at) {
+        out.push(first);
+        rest = second;
+    }
+    out.push(rest);
+    out
+}
+
+/// The deepest nesting in src, counting parentheses and braces as well as chained operators like the ones in 2*3^4,
+/// as every operator of a chain holds the rest of it
+pub fn nesting_depth(src: &str) -> usize {
+    // Number of operators chained so far in the current term of every level of parentheses
+    let mut chains = vec![0usize];
+    let mut depth = 0usize;
+    let mut deepest = 0;
+    let mut after_operator = false;
+    for c in src.chars().filter(|c| !c.is_whitespace()) {
+        match c {
+            '(' | '{' => {
+                chains.push(0);
+                depth += 1;
+            }
+            ')' | '}' if chains.len() > 1 => {
+                depth -= 1 + chains.pop().unwrap_or(0);
+            }
+            '*' | '/' | '%' | '^' => {
+                if let Some(chain) = chains.last_mut() {
+                    *chain += 1;
+                    depth += 1;
+                }
+            }
+            // A sign right after an operator belongs to the number after it, and continues the chain
+            '+' | '-' | ',' if !after_operator => {
+                if let Some(chain) = chains.last_mut() {
+                    depth -= *chain;
+                    *chain = 0;
+                }
+            }
+            _ => {}
+        }
+        after_operator = matches!(c, '*' | '/' | '%' | '^' | '<' | '>' | '=');
+        deepest = deepest.max(depth);
+    }
+    deepest
+}
+
+/// Computes ln(n!), which unlike n! itself does not overflow for large n
+pub fn ln_factorial(n: usize) -> f64 {
+    if n < STIRLING_THRESHOLD {
+        (2..=n).map(|i| (i as f64).ln()).sum()
+    } else {
+        let n = n as f64;
+        n * n.ln() - n + 0.5 * (2.0 * std::f64::consts::PI * n).ln() + 1.0 / (12.0 * n)
+            - 1.0 / (360.0 * n.powi(3))
+    }
+}
+
+/// Computes ln(n choose k)
+pub fn ln_binomial(n: usize, k: usize) -> f64 {
+    ln_factorial(n) - ln_factorial(k) - ln_factorial(n - k)
+}
+
+/// Division rounding towards negative infinity.
+/// Division by zero and overflow are reported, resulting in 0 and a saturated quotient respectively
+pub fn floor_div(lhs: Value, rhs: Value) -> Value {
+    if rhs == 0 {
+        eval::report(DiceError::division_by_zero());
+        return 0;
+    }
+    let Some(quotient) = lhs.checked_div(rhs) else {
+        eval::report(DiceError::overflow());
+        return Value::MAX;
+    };
+    if lhs % rhs != 0 && (lhs < 0) != (rhs < 0) {
+        quotient - 1
+    } else {
+        quotient
+    }
+}
+
+/// Division rounding towards positive infinity, see `floor_div`
+pub fn ceil_div(lhs: Value, rhs: Value) -> Value {
+    eval::neg(floor_div(eval::neg(lhs), rhs))
+}
+
+/// Division rounding to the nearest integer, with halves rounded up, see `floor_div`
+pub fn round_div(lhs: Value, rhs: Value) -> Value {
+    let (lhs, rhs) = if rhs < 0 {
+        (eval::neg(lhs), eval::neg(rhs))
+    } else {
+        (lhs, rhs)
+    };
+    floor_div(eval::add(eval::mul(lhs, 2), rhs), eval::mul(rhs, 2))
+}
+
+/// Remainder of `floor_div`, which has the sign of rhs
+pub fn floor_rem(lhs: Value, rhs: Value) -> Value {
+    if rhs == 0 {
+        eval::report(DiceError::division_by_zero());
+        return 0;
+    }
+    let rem = lhs.wrapping_rem(rhs);
+    if rem != 0 && (rem < 0) != (rhs < 0) {
+        rem + rhs
+    } else {
+        rem
+    }
+}
+
+/// Raises base to the power exp, rounding down for negative exponents.
+/// Raising 0 to a negative power is reported as division by zero and results in 0
+pub fn int_pow(base: Value, exp: Value) -> Value {
+    match base {
+        0 if exp < 0 => {
+            eval::report(DiceError::division_by_zero());
+            0
+        }
+        0 | 1 => base.pow(u32::from(exp != 0)),
+        -1 if exp % 2 == 0 => 1,
+        -1 => -1,
+        // 1 / base^-exp lies strictly between -1 and 1
+        _ if exp < 0 && base < 0 && exp % 2 != 0 => -1,
+        _ if exp < 0 => 0,
+        _ => u32::try_from(exp)
+            .ok()
+            .and_then(|exp| base.checked_pow(exp))
+            .unwrap_or_else(|| {
+                eval::report(DiceError::overflow());
+                if base < 0 && exp % 2 != 0 {
+                    Value::MIN
+                } else {
+                    Value::MAX
+                }
+            }),
+    }
+}
@@ -1,6 +1,10 @@
 use egui::{Context, Key, Modifiers};
+use serde_json::Value;
 
-use doice_gui::{components::DiceGrapher, Activity, DCtx};
+use doice_gui::{
+    components::{DiceGrapher, GrapherState},
+    Activity, DCtx,
+};
 
 #[derive(Clone, Default)]
 pub struct DiceRoller {
@@ -61,6 +65,18 @@ impl Activity for DiceRoller {
     fn category(&self) -> &str {
         "Utility"
     }
+
+    fn save_state(&self) -> Option<Value> {
+        serde_json::to_value(self.plotter.save()).ok()
+    }
+
+    fn load_state(&mut self, state: Value) {
+        if let Ok(state) = serde_json::from_value::<GrapherState>(state) {
+            self.text_in = state.input.clone();
+            self.prev_input = state.input.clone();
+            self.plotter.load(state);
+        }
+    }
 }
 
 // fn _make_plot(ui: &mut Ui, roller: &Roll) {
@@ -1,4 +1,5 @@
 use egui::{ScrollArea, TextEdit};
+use serde_json::Value;
 
 use doice_gui::{Activity, DCtx};
 
@@ -47,4 +48,14 @@ impl Activity for Notes {
     fn category(&self) -> &str {
         "character"
     }
+
+    fn save_state(&self) -> Option<Value> {
+        Some(Value::String(self.text.clone()))
+    }
+
+    fn load_state(&mut self, state: Value) {
+        if let Value::String(text) = state {
+            self.text = text;
+        }
+    }
 }
\ No newline at end of file
@@ -1,4 +1,5 @@
 use egui::{Key, Modifiers};
+use serde_json::Value;
 
 use doice_gui::{Activity, DCtx};
 
@@ -58,4 +59,16 @@ impl Activity for WideAnalyzer {
     fn category(&self) -> &str {
         "Utility"
     }
+
+    fn save_state(&self) -> Option<Value> {
+        Some(Value::String(self.text_in.clone()))
+    }
+
+    /// Only restores the input, as the grapher itself is restored along with the `AppData`
+    fn load_state(&mut self, state: Value) {
+        if let Value::String(text) = state {
+            self.prev_input = text.clone();
+            self.text_in = text;
+        }
+    }
 }
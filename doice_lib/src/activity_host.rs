@@ -1,7 +1,7 @@
 use std::panic::{catch_unwind, AssertUnwindSafe};
 use std::rc::Rc;
 
-use doice_gui::{eframe, Activity, AppData, DCtx};
+use doice_gui::{eframe, Activity, AppData, Config, DCtx, SavedState};
 use dyn_clone::DynClone;
 
 use crate::draw_topbar;
@@ -25,15 +25,25 @@ impl ActivityHost {
     // Create a new activityhost hosting the provided activity
     pub fn new<T: CloneActivity + Clone + Default + 'static>(
         cc: &eframe::CreationContext<'_>,
+        config: Config,
     ) -> Self {
+        config.apply(&cc.egui_ctx);
         let mut act: Box<(dyn CloneActivity + 'static)> = Box::<T>::default();
         let backup_act = dyn_clone::clone_box(&*act);
         let ctx = cc.egui_ctx.clone();
         act.init(ctx);
+        let data = AppData::new(cc);
+        if let Some(mut state) = SavedState::load(cc.storage) {
+            data.load(&mut state);
+            let name = act.name().to_string();
+            if let Some(act_state) = state.take_activity(&name) {
+                act.load_state(act_state);
+            }
+        }
         ActivityHost {
             act: Some(act),
             backup_act,
-            data: Rc::new(AppData::new(cc)),
+            data: Rc::new(data),
             focused: false,
             errored_out: false,
         }
@@ -87,7 +97,6 @@ impl ActivityHost {
 
 impl eframe::App for ActivityHost {
     fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
-        ctx.set_visuals(egui::Visuals::dark());
         draw_topbar(ctx, frame);
 
         if self.errored_out {
@@ -98,7 +107,16 @@ impl eframe::App for ActivityHost {
         self.update_logic(ctx, frame);
     }
 
-    fn save(&mut self, _storage: &mut dyn eframe::Storage) {}
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        let mut state = SavedState::new();
+        self.data.save(&mut state);
+        if let Some(act) = &self.act {
+            if let Some(act_state) = act.save_state() {
+                state.activities.push((act.name().to_string(), act_state));
+            }
+        }
+        state.store(storage);
+    }
 
     fn on_exit(&mut self, _gl: std::option::Option<&eframe::glow::Context>) {}
 
@@ -106,12 +124,14 @@ impl eframe::App for ActivityHost {
         Duration::from_secs(30)
     }
 
-    fn clear_color(&self, _visuals: &egui::Visuals) -> [f32; 4] {
+    fn clear_color(&self, visuals: &egui::Visuals) -> [f32; 4] {
         // NOTE: a bright gray makes the shadows of the windows look weird.
         // We use a bit of transparency so that if the user switches on the
         // `transparent()` option they get immediate results.
-        egui::Color32::from_rgba_unmultiplied(12, 12, 12, 180).to_normalized_gamma_f32()
-
-        // _visuals.window_fill() would also be a natural choice
+        if visuals.dark_mode {
+            egui::Color32::from_rgba_unmultiplied(12, 12, 12, 180).to_normalized_gamma_f32()
+        } else {
+            visuals.window_fill().to_normalized_gamma_f32()
+        }
     }
 }
@@ -2,7 +2,7 @@ use std::rc::Rc;
 
 use doice_gui::{
     eframe::{App, CreationContext},
-    Activity, AppData, DCtx,
+    Activity, AppData, Config, DCtx, SavedState,
 };
 use egui::{Frame, Vec2};
 
@@ -20,15 +20,30 @@ pub struct TailoredUI {
 }
 
 impl TailoredUI {
-    pub fn new(cc: &CreationContext<'_>) -> Self {
-        cc.egui_ctx.set_visuals(egui::Visuals::dark());
+    pub fn new(cc: &CreationContext<'_>, config: Config) -> Self {
+        config.apply(&cc.egui_ctx);
 
-        Self {
+        let mut out = Self {
             data: Rc::new(AppData::new(cc)),
             roller: WideAnalyzer::default(),
             manager: CharacterManager::default(),
             notes: Notes::default(),
+        };
+        if let Some(mut state) = SavedState::load(cc.storage) {
+            out.data.load(&mut state);
+            for act in out.activities() {
+                let name = act.name().to_string();
+                if let Some(act_state) = state.take_activity(&name) {
+                    act.load_state(act_state);
+                }
+            }
         }
+        out
+    }
+
+    /// The activities making up the ui, besides the shared components
+    fn activities(&mut self) -> [&mut dyn Activity; 3] {
+        [&mut self.roller, &mut self.manager, &mut self.notes]
     }
 
     fn context(&self, i: usize) -> DCtx {
@@ -102,4 +117,15 @@ impl App for TailoredUI {
             });
         });
     }
+
+    fn save(&mut self, storage: &mut dyn doice_gui::eframe::Storage) {
+        let mut state = SavedState::new();
+        self.data.save(&mut state);
+        for act in self.activities() {
+            if let Some(act_state) = act.save_state() {
+                state.activities.push((act.name().to_string(), act_state));
+            }
+        }
+        state.store(storage);
+    }
 }
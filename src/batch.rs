@@ -0,0 +1,262 @@
+//! Runs every expression in a file, like a list of attacks for an encounter
+
+use std::{fmt::Write, fs, path::PathBuf, str::FromStr};
+
+use clap::{Args, ValueEnum};
+use doice_roller::{DiceError, Roll};
+use serde_json::{json, Value};
+
+use crate::report::{ErrorReport, RollReport, StatsReport};
+
+#[derive(Args)]
+pub struct BatchArgs {
+    /// File with an expression on every line, optionally labelled like "attack: 2d20kh1+5", and comments after #
+    file: PathBuf,
+    /// Reports the statistics of the distribution of every expression, instead of rolling it
+    #[arg(long)]
+    stats: bool,
+    /// Adds the chance to roll at least the DC to the statistics
+    #[arg(long, requires = "stats")]
+    dc: Option<isize>,
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    format: Format,
+    /// Seed for the random number generator, making the rolls reproducible
+    #[arg(long)]
+    seed: Option<u64>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Format {
+    Text,
+    Csv,
+    Json,
+}
+
+/// An expression in a batch file
+#[derive(Debug, PartialEq, Eq)]
+struct Entry {
+    /// Line number, starting at 1
+    line: usize,
+    label: Option<String>,
+    expr: String,
+}
+
+enum Outcome {
+    Roll(RollReport),
+    /// The statistics, with the chance of success if there is a DC
+    Stats(StatsReport, Option<f64>),
+    Error(ErrorReport),
+}
+
+pub fn batch(args: &BatchArgs) -> Result<(), String> {
+    let src = fs::read_to_string(&args.file)
+        .map_err(|err| format!("could not read {}: {err}", args.file.display()))?;
+    if let Some(seed) = args.seed {
+        doice_roller::seed(seed);
+    }
+
+    let results: Vec<_> = parse_entries(&src)
+        .into_iter()
+        .map(|entry| {
+            let outcome =
+                evaluate(&entry, args).unwrap_or_else(|err| Outcome::Error((&err).into()));
+            (entry, outcome)
+        })
+        .collect();
+
+    print!(
+        "{}",
+        match args.format {
+            Format::Text => text(&results),
+            Format::Csv => csv(&results, args.stats),
+            Format::Json => format!("{:#}\n", json(&results)),
+        }
+    );
+
+    let failed = results
+        .iter()
+        .filter(|(_, outcome)| matches!(outcome, Outcome::Error(_)))
+        .count();
+    if failed > 0 {
+        return Err(format!("{failed} of {} expressions failed", results.len()));
+    }
+    Ok(())
+}
+
+/// Finds the expressions in a batch file, skipping empty lines and comments
+fn parse_entries(src: &str) -> Vec<Entry> {
+    src.lines()
+        .enumerate()
+        .filter_map(|(i, line)| {
+            let line = line.split_once('#').map_or(line, |(line, _)| line).trim();
+            if line.is_empty() {
+                return None;
+            }
+            // Colons inside braces name the entries of a list instead
+            let (label, expr) = match line.split_once(':') {
+                Some((label, expr)) if !label.contains(['{', '(']) => {
+                    (Some(label.trim().to_string()), expr.trim())
+                }
+                _ => (None, line),
+            };
+            Some(Entry {
+                line: i + 1,
+                label,
+                expr: expr.to_string(),
+            })
+        })
+        .collect()
+}
+
+fn evaluate(entry: &Entry, args: &BatchArgs) -> Result<Outcome, DiceError> {
+    let roll = Roll::from_str(&entry.expr)?;
+    if !args.stats {
+        let out = roll.try_roll()?;
+        return Ok(Outcome::Roll(RollReport::new(&roll, &out)));
+    }
+    let dist = roll.try_dist()?;
+    let success = args
+        .dc
+        .map(|dc| dist.range(dc..).fold(0.0, |acc, (_, prob)| acc + prob));
+    Ok(Outcome::Stats((&dist).into(), success))
+}
+
+fn text(results: &[(Entry, Outcome)]) -> String {
+    let name = |entry: &Entry| entry.label.clone().unwrap_or_else(|| entry.expr.clone());
+    let width = results
+        .iter()
+        .map(|(entry, _)| name(entry).chars().count())
+        .max()
+        .unwrap_or(0);
+
+    let mut out = String::new();
+    for (entry, outcome) in results {
+        let name = name(entry);
+        match outcome {
+            Outcome::Roll(roll) => writeln!(out, "{name:<width$}  {} = {}", roll.text, roll.value),
+            Outcome::Stats(stats, success) => {
+                let mut line = format!(
+                    "{name:<width$}  average {:>8.3}  deviation {:>8.3}  range {} to {}",
+                    stats.mean,
+                    stats.sigma,
+                    stats.min.unwrap_or(0),
+                    stats.max.unwrap_or(0)
+                );
+                if let Some(success) = success {
+                    write!(line, "  success {:>6.2}%", 100.0 * success).unwrap();
+                }
+                writeln!(out, "{line}")
+            }
+            Outcome::Error(err) => {
+                writeln!(
+                    out,
+                    "{name:<width$}  error on line {}: {}",
+                    entry.line, err.message
+                )
+            }
+        }
+        .unwrap();
+    }
+    out
+}
+
+fn csv(results: &[(Entry, Outcome)], stats: bool) -> String {
+    let mut out = if stats {
+        "line,label,expression,mean,sigma,min,max,success,error\n".to_string()
+    } else {
+        "line,label,expression,value,text,error\n".to_string()
+    };
+    for (entry, outcome) in results {
+        let mut fields = vec![
+            entry.line.to_string(),
+            csv_field(entry.label.as_deref().unwrap_or_default()),
+            csv_field(&entry.expr),
+        ];
+        let optional = |value: Option<String>| value.unwrap_or_default();
+        match outcome {
+            Outcome::Roll(roll) => {
+                fields.extend([roll.value.to_string(), csv_field(&roll.text), String::new()])
+            }
+            Outcome::Stats(stats, success) => fields.extend([
+                stats.mean.to_string(),
+                stats.sigma.to_string(),
+                optional(stats.min.map(|min| min.to_string())),
+                optional(stats.max.map(|max| max.to_string())),
+                optional(success.map(|success| success.to_string())),
+                String::new(),
+            ]),
+            Outcome::Error(err) => {
+                // Leave the result columns empty
+                let columns = if stats { 5 } else { 2 };
+                fields.resize(fields.len() + columns, String::new());
+                fields.push(csv_field(&err.message));
+            }
+        }
+        out += &fields.join(",");
+        out.push('\n');
+    }
+    out
+}
+
+/// Quotes a field if it contains characters that would otherwise break up the row
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn json(results: &[(Entry, Outcome)]) -> Value {
+    results
+        .iter()
+        .map(|(entry, outcome)| {
+            let mut out = json!({
+                "line": entry.line,
+                "label": entry.label,
+                "expression": entry.expr,
+            });
+            match outcome {
+                Outcome::Roll(roll) => out["roll"] = json!(roll),
+                Outcome::Stats(stats, success) => {
+                    out["stats"] = json!(stats);
+                    if let Some(success) = success {
+                        out["stats"]["success"] = json!(success);
+                    }
+                }
+                Outcome::Error(err) => out["error"] = json!(err),
+            }
+            out
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_entries_test() {
+        let src = "# Goblin ambush\n\nattack: 2d20kh1+5 # with advantage\n{fire: 3d6, cold: 2d6}\n  4d6kh3\n";
+        let entry = |line, label: Option<&str>, expr: &str| Entry {
+            line,
+            label: label.map(String::from),
+            expr: expr.into(),
+        };
+        assert_eq!(
+            parse_entries(src),
+            vec![
+                entry(3, Some("attack"), "2d20kh1+5"),
+                entry(4, None, "{fire: 3d6, cold: 2d6}"),
+                entry(5, None, "4d6kh3"),
+            ]
+        );
+    }
+
+    #[test]
+    fn csv_field_test() {
+        assert_eq!(csv_field("2d6"), "2d6");
+        assert_eq!(csv_field("{3d6, 2d6}"), "\"{3d6, 2d6}\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+}
@@ -0,0 +1,12 @@
+//! Names of the roller's functions, for completion and listings
+
+use doice_roller::FUNCTION_DOCS;
+
+/// The names of the functions documented in `FUNCTION_DOCS` with their documentation, the names taken from their usage
+pub fn function_names() -> impl Iterator<Item = (&'static str, &'static str)> {
+    FUNCTION_DOCS.iter().filter_map(|(_, doc)| {
+        let (_, usage) = doc.split_once("Usage: ")?;
+        let (name, _) = usage.split_once('(')?;
+        Some((name.trim(), *doc))
+    })
+}
@@ -0,0 +1,252 @@
+//! A local HTTP API, like POST /roll with {"expression": "2d6+3"}, answered the same way as JSON-RPC methods
+
+use std::{io::Read, net::SocketAddr, sync::Arc, thread};
+
+use serde_json::{json, Value};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::serve::{call, RpcError, ServeConfig};
+
+/// Requests with larger bodies are refused without being read
+const MAX_BODY: usize = 64 * 1024;
+/// Requests are answered by this many threads, so a slow distribution does not hold up quick rolls
+const WORKERS: usize = 4;
+
+pub fn serve(addr: SocketAddr, config: &ServeConfig) -> Result<(), String> {
+    let server = Server::http(addr).map_err(|err| format!("could not serve on {addr}: {err}"))?;
+    eprintln!("serving on http://{addr}");
+    run(Arc::new(server), config);
+    Ok(())
+}
+
+/// Answers requests until the server is unblocked
+fn run(server: Arc<Server>, config: &ServeConfig) {
+    thread::scope(|scope| {
+        for _ in 0..WORKERS {
+            let server = server.clone();
+            scope.spawn(move || {
+                for request in server.incoming_requests() {
+                    answer(request, config);
+                }
+            });
+        }
+    });
+}
+
+fn answer(mut request: Request, config: &ServeConfig) {
+    let origin = request
+        .headers()
+        .iter()
+        .find(|header| header.field.equiv("Origin"))
+        .map(|header| header.value.as_str().to_owned());
+    let allowed = origin
+        .as_ref()
+        .filter(|origin| config.allow_origin.contains(origin));
+
+    let res = match &origin {
+        // Any web page could otherwise make the server compute expensive distributions
+        Some(origin) if allowed.is_none() && is_web_origin(origin) => {
+            let err = RpcError::new(
+                RpcError::INVALID_REQUEST,
+                format!("requests from {origin} are not allowed, see --allow-origin"),
+            );
+            Err((403, err))
+        }
+        _ => route(&mut request, config),
+    };
+    let (status, body) = match res {
+        Ok(result) => (200, result),
+        Err((status, err)) => (status, json!({ "error": err.to_json() })),
+    };
+    let mut response = Response::from_string(body.to_string())
+        .with_status_code(status)
+        .with_header(header("Content-Type", "application/json"));
+    // Only the web pages that are allowed can read the responses
+    if let Some(origin) = allowed {
+        response.add_header(header("Access-Control-Allow-Origin", origin));
+        response.add_header(header("Access-Control-Allow-Headers", "Content-Type"));
+        response.add_header(header("Vary", "Origin"));
+    }
+    // The client may have hung up already, which is its problem
+    let _ = request.respond(response);
+}
+
+/// Whether requests with this origin come from a web page, rather than from a browser extension.
+/// Sandboxed pages and local files have the origin null
+fn is_web_origin(origin: &str) -> bool {
+    origin == "null" || origin.starts_with("http://") || origin.starts_with("https://")
+}
+
+fn header(field: &str, value: &str) -> Header {
+    Header::from_bytes(field.as_bytes(), value.as_bytes()).expect("headers are valid ASCII")
+}
+
+/// Finds the method for the path, with the HTTP status to respond with on failure
+fn route(request: &mut Request, config: &ServeConfig) -> Result<Value, (u16, RpcError)> {
+    let path = request
+        .url()
+        .split('?')
+        .next()
+        .unwrap_or_default()
+        .to_string();
+    let (method, post) = match path.as_str() {
+        "/roll" => ("roll", true),
+        "/dist" => ("dist", true),
+        "/stats" => ("stats", true),
+        "/validate" => ("validate", true),
+        "/functions" => ("list_functions", false),
+        _ => {
+            let err = RpcError::new(RpcError::METHOD_NOT_FOUND, format!("no endpoint {path}"));
+            return Err((404, err));
+        }
+    };
+
+    match (request.method(), post) {
+        // Preflight requests of browsers
+        (Method::Options, _) => return Ok(Value::Null),
+        (Method::Post, true) | (Method::Get, false) => {}
+        (_, true) => return Err((405, method_not_allowed("POST"))),
+        (_, false) => return Err((405, method_not_allowed("GET"))),
+    }
+
+    let params = if post {
+        read_body(request)?
+    } else {
+        Value::Null
+    };
+    call(method, params, config).map_err(|err| {
+        let status = match err.code {
+            RpcError::DICE_ERROR => 422,
+            _ => 400,
+        };
+        (status, err)
+    })
+}
+
+fn method_not_allowed(allowed: &str) -> RpcError {
+    RpcError::new(
+        RpcError::INVALID_REQUEST,
+        format!("only {allowed} is allowed here"),
+    )
+}
+
+fn read_body(request: &mut Request) -> Result<Value, (u16, RpcError)> {
+    if request.body_length().unwrap_or(0) > MAX_BODY {
+        let err = RpcError::new(
+            RpcError::INVALID_REQUEST,
+            format!("bodies may be at most {MAX_BODY} bytes"),
+        );
+        return Err((413, err));
+    }
+    let mut body = String::new();
+    request
+        .as_reader()
+        .take(MAX_BODY as u64)
+        .read_to_string(&mut body)
+        .map_err(|err| (400, RpcError::new(RpcError::PARSE_ERROR, err.to_string())))?;
+    serde_json::from_str(&body)
+        .map_err(|err| (400, RpcError::new(RpcError::PARSE_ERROR, err.to_string())))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::TcpStream,
+    };
+
+    use super::*;
+
+    /// Sends a request to the server, returning the status line and the body of the response
+    fn send(addr: SocketAddr, method: &str, path: &str, body: &str) -> (String, Value) {
+        let (head, body) = send_from(addr, None, method, path, body);
+        (head.lines().next().unwrap().to_string(), body)
+    }
+
+    /// Sends a request as if from a web page with the origin, returning the head and the body of the response
+    fn send_from(
+        addr: SocketAddr,
+        origin: Option<&str>,
+        method: &str,
+        path: &str,
+        body: &str,
+    ) -> (String, Value) {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        let origin = origin.map_or(String::new(), |origin| format!("Origin: {origin}\r\n"));
+        write!(
+            stream,
+            "{method} {path} HTTP/1.1\r\nHost: localhost\r\n{origin}Content-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        (head.to_string(), serde_json::from_str(body).unwrap())
+    }
+
+    #[test]
+    fn endpoints_test() {
+        let server = Arc::new(Server::http("127.0.0.1:0").unwrap());
+        let addr = server.server_addr().to_ip().unwrap();
+        thread::spawn(move || run(server, &ServeConfig::default()));
+
+        let (status, body) = send(
+            addr,
+            "POST",
+            "/roll",
+            r#"{"expression": "2d6+3", "repeat": 2}"#,
+        );
+        assert!(status.contains("200"), "{status}");
+        assert_eq!(body["rolls"].as_array().unwrap().len(), 2);
+
+        let (status, body) = send(addr, "POST", "/dist", r#"{"expression": "d4"}"#);
+        assert!(status.contains("200"), "{status}");
+        assert!((body["mean"].as_f64().unwrap() - 2.5).abs() < 1e-9);
+
+        let (status, body) = send(addr, "GET", "/functions", "");
+        assert!(status.contains("200"), "{status}");
+        assert!(!body.as_array().unwrap().is_empty());
+
+        let (status, body) = send(addr, "POST", "/roll", r#"{"expression": "d6/0"}"#);
+        assert!(status.contains("422"), "{status}");
+        assert_eq!(body["error"]["data"]["kind"], "division_by_zero");
+
+        let (status, _) = send(addr, "GET", "/roll", "");
+        assert!(status.contains("405"), "{status}");
+        let (status, _) = send(addr, "POST", "/nope", "{}");
+        assert!(status.contains("404"), "{status}");
+    }
+
+    #[test]
+    fn origin_test() {
+        let server = Arc::new(Server::http("127.0.0.1:0").unwrap());
+        let addr = server.server_addr().to_ip().unwrap();
+        let config = ServeConfig {
+            allow_origin: vec!["https://overlay.example".into()],
+            ..Default::default()
+        };
+        thread::spawn(move || run(server, &config));
+        let dist = r#"{"expression": "d4"}"#;
+
+        // Other web pages are refused before anything is computed
+        let (head, body) = send_from(addr, Some("https://evil.example"), "POST", "/dist", dist);
+        assert!(head.starts_with("HTTP/1.1 403"), "{head}");
+        assert!(!head.contains("Access-Control-Allow-Origin"), "{head}");
+        assert!(body["mean"].is_null());
+
+        let (head, _) = send_from(addr, Some("https://overlay.example"), "POST", "/dist", dist);
+        assert!(head.starts_with("HTTP/1.1 200"), "{head}");
+        assert!(
+            head.contains("Access-Control-Allow-Origin: https://overlay.example"),
+            "{head}"
+        );
+
+        // Extensions and programs are answered, without allowing any web page to read along
+        let extension = Some("chrome-extension://abcdefgh");
+        let (head, _) = send_from(addr, extension, "POST", "/dist", dist);
+        assert!(head.starts_with("HTTP/1.1 200"), "{head}");
+        let (head, _) = send_from(addr, None, "POST", "/dist", dist);
+        assert!(!head.contains("Access-Control-Allow-Origin"), "{head}");
+    }
+}
@@ -1,14 +1,62 @@
-#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+use std::{path::PathBuf, process::ExitCode};
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
+
+mod batch;
+mod docs;
+mod http;
+mod repl;
+mod report;
+mod roll;
+mod serve;
 
 #[derive(Parser)]
 #[command(name = "Doice")]
 #[command(version)]
 #[command(about = "Rolls nice dice. Once, twice, or thrice")]
 #[command(long_about = None)]
-struct Cli {}
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+    /// YAML or JSON file of random tables, which expressions can then roll on like table(loot)
+    #[arg(long, global = true)]
+    tables: Vec<PathBuf>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Rolls an expression and prints the result
+    Roll(roll::RollArgs),
+    /// Prints the statistics and a histogram of the distribution of an expression
+    Dist(roll::DistArgs),
+    /// Rolls every expression in a file, or reports the statistics of their distributions
+    Batch(batch::BatchArgs),
+    /// Starts an interactive prompt that rolls every line entered
+    Repl(repl::ReplArgs),
+    /// Answers JSON-RPC requests over stdio, or serves an HTTP API, for scripts, plugins and overlays
+    Serve(serve::ServeArgs),
+}
 
-fn main() {
-    let _cli = Cli::parse();
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    for path in &cli.tables {
+        if let Err(err) = doice_roller::load_tables(path) {
+            eprintln!("error: {err}");
+            return ExitCode::FAILURE;
+        }
+    }
+    let res = match cli.command {
+        Command::Roll(args) => roll::roll(&args),
+        Command::Dist(args) => roll::dist(&args),
+        Command::Batch(args) => batch::batch(&args),
+        Command::Repl(args) => repl::repl(&args),
+        Command::Serve(args) => serve::serve(&args),
+    };
+    match res {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {err}");
+            ExitCode::FAILURE
+        }
+    }
 }
@@ -0,0 +1,255 @@
+//! An interactive prompt that rolls every line entered, with line editing, history and completion
+
+use std::{
+    fs,
+    io::{stdout, IsTerminal},
+    path::PathBuf,
+    str::FromStr,
+};
+
+use clap::Args;
+use doice_roller::{Layouter, Roll};
+use egui::Color32;
+use rustyline::{
+    completion::{Completer, Pair},
+    error::ReadlineError,
+    highlight::Highlighter,
+    hint::Hinter,
+    history::DefaultHistory,
+    validate::Validator,
+    Context, Editor, Helper,
+};
+
+use crate::{docs::function_names, report::roll_text, roll};
+
+const PROMPT: &str = "doice> ";
+const COMMANDS: &[(&str, &str)] = &[
+    (
+        ":help",
+        "Lists the commands and functions, or explains a function with :help <fn>",
+    ),
+    (
+        ":dist",
+        "Prints the statistics and a histogram of an expression, like :dist 4d6kh3",
+    ),
+    (
+        ":seed",
+        "Seeds the random number generator, making the following rolls reproducible",
+    ),
+    (":quit", "Leaves the prompt, as does Ctrl-D"),
+];
+
+const RESET: &str = "\x1b[0m";
+const STRIKETHROUGH: &str = "\x1b[9m";
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[31m";
+
+#[derive(Args)]
+pub struct ReplArgs {
+    /// Prints without colors, which is the default when not printing to a terminal
+    #[arg(long)]
+    no_color: bool,
+}
+
+pub fn repl(args: &ReplArgs) -> Result<(), String> {
+    let repl = Repl {
+        color: !args.no_color && stdout().is_terminal(),
+    };
+    let mut editor: Editor<ReplHelper, DefaultHistory> =
+        Editor::new().map_err(|err| err.to_string())?;
+    editor.set_helper(Some(ReplHelper));
+
+    let history = history_path();
+    if let Some(path) = &history {
+        // There is no history yet on the first run
+        let _ = editor.load_history(path);
+    }
+
+    loop {
+        let line = match editor.readline(PROMPT) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(err) => return Err(err.to_string()),
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let _ = editor.add_history_entry(line);
+        if !repl.execute(line) {
+            break;
+        }
+    }
+
+    if let Some(path) = &history {
+        if let Some(dir) = path.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        editor
+            .save_history(path)
+            .map_err(|err| format!("could not save history: {err}"))?;
+    }
+    Ok(())
+}
+
+/// The file that the history of entered lines is kept in between sessions
+fn history_path() -> Option<PathBuf> {
+    Some(dirs::data_dir()?.join("doice").join("history.txt"))
+}
+
+struct Repl {
+    color: bool,
+}
+
+impl Repl {
+    /// Executes a command or rolls an expression, returning whether to carry on
+    fn execute(&self, line: &str) -> bool {
+        let (command, arg) = line.split_once(' ').unwrap_or((line, ""));
+        let arg = arg.trim();
+        let res = match command {
+            ":q" | ":quit" | ":exit" => return false,
+            ":h" | ":help" => {
+                self.help(arg);
+                Ok(())
+            }
+            ":dist" => self.dist(arg),
+            ":seed" => arg
+                .parse()
+                .map(doice_roller::seed)
+                .map_err(|_| format!("invalid seed {arg}, expected a whole number")),
+            _ if command.starts_with(':') => Err(format!("unknown command {command}, see :help")),
+            _ => self.roll(line),
+        };
+        if let Err(err) = res {
+            self.print_error(&err);
+        }
+        true
+    }
+
+    fn roll(&self, src: &str) -> Result<(), String> {
+        let roll = Roll::from_str(src)?;
+        let out = roll.try_roll()?;
+        let txt = if self.color && out.txt.sections.len() <= roll.limits().max_text_sections {
+            ansi(&out.txt)
+        } else {
+            roll_text(&roll, &out)
+        };
+        if self.color {
+            println!("{txt} = {BOLD}{}{RESET}", out.value);
+        } else {
+            println!("{txt} = {}", out.value);
+        }
+        Ok(())
+    }
+
+    fn dist(&self, src: &str) -> Result<(), String> {
+        let dist = Roll::from_str(src)?.try_dist()?;
+        if dist.is_empty() {
+            return Err("the expression has no outcomes".into());
+        }
+        print!("{}", roll::summary(&dist));
+        print!("{}", roll::histogram(&dist));
+        Ok(())
+    }
+
+    fn help(&self, name: &str) {
+        if name.is_empty() {
+            println!("Enter an expression like 2d20kh1+5 to roll it, or one of the commands:");
+            for (command, doc) in COMMANDS {
+                println!("  {command:<6} {doc}");
+            }
+            let names: Vec<_> = function_names().map(|(name, _)| name).collect();
+            println!("Functions: {}", names.join(", "));
+            return;
+        }
+        match function_names().find(|(function, _)| *function == name) {
+            Some((_, doc)) => println!("{doc}"),
+            None => self.print_error(&format!("no function is called {name}")),
+        }
+    }
+
+    fn print_error(&self, err: &str) {
+        if self.color {
+            eprintln!("{RED}error:{RESET} {err}");
+        } else {
+            eprintln!("error: {err}");
+        }
+    }
+}
+
+/// The text of a roll with its colors and strikethrough as ANSI escape codes
+fn ansi(txt: &Layouter) -> String {
+    let mut out = String::new();
+    for (section, format) in &txt.sections {
+        let mut style = String::new();
+        if format.color != Color32::GRAY {
+            let [r, g, b, _] = format.color.to_array();
+            style += &format!("\x1b[38;2;{r};{g};{b}m");
+        }
+        if format.strikethrough.width > 0.0 {
+            style += STRIKETHROUGH;
+        }
+        if style.is_empty() {
+            out += section;
+        } else {
+            out += &format!("{style}{section}{RESET}");
+        }
+    }
+    out
+}
+
+/// Completes function names and, at the start of the line, commands
+struct ReplHelper;
+
+impl Completer for ReplHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let start = line[..pos]
+            .char_indices()
+            .rev()
+            .take_while(|(_, c)| c.is_ascii_alphanumeric() || *c == ':')
+            .last()
+            .map_or(pos, |(i, _)| i);
+        let word = &line[start..pos];
+        if word.is_empty() {
+            return Ok((pos, Vec::new()));
+        }
+
+        let candidates = if start == 0 && word.starts_with(':') {
+            COMMANDS
+                .iter()
+                .filter(|(command, _)| command.starts_with(word))
+                .map(|(command, _)| Pair {
+                    display: command.to_string(),
+                    replacement: format!("{command} "),
+                })
+                .collect()
+        } else {
+            function_names()
+                .filter(|(name, _)| name.starts_with(word))
+                .map(|(name, _)| Pair {
+                    display: name.to_string(),
+                    replacement: format!("{name}("),
+                })
+                .collect()
+        };
+        Ok((start, candidates))
+    }
+}
+
+impl Hinter for ReplHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ReplHelper {}
+
+impl Validator for ReplHelper {}
+
+impl Helper for ReplHelper {}
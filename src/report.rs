@@ -0,0 +1,128 @@
+//! Serializable summaries of rolls and distributions, for machine-readable output
+
+use doice_roller::{DiceError, ErrorKind, Layouter, ProbDist, Roll, RollOut, Value};
+use serde::Serialize;
+
+#[derive(Serialize)]
+pub struct RollReport {
+    pub value: Value,
+    pub text: String,
+    /// The named values of a multi-valued roll, left out for rolls with a single value
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub components: Vec<ComponentReport>,
+}
+
+#[derive(Serialize)]
+pub struct ComponentReport {
+    pub name: String,
+    pub value: Value,
+}
+
+impl RollReport {
+    /// Summarizes the result of rolling roll, with its text cut short like in the GUI if it gets too long
+    pub fn new(roll: &Roll, out: &RollOut) -> Self {
+        RollReport {
+            value: out.value,
+            text: roll_text(roll, out),
+            components: out
+                .components
+                .iter()
+                .map(|(name, value)| ComponentReport {
+                    name: name.clone(),
+                    value: *value,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// The text of a roll without its value, which is replaced by "[...]" if it has too many sections
+pub fn roll_text(roll: &Roll, out: &RollOut) -> String {
+    if out.txt.sections.len() > roll.limits().max_text_sections {
+        return Layouter::from("[...]").to_string();
+    }
+    out.txt.to_string()
+}
+
+#[derive(Serialize)]
+pub struct StatsReport {
+    pub mean: f64,
+    pub sigma: f64,
+    pub min: Option<Value>,
+    pub max: Option<Value>,
+    /// Present if the distribution was approximated by sampling rather than computed exactly
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub approximation: Option<ApproximationReport>,
+}
+
+#[derive(Serialize)]
+pub struct ApproximationReport {
+    pub samples: usize,
+    pub max_error: f64,
+    pub mean_error: f64,
+}
+
+impl From<&ProbDist> for StatsReport {
+    fn from(dist: &ProbDist) -> Self {
+        StatsReport {
+            mean: dist.expectation(),
+            sigma: dist.sigma(),
+            min: dist.min(),
+            max: dist.max(),
+            approximation: dist.approximation().map(|approx| ApproximationReport {
+                samples: approx.samples,
+                max_error: approx.max_error,
+                mean_error: approx.mean_error,
+            }),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct DistReport {
+    #[serde(flatten)]
+    pub stats: StatsReport,
+    pub probabilities: Vec<OutcomeReport>,
+}
+
+#[derive(Serialize)]
+pub struct OutcomeReport {
+    pub outcome: Value,
+    pub probability: f64,
+}
+
+impl From<&ProbDist> for DistReport {
+    fn from(dist: &ProbDist) -> Self {
+        DistReport {
+            stats: dist.into(),
+            probabilities: dist
+                .iter()
+                .map(|(&outcome, &probability)| OutcomeReport {
+                    outcome,
+                    probability,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct ErrorReport {
+    /// What went wrong, one of invalid, division_by_zero, overflow and resource_limit
+    pub kind: &'static str,
+    pub message: String,
+}
+
+impl From<&DiceError> for ErrorReport {
+    fn from(err: &DiceError) -> Self {
+        ErrorReport {
+            kind: match err.kind() {
+                ErrorKind::Invalid => "invalid",
+                ErrorKind::DivisionByZero => "division_by_zero",
+                ErrorKind::Overflow => "overflow",
+                ErrorKind::ResourceLimit => "resource_limit",
+            },
+            message: err.to_string(),
+        }
+    }
+}
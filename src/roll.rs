@@ -0,0 +1,222 @@
+//! The roll and dist commands, which evaluate a single expression
+
+use std::{fmt::Write, path::PathBuf, str::FromStr};
+
+use clap::Args;
+use doice_chart::ChartOptions;
+use doice_roller::{ProbDist, Roll, Value};
+use serde_json::json;
+
+use crate::report::{roll_text, DistReport, RollReport};
+
+/// Length of the bar of the most likely row of a histogram
+const BAR_WIDTH: usize = 50;
+/// Histograms of distributions with more outcomes than this group neighbouring outcomes into a single row
+const MAX_ROWS: usize = 40;
+
+#[derive(Args)]
+pub struct RollArgs {
+    /// The expression to roll, like "2d6+3"
+    expression: String,
+    /// Number of times to roll the expression
+    #[arg(short = 'n', long, default_value_t = 1)]
+    repeat: usize,
+    /// Seed for the random number generator, making the rolls reproducible
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Prints the rolls as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Args)]
+pub struct DistArgs {
+    /// The expression to analyze, like "4d6kh3"
+    expression: String,
+    /// Seed for the random number generator, which is used by distributions that are approximated by sampling
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Prints the distribution as JSON
+    #[arg(long)]
+    json: bool,
+    /// Also draws the distribution as a chart, saved as svg or png depending on the extension
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    /// Draws the chance to roll at least each outcome on the chart
+    #[arg(long, requires = "output")]
+    cumulative: bool,
+    /// Marks a DC on the chart, along with the chance to meet it
+    #[arg(long, requires = "output", allow_hyphen_values = true)]
+    dc: Option<Value>,
+}
+
+/// Parses the expression after seeding the generator, as parsing may already roll
+fn parse(expression: &str, seed: Option<u64>) -> Result<Roll, String> {
+    if let Some(seed) = seed {
+        doice_roller::seed(seed);
+    }
+    Ok(Roll::from_str(expression)?)
+}
+
+pub fn roll(args: &RollArgs) -> Result<(), String> {
+    let roll = parse(&args.expression, args.seed)?;
+    let outs = (0..args.repeat)
+        .map(|_| roll.try_roll())
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if args.json {
+        let rolls: Vec<_> = outs.iter().map(|out| RollReport::new(&roll, out)).collect();
+        let report = json!({ "expression": args.expression, "rolls": rolls });
+        println!("{report:#}");
+    } else {
+        for out in &outs {
+            println!("{} = {}", roll_text(&roll, out), out.value);
+        }
+    }
+    Ok(())
+}
+
+pub fn dist(args: &DistArgs) -> Result<(), String> {
+    let roll = parse(&args.expression, args.seed)?;
+    let dist = roll.try_dist()?;
+    if dist.is_empty() {
+        return Err("the expression has no outcomes".into());
+    }
+
+    if args.json {
+        let report = json!({ "expression": args.expression, "dist": DistReport::from(&dist) });
+        println!("{report:#}");
+    } else {
+        print!("{}", summary(&dist));
+        print!("{}", histogram(&dist));
+    }
+
+    if let Some(path) = &args.output {
+        let options = ChartOptions {
+            title: Some(args.expression.clone()),
+            cumulative: args.cumulative,
+            dc: args.dc,
+            ..Default::default()
+        };
+        doice_chart::save(&dist, &options, path)?;
+    }
+    Ok(())
+}
+
+/// The statistics of a non-empty distribution, one per line
+pub fn summary(dist: &ProbDist) -> String {
+    let mut out = String::new();
+    let (min, max) = (dist.min().unwrap_or(0), dist.max().unwrap_or(0));
+    writeln!(out, "mean:  {:.3}", dist.expectation()).unwrap();
+    writeln!(out, "sigma: {:.3}", dist.sigma()).unwrap();
+    writeln!(out, "range: {min} to {max}").unwrap();
+    if let Some(approx) = dist.approximation() {
+        writeln!(
+            out,
+            "approximated from {} samples, to within {:.2}%",
+            approx.samples,
+            100.0 * approx.max_error
+        )
+        .unwrap();
+    }
+    out
+}
+
+/// Draws a non-empty distribution as horizontal bars, one row per outcome or per group of outcomes
+pub fn histogram(dist: &ProbDist) -> String {
+    let (min, max) = (dist.min().unwrap_or(0), dist.max().unwrap_or(0));
+    let outcomes = max.abs_diff(min).saturating_add(1);
+    let width = outcomes.div_ceil(MAX_ROWS);
+
+    let mut rows: Vec<(String, f64)> = Vec::new();
+    let mut start = min;
+    loop {
+        let end = start.saturating_add((width - 1) as Value).min(max);
+        let prob = dist
+            .range(start..=end)
+            .fold(0.0, |acc, (_, prob)| acc + prob);
+        let label = if start == end {
+            start.to_string()
+        } else {
+            format!("{start}..{end}")
+        };
+        rows.push((label, prob));
+        if end == max {
+            break;
+        }
+        start = end + 1;
+    }
+
+    let label_width = rows.iter().map(|(label, _)| label.len()).max().unwrap_or(0);
+    let peak = rows.iter().map(|(_, prob)| *prob).fold(0.0, f64::max);
+    let mut out = String::new();
+    for (label, prob) in rows {
+        let bar = "#".repeat((prob / peak * BAR_WIDTH as f64).round() as usize);
+        writeln!(
+            out,
+            "{label:>label_width$} | {bar:<BAR_WIDTH$} {:>6.2}%",
+            100.0 * prob
+        )
+        .unwrap();
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dist(src: &str) -> ProbDist {
+        Roll::from_str(src).unwrap().try_dist().unwrap()
+    }
+
+    #[test]
+    fn seed_test() {
+        let output = |src| {
+            let dist = parse(src, Some(42)).unwrap().try_dist().unwrap();
+            format!("{}{}", summary(&dist), histogram(&dist))
+        };
+        let src = "draw(standard, 3) + 4d6kh3";
+        assert_eq!(output(src), output(src));
+
+        let rolls = || {
+            let roll = parse(src, Some(42)).unwrap();
+            (0..10)
+                .map(|_| roll.try_roll().unwrap().value)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(rolls(), rolls());
+    }
+
+    #[test]
+    fn summary_test() {
+        assert_eq!(
+            summary(&dist("2d6")),
+            "mean:  7.000\nsigma: 2.415\nrange: 2 to 12\n"
+        );
+    }
+
+    #[test]
+    fn histogram_test() {
+        // Every outcome gets a row of its own
+        let rows = histogram(&dist("d6"));
+        let labels: Vec<_> = rows
+            .lines()
+            .map(|row| row.split(" | ").next().unwrap())
+            .collect();
+        assert_eq!(labels, ["1", "2", "3", "4", "5", "6"]);
+        assert!(rows.lines().all(|row| row.ends_with(" 16.67%")), "{rows}");
+
+        // 100 outcomes are grouped by 3, leaving a single outcome for the last row
+        let rows = histogram(&dist("d100"));
+        let rows: Vec<_> = rows.lines().collect();
+        assert_eq!(rows.len(), 34);
+        assert!(rows[0].starts_with("  1..3 | "), "{}", rows[0]);
+        assert!(rows[0].ends_with("   3.00%"), "{}", rows[0]);
+        assert!(rows[33].starts_with("   100 | "), "{}", rows[33]);
+        assert!(rows[33].ends_with("   1.00%"), "{}", rows[33]);
+
+        let rows = histogram(&dist("5"));
+        assert_eq!(rows, format!("5 | {} 100.00%\n", "#".repeat(BAR_WIDTH)));
+    }
+}
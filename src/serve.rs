@@ -0,0 +1,357 @@
+//! Machine interfaces to the roller, which answer JSON-RPC 2.0 requests
+
+use std::{
+    io::{self, BufRead, Write},
+    net::SocketAddr,
+    time::Duration,
+};
+
+use clap::Args;
+use doice_roller::{DiceError, EvalLimits, Roll};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Value};
+
+use crate::{
+    docs::function_names,
+    http,
+    report::{DistReport, ErrorReport, RollReport, StatsReport},
+};
+
+#[derive(Args)]
+pub struct ServeArgs {
+    /// Reads a JSON-RPC request from every line of stdin, and writes the responses to stdout
+    #[arg(long, conflicts_with = "http")]
+    stdio: bool,
+    /// Serves an HTTP API on the address, like 127.0.0.1:8080
+    #[arg(long)]
+    http: Option<SocketAddr>,
+    #[command(flatten)]
+    config: ServeConfig,
+}
+
+/// Limits on what a single request may do and who may make it, which apply to every interface
+#[derive(Args, Clone, Debug)]
+pub struct ServeConfig {
+    /// Most dice a single expression may roll
+    #[arg(long, default_value_t = 10_000)]
+    pub max_dice: usize,
+    /// Most faces a single die may have
+    #[arg(long, default_value_t = 10_000)]
+    pub max_faces: usize,
+    /// Most outcomes a distribution may have
+    #[arg(long, default_value_t = 100_000)]
+    pub max_support: usize,
+    /// Milliseconds that computing a distribution may take, before falling back to sampling
+    #[arg(long, default_value_t = 2_000)]
+    pub time_budget_ms: u64,
+    /// Most times a single request may roll an expression
+    #[arg(long, default_value_t = 1_000)]
+    pub max_repeat: usize,
+    /// Origin of a web page that may call the HTTP API, like https://example.com, can be given several times.
+    /// Requests from other web pages are refused, while browser extensions and other programs are always answered
+    #[arg(long)]
+    pub allow_origin: Vec<String>,
+}
+
+impl ServeConfig {
+    pub fn eval_limits(&self) -> EvalLimits {
+        EvalLimits {
+            max_dice: self.max_dice,
+            max_faces: self.max_faces,
+            max_support: self.max_support,
+            time_budget: Duration::from_millis(self.time_budget_ms),
+            ..Default::default()
+        }
+    }
+
+    fn parse(&self, src: &str) -> Result<Roll, DiceError> {
+        Roll::parse_with_limits(src, self.eval_limits())
+    }
+}
+
+impl Default for ServeConfig {
+    fn default() -> Self {
+        ServeConfig {
+            max_dice: 10_000,
+            max_faces: 10_000,
+            max_support: 100_000,
+            time_budget_ms: 2_000,
+            max_repeat: 1_000,
+            allow_origin: Vec::new(),
+        }
+    }
+}
+
+pub fn serve(args: &ServeArgs) -> Result<(), String> {
+    if args.stdio {
+        serve_stdio(&args.config).map_err(|err| err.to_string())
+    } else if let Some(addr) = args.http {
+        http::serve(addr, &args.config)
+    } else {
+        Err("nothing to serve on, pass --stdio or --http <address>".into())
+    }
+}
+
+fn serve_stdio(config: &ServeConfig) -> io::Result<()> {
+    let mut stdout = io::stdout().lock();
+    for line in io::stdin().lock().lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(response) = respond(&line, config) {
+            writeln!(stdout, "{response}")?;
+            stdout.flush()?;
+        }
+    }
+    Ok(())
+}
+
+/// An error as it appears in a JSON-RPC response
+#[derive(Debug)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+    pub data: Option<Value>,
+}
+
+impl RpcError {
+    pub const PARSE_ERROR: i64 = -32700;
+    pub const INVALID_REQUEST: i64 = -32600;
+    pub const METHOD_NOT_FOUND: i64 = -32601;
+    pub const INVALID_PARAMS: i64 = -32602;
+    /// The expression could not be parsed or evaluated
+    pub const DICE_ERROR: i64 = -32000;
+
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        RpcError {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        let mut err = json!({ "code": self.code, "message": self.message });
+        if let Some(data) = &self.data {
+            err["data"] = data.clone();
+        }
+        err
+    }
+}
+
+impl From<DiceError> for RpcError {
+    fn from(err: DiceError) -> Self {
+        let report = ErrorReport::from(&err);
+        RpcError {
+            code: RpcError::DICE_ERROR,
+            message: report.message.clone(),
+            data: Some(json!(report)),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Request {
+    jsonrpc: String,
+    /// Requests without an id are notifications, which get no response
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Deserialize)]
+struct ExpressionParams {
+    expression: String,
+}
+
+#[derive(Deserialize)]
+struct RollParams {
+    expression: String,
+    #[serde(default = "one")]
+    repeat: usize,
+    seed: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct StatsParams {
+    expression: String,
+    /// Also reports the chance to roll at least the DC
+    dc: Option<isize>,
+}
+
+fn one() -> usize {
+    1
+}
+
+/// Answers a line holding a JSON-RPC request, unless the request is a notification
+pub fn respond(line: &str, config: &ServeConfig) -> Option<Value> {
+    let request: Value = match serde_json::from_str(line) {
+        Ok(request) => request,
+        Err(err) => {
+            let err = RpcError::new(RpcError::PARSE_ERROR, err.to_string());
+            return Some(json!({ "jsonrpc": "2.0", "id": null, "error": err.to_json() }));
+        }
+    };
+    let request = match serde_json::from_value::<Request>(request.clone()) {
+        Ok(request) if request.jsonrpc == "2.0" => request,
+        _ => {
+            let err = RpcError::new(RpcError::INVALID_REQUEST, "invalid JSON-RPC 2.0 request");
+            let id = request.get("id").cloned().unwrap_or(Value::Null);
+            return Some(json!({ "jsonrpc": "2.0", "id": id, "error": err.to_json() }));
+        }
+    };
+
+    let res = call(&request.method, request.params, config);
+    let id = request.id?;
+    Some(match res {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(err) => json!({ "jsonrpc": "2.0", "id": id, "error": err.to_json() }),
+    })
+}
+
+/// Executes a method with its params, independent of how the request came in
+pub fn call(method: &str, params: Value, config: &ServeConfig) -> Result<Value, RpcError> {
+    match method {
+        "roll" => {
+            let params: RollParams = parse_params(params)?;
+            if params.repeat > config.max_repeat {
+                return Err(RpcError::new(
+                    RpcError::INVALID_PARAMS,
+                    format!("repeat may be at most {}", config.max_repeat),
+                ));
+            }
+            let roll = config.parse(&params.expression)?;
+            let roll_all = || {
+                (0..params.repeat)
+                    .map(|_| roll.try_roll().map(|out| RollReport::new(&roll, &out)))
+                    .collect::<Result<Vec<_>, _>>()
+            };
+            // The seed only applies to this request, later ones on the same thread roll randomly again
+            let rolls = match params.seed {
+                Some(seed) => doice_roller::with_seed(seed, roll_all),
+                None => roll_all(),
+            }?;
+            Ok(json!({ "expression": params.expression, "rolls": rolls }))
+        }
+        "dist" => {
+            let params: ExpressionParams = parse_params(params)?;
+            let dist = config.parse(&params.expression)?.try_dist()?;
+            Ok(json!(DistReport::from(&dist)))
+        }
+        "stats" => {
+            let params: StatsParams = parse_params(params)?;
+            let dist = config.parse(&params.expression)?.try_dist()?;
+            let mut stats = json!(StatsReport::from(&dist));
+            if let Some(dc) = params.dc {
+                stats["success"] = json!(dist.range(dc..).fold(0.0, |acc, (_, prob)| acc + prob));
+            }
+            Ok(stats)
+        }
+        "validate" => {
+            let params: ExpressionParams = parse_params(params)?;
+            Ok(match config.parse(&params.expression) {
+                Ok(_) => json!({ "valid": true }),
+                Err(err) => json!({ "valid": false, "error": ErrorReport::from(&err) }),
+            })
+        }
+        "list_functions" => Ok(function_names()
+            .map(|(name, doc)| json!({ "name": name, "doc": doc }))
+            .collect()),
+        _ => Err(RpcError::new(
+            RpcError::METHOD_NOT_FOUND,
+            format!("unknown method {method}"),
+        )),
+    }
+}
+
+fn parse_params<T: DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    serde_json::from_value(params)
+        .map_err(|err| RpcError::new(RpcError::INVALID_PARAMS, err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(request: Value) -> Value {
+        respond(&request.to_string(), &ServeConfig::default()).unwrap()
+    }
+
+    #[test]
+    fn methods_test() {
+        let res = request(json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "roll",
+            "params": { "expression": "2d6+3", "repeat": 3, "seed": 7 }
+        }));
+        assert_eq!(res["id"], 1);
+        assert_eq!(res["result"]["rolls"].as_array().unwrap().len(), 3);
+        let again = request(json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "roll",
+            "params": { "expression": "2d6+3", "repeat": 3, "seed": 7 }
+        }));
+        assert_eq!(again, res);
+
+        let res = request(json!({
+            "jsonrpc": "2.0",
+            "id": "stats",
+            "method": "stats",
+            "params": { "expression": "d20", "dc": 11 }
+        }));
+        assert!((res["result"]["mean"].as_f64().unwrap() - 10.5).abs() < 1e-9);
+        assert!((res["result"]["success"].as_f64().unwrap() - 0.5).abs() < 1e-9);
+
+        let res = request(json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "validate",
+            "params": { "expression": "5/0" }
+        }));
+        assert_eq!(res["result"]["valid"], true);
+
+        let res = request(json!({ "jsonrpc": "2.0", "id": 3, "method": "list_functions" }));
+        assert!(res["result"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|function| function["name"] == "save"));
+    }
+
+    #[test]
+    fn errors_test() {
+        assert_eq!(
+            respond("{", &ServeConfig::default()).unwrap()["error"]["code"],
+            RpcError::PARSE_ERROR
+        );
+        let res = request(json!({ "jsonrpc": "2.0", "id": 1, "method": "explode" }));
+        assert_eq!(res["error"]["code"], RpcError::METHOD_NOT_FOUND);
+        let res = request(json!({ "jsonrpc": "2.0", "id": 1, "method": "dist", "params": {} }));
+        assert_eq!(res["error"]["code"], RpcError::INVALID_PARAMS);
+
+        let res = request(json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "roll",
+            "params": { "expression": "d6/0" }
+        }));
+        assert_eq!(res["error"]["data"]["kind"], "division_by_zero");
+
+        let res = request(json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "dist",
+            "params": { "expression": "20000d6" }
+        }));
+        assert_eq!(res["error"]["data"]["kind"], "resource_limit");
+
+        // Notifications are executed, but not answered
+        let notification =
+            json!({ "jsonrpc": "2.0", "method": "roll", "params": { "expression": "d6" } });
+        assert!(respond(&notification.to_string(), &ServeConfig::default()).is_none());
+    }
+}